@@ -0,0 +1,633 @@
+use super::echo_ai::{AgentInput, EchoAgent};
+use crate::cfr::decision::{DecisionMatrices, DecisionVector, Scope};
+use crate::cfr::decision_index::DecisionIndex;
+use crate::cfr::generate::GenerationContext;
+use crate::cfr::hidden_index::HiddenIndex;
+use crate::cfr::phase::PhaseTag;
+use crate::cfr::reveal_index::RevealIndex;
+use crate::cfr::scope_cursor::ScopeCursor;
+use crate::cfr::train::TrainingContext;
+use crate::game::known_state::KnownState;
+use crate::game::known_state_summary::KnownStateEssentials;
+use crate::game::rules::RuleSet;
+use crate::game::types::{Player, Score};
+use crate::helpers::bitfield::Bitfield;
+use bumpalo::Bump;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use tracing::Level;
+
+// {{{ Action selection
+/// The way a blueprint agent turns a strategy into a single decision.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ActionSelection {
+    /// Samples a decision from the average strategy.
+    #[default]
+    Sample,
+    /// Always takes the most likely decision of the average strategy.
+    Argmax,
+}
+
+impl ActionSelection {
+    /// Picks a decision from the given node.
+    pub fn select<R: Rng>(self, node: &DecisionVector, rng: &mut R) -> DecisionIndex {
+        match self {
+            Self::Sample => DecisionIndex(node.random_action(rng)),
+            Self::Argmax => {
+                let strategy = node.get_average_strategy();
+                let index = strategy
+                    .iter()
+                    .enumerate()
+                    .max_by(|a, b| a.1.total_cmp(b.1))
+                    .map_or(0, |(index, _)| index);
+
+                DecisionIndex(index)
+            }
+        }
+    }
+}
+// }}}
+// {{{ Fallback policies
+/// The reason a blueprint agent could not use it's blueprint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FallbackReason {
+    /// The game went past the turn horizon the blueprint was generated for.
+    Unexplored,
+    /// The blueprint considers the game over (usually because of a
+    /// hopeless surrender), but the game is still going.
+    Completed,
+}
+
+impl FallbackReason {
+    /// Returns the reason we cannot play from a given scope (if any).
+    fn from_scope(scope: Option<&Scope>) -> Option<Self> {
+        match scope {
+            Some(Scope::Explored(_)) => None,
+            Some(Scope::Completed(_)) => Some(Self::Completed),
+            Some(Scope::Unexplored(_)) | None => Some(Self::Unexplored),
+        }
+    }
+}
+
+/// What a blueprint agent does once the game leaves the trained tree.
+pub enum Fallback<'a, A> {
+    /// Hands every decision over to another agent.
+    /// The agent is kept informed about every reveal, even while the
+    /// blueprint is still in use, so it can keep track of the game.
+    Agent(A),
+
+    /// Generates and trains the remainder of the game from the current position,
+    /// and keeps following the resulting tree until the game leaves it again.
+    /// Every combination of hidden information is assumed to be equally likely,
+    /// so the history of the game is ignored.
+    ///
+    /// The trees are kept in the given allocator, which thus keeps growing
+    /// for as long as the agent gets used. Positions with more than `max_turns`
+    /// turns left are played at random.
+    Resolve {
+        max_turns: usize,
+        iterations: usize,
+        allocator: &'a Bump,
+    },
+
+    /// Looks for the trained state most similar to the current one, and continues
+    /// playing from there. The scopes must have been generated from the given states
+    /// (starting in the main phase), and only states matching the current
+    /// graveyard, edicts and seer status are ever considered.
+    ///
+    /// Positions with no such state are played at random.
+    Nearest(Vec<(KnownState, &'a Scope<'a>)>),
+}
+
+impl<'a, A> Fallback<'a, A> {
+    /// Short human readable name used for logging.
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Agent(_) => "agent",
+            Self::Resolve { .. } => "resolve",
+            Self::Nearest(_) => "nearest",
+        }
+    }
+}
+
+/// Measures how different two states with matching summaries are.
+fn state_distance(a: &KnownState, b: &KnownState) -> usize {
+    let score_distance = (a.score.0 as i16 - b.score.0 as i16).unsigned_abs() as usize;
+    let effect_distance: usize = Player::PLAYERS
+        .map(|player| {
+            let [a, b] = [a, b].map(|s| player.select(s.player_states).effects);
+            (a - b).len() + (b - a).len()
+        })
+        .into_iter()
+        .sum();
+    let battlefield_distance = (a.battlefields.current..4)
+        .filter(|i| a.battlefields.all[*i] != b.battlefields.all[*i])
+        .count();
+
+    score_distance + effect_distance + battlefield_distance
+}
+// }}}
+// {{{ Agent
+/// An echo agent which plays according to a trained strategy,
+/// walking down the scope tree as information gets revealed.
+///
+/// Once the game leaves the explored part of the tree
+/// (for instance, after the turn horizon is reached),
+/// the agent falls back to the configured policy.
+pub struct CfrAgent<'a, R, A> {
+    root: ScopeCursor<'a, 'a>,
+
+    /// Follows the game down the tree.
+    /// Is `None` if the game is no longer consistent with the tree.
+    cursor: Option<ScopeCursor<'a, 'a>>,
+    selection: ActionSelection,
+    fallback: Fallback<'a, A>,
+    rules: RuleSet,
+    rng: R,
+
+    /// Whether we've already reported falling back since leaving the tree.
+    reported_fallback: bool,
+}
+
+impl<'a, R: Rng, A: EchoAgent> CfrAgent<'a, R, A> {
+    /// Creates an agent playing from the scope the given cursor points to.
+    /// Games must start from the state and phase of said cursor.
+    pub fn new(
+        root: ScopeCursor<'a, 'a>,
+        selection: ActionSelection,
+        fallback: Fallback<'a, A>,
+        rng: R,
+    ) -> Self {
+        Self {
+            root,
+            cursor: Some(root),
+            selection,
+            fallback,
+            rules: RuleSet::default(),
+            rng,
+            reported_fallback: false,
+        }
+    }
+
+    /// Plays under some variant of the rules. The blueprint should
+    /// have been generated using the same rules.
+    pub fn with_rules(mut self, rules: RuleSet) -> Self {
+        self.rules = rules;
+        self
+    }
+
+    /// Returns true if the agent is still inside the explored part of the tree.
+    #[inline(always)]
+    pub fn is_in_tree(&self) -> bool {
+        self.fallback_reason().is_none()
+    }
+
+    /// Returns the reason the blueprint cannot be used right now (if any).
+    #[inline(always)]
+    pub fn fallback_reason(&self) -> Option<FallbackReason> {
+        FallbackReason::from_scope(self.cursor.and_then(|cursor| cursor.scope()))
+    }
+
+    /// Picks a decision using the strategy stored in some decision matrices.
+    fn decide_from(
+        matrices: &DecisionMatrices,
+        agent_input: AgentInput,
+        selection: ActionSelection,
+        rng: &mut R,
+    ) -> DecisionIndex {
+        let hidden =
+            HiddenIndex::encode(&agent_input.state, agent_input.player, agent_input.hidden);
+
+        match matrices.get_matrix(agent_input.player).get_node(hidden) {
+            Some(node) => {
+                debug_assert_eq!(
+                    node.len(),
+                    agent_input
+                        .player
+                        .select(agent_input.phase.decision_counts(&agent_input.state))
+                );
+
+                selection.select(node, rng)
+            }
+            None => DecisionIndex::default(),
+        }
+    }
+
+    /// Picks a decision uniformly at random.
+    fn random_decision(&mut self, agent_input: AgentInput) -> DecisionIndex {
+        let counts = agent_input.phase.decision_counts(&agent_input.state);
+        let count = agent_input.player.select(counts);
+
+        DecisionIndex(self.rng.gen_range(0..count))
+    }
+
+    /// Solves the remainder of the game on the fly, and continues
+    /// playing from the resulting tree.
+    /// Returns `None` if the game is too long to solve.
+    fn resolve(&mut self, agent_input: AgentInput) -> Option<DecisionIndex> {
+        let Fallback::Resolve {
+            max_turns,
+            iterations,
+            allocator,
+        } = self.fallback
+        else {
+            return None;
+        };
+
+        let turns = 4 - agent_input.state.battlefields.current;
+
+        if turns > max_turns {
+            return None;
+        }
+
+        let rules = self.rules.with_hopeless_surrenders(true);
+        let generator = GenerationContext::new(turns, agent_input.state, rules, allocator);
+        let scope = allocator.alloc(generator.generate_from(agent_input.phase));
+
+        TrainingContext::new(false).cfr_from(
+            scope,
+            agent_input.phase,
+            agent_input.state.to_summary(),
+            iterations,
+        );
+
+        let cursor = ScopeCursor::new(scope, agent_input.state, agent_input.phase);
+        self.cursor = Some(cursor);
+        self.reported_fallback = false;
+
+        Some(Self::decide_from(
+            cursor.matrices()?,
+            agent_input,
+            self.selection,
+            &mut self.rng,
+        ))
+    }
+
+    /// Attempts to continue playing from the most similar trained state.
+    fn try_reenter(&mut self, agent_input: AgentInput) {
+        let Fallback::Nearest(library) = &self.fallback else {
+            return;
+        };
+
+        if agent_input.phase.tag() != PhaseTag::Main {
+            return;
+        }
+
+        let summary = agent_input.state.to_summary();
+        let nearest = library
+            .iter()
+            .filter(|(state, _)| {
+                state.to_summary() == summary
+                    && state.battlefields.current == agent_input.state.battlefields.current
+            })
+            .min_by_key(|(state, _)| state_distance(state, &agent_input.state));
+
+        if let Some((state, scope)) = nearest {
+            tracing::event!(
+                Level::INFO,
+                distance = state_distance(state, &agent_input.state),
+                "Blueprint agent continues from the nearest trained state"
+            );
+
+            self.cursor = Some(ScopeCursor::new(
+                scope,
+                agent_input.state,
+                agent_input.phase,
+            ));
+            self.reported_fallback = false;
+        }
+    }
+}
+
+impl<'a, R: Rng, A: EchoAgent> EchoAgent for CfrAgent<'a, R, A> {
+    fn choose(&mut self, agent_input: AgentInput) -> DecisionIndex {
+        if !self.is_in_tree() {
+            self.try_reenter(agent_input);
+        }
+
+        if let Some(matrices) = self.cursor.and_then(|cursor| cursor.matrices()) {
+            return Self::decide_from(matrices, agent_input, self.selection, &mut self.rng);
+        }
+
+        // {{{ Fallback
+        let reason = self.fallback_reason();
+        let level = if self.reported_fallback {
+            Level::DEBUG
+        } else {
+            Level::INFO
+        };
+
+        self.reported_fallback = true;
+
+        // Macros from tracing require the level to be a constant
+        if level == Level::INFO {
+            tracing::event!(
+                Level::INFO,
+                ?reason,
+                policy = self.fallback.name(),
+                turn = agent_input.state.battlefields.current,
+                phase = ?agent_input.phase.tag(),
+                "Blueprint agent left the trained tree, falling back"
+            );
+        } else {
+            tracing::event!(
+                Level::DEBUG,
+                ?reason,
+                policy = self.fallback.name(),
+                phase = ?agent_input.phase.tag(),
+                "Blueprint agent falling back"
+            );
+        }
+
+        let decision = match &mut self.fallback {
+            Fallback::Agent(agent) => Some(agent.choose(agent_input)),
+            Fallback::Resolve { .. } => self.resolve(agent_input),
+            Fallback::Nearest(_) => None,
+        };
+
+        decision.unwrap_or_else(|| {
+            tracing::event!(
+                Level::DEBUG,
+                policy = self.fallback.name(),
+                "Fallback policy failed, playing at random"
+            );
+
+            self.random_decision(agent_input)
+        })
+        // }}}
+    }
+
+    fn reveal_info(&mut self, reveal_index: RevealIndex, updated_score: Score) {
+        // Games are usually played without hopeless surrenders. If the tree thinks
+        // the game should have ended, the cursor ends up in a completed scope.
+        let rules = self.rules;
+        self.cursor = self
+            .cursor
+            .and_then(|cursor| cursor.advance(reveal_index, rules)?.get_unfinished());
+
+        if let Fallback::Agent(agent) = &mut self.fallback {
+            agent.reveal_info(reveal_index, updated_score);
+        }
+    }
+
+    fn game_finished(&mut self) {
+        self.cursor = Some(self.root);
+        self.reported_fallback = false;
+
+        if let Fallback::Agent(agent) = &mut self.fallback {
+            agent.game_finished();
+        }
+    }
+}
+// }}}
+// {{{ Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::always_zero_agent::AlwaysZeroAgent;
+    use crate::ai::echo_ai::EchoRunner;
+    use crate::ai::random_agent::RandomAgent;
+    use crate::cfr::decision::DecisionVector;
+    use crate::cfr::generate::GenerationContext;
+    use crate::cfr::phase::{MainPhase, PerPhase, Phase};
+    use crate::cfr::train::fixtures::{generated_scope, rules, trained_scope};
+    use crate::cfr::train::TrainingContext;
+    use crate::game::battlefield::Battlefield;
+    use crate::game::creature::Creature;
+    use crate::game::edict::Edict;
+    use crate::game::known_state::KnownState;
+    use crate::game::notation::Position;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    type Agent<'a> = CfrAgent<'a, StdRng, AlwaysZeroAgent>;
+
+    /// Creates a state at the start of the given turn,
+    /// with the first few creatures and edicts already played.
+    fn state_on_turn(turn: usize) -> KnownState {
+        let mut state = KnownState::new_starting([Battlefield::Plains; 4]);
+        state.battlefields.current = turn;
+        for creature in Creature::CREATURES.into_iter().take(2 * turn) {
+            state.graveyard.insert(creature);
+        }
+
+        for state in state.player_states.iter_mut() {
+            for edict in Edict::EDICTS.into_iter().take(turn) {
+                state.edicts.remove(edict);
+            }
+        }
+
+        state
+    }
+
+    fn blueprint_state() -> KnownState {
+        Position::parse("PPPL 2 +0 WSRB RSG/RSG -/-").unwrap().state
+    }
+
+    /// Follows the blueprint down to the start of the next turn,
+    /// which lies past its horizon.
+    fn past_the_horizon<'a>(scope: &'a Scope<'a>) -> ScopeCursor<'a, 'a> {
+        let phase = PerPhase::Main(MainPhase::new());
+        let mut cursor = ScopeCursor::new(scope, blueprint_state(), phase);
+
+        while cursor.is_explored() {
+            let (_, child) = cursor.children(rules()).next().unwrap();
+            cursor = child;
+        }
+
+        cursor
+    }
+
+    /// Creates an agent which plays the first decision once it leaves the tree.
+    fn blueprint_agent<'a>(
+        scope: &'a Scope<'a>,
+        state: KnownState,
+        selection: ActionSelection,
+    ) -> Agent<'a> {
+        let root = ScopeCursor::new(scope, state, PerPhase::Main(MainPhase::new()));
+        let fallback = Fallback::Agent(AlwaysZeroAgent::default());
+        CfrAgent::new(root, selection, fallback, StdRng::seed_from_u64(0))
+    }
+
+    /// Keeps track of whether the inner agent is in the tree before every decision.
+    struct Tracked<'r, 'a> {
+        agent: &'r mut Agent<'a>,
+        in_tree: &'r mut Vec<bool>,
+    }
+
+    impl<'r, 'a> EchoAgent for Tracked<'r, 'a> {
+        fn choose(&mut self, agent_input: AgentInput) -> DecisionIndex {
+            self.in_tree.push(self.agent.is_in_tree());
+            self.agent.choose(agent_input)
+        }
+
+        fn reveal_info(&mut self, reveal_index: RevealIndex, updated_score: Score) {
+            self.agent.reveal_info(reveal_index, updated_score);
+        }
+
+        fn game_finished(&mut self) {
+            self.agent.game_finished();
+        }
+    }
+
+    /// Plays a game against a random opponent, starting from
+    /// the main phase of the given state.
+    fn play(agent: &mut Agent, state: KnownState, seed: u64) -> Vec<bool> {
+        let phase = MainPhase::new();
+        let hidden_states: Vec<_> = phase.valid_hidden_states(state.to_summary()).collect();
+        let hidden = hidden_states[seed as usize * 7 % hidden_states.len()];
+
+        let mut in_tree = Vec::new();
+        let tracked = Tracked {
+            agent,
+            in_tree: &mut in_tree,
+        };
+        let opponent = RandomAgent::new(StdRng::seed_from_u64(seed));
+        let runner = EchoRunner::new(state, PerPhase::Main(phase), (tracked, opponent), hidden);
+        assert!(runner.run_game().is_some());
+
+        in_tree
+    }
+
+    #[test]
+    fn follows_the_tree_until_the_end() {
+        let state = state_on_turn(3);
+        let allocator = Bump::new();
+        let mut scope = GenerationContext::new(1, state, RuleSet::default(), &allocator).generate();
+        TrainingContext::new(false).cfr(&mut scope, state.to_summary(), 100);
+
+        let mut agent = blueprint_agent(&scope, state, ActionSelection::Sample);
+
+        // Every game starts back at the root
+        for seed in 0..20 {
+            let in_tree = play(&mut agent, state, seed);
+            assert!(!in_tree.is_empty());
+            assert!(in_tree.into_iter().all(|in_tree| in_tree));
+        }
+    }
+
+    #[test]
+    fn leaves_the_tree_past_the_horizon() {
+        let state = state_on_turn(2);
+        let allocator = Bump::new();
+        let scope = GenerationContext::new(1, state, RuleSet::default(), &allocator).generate();
+
+        let mut agent = blueprint_agent(&scope, state, ActionSelection::Argmax);
+        let in_tree = play(&mut agent, state, 0);
+
+        assert!(in_tree[0]);
+        assert!(!in_tree[in_tree.len() - 1]);
+    }
+
+    #[test]
+    fn argmax_takes_the_likeliest_decision() {
+        let allocator = Bump::new();
+        let node = DecisionVector::new(3, &allocator);
+        node.strategy_sum.copy_from_slice(&[1.0, 5.0, 2.0]);
+
+        let mut rng = StdRng::seed_from_u64(0);
+        assert_eq!(
+            ActionSelection::Argmax.select(&node, &mut rng),
+            DecisionIndex(1)
+        );
+    }
+
+    #[test]
+    fn resolved_trees_are_followed() {
+        let allocator = Bump::new();
+        let scope = generated_scope(&allocator, blueprint_state());
+        let cursor = past_the_horizon(&scope);
+        let state = cursor.state();
+
+        let resolved = Bump::new();
+        let fallback = Fallback::Resolve {
+            max_turns: 1,
+            iterations: 10,
+            allocator: &resolved,
+        };
+        let mut agent: Agent = CfrAgent::new(
+            cursor,
+            ActionSelection::Sample,
+            fallback,
+            StdRng::seed_from_u64(0),
+        );
+        assert!(!agent.is_in_tree());
+
+        // The turn only gets solved once, and is then followed until the end
+        let in_tree = play(&mut agent, state, 0);
+        assert!(resolved.allocated_bytes() > 0);
+        assert!(in_tree.len() > 1);
+        assert!(!in_tree[0]);
+        assert!(in_tree[1..].iter().all(|in_tree| *in_tree));
+    }
+
+    #[test]
+    fn long_games_are_not_resolved() {
+        let allocator = Bump::new();
+        let scope = generated_scope(&allocator, blueprint_state());
+        let cursor = past_the_horizon(&scope);
+        let (state, phase) = (cursor.state(), cursor.phase());
+        let hidden = phase.valid_hidden_states(state.to_summary())[0];
+
+        let resolved = Bump::new();
+        let fallback = Fallback::Resolve {
+            max_turns: 0,
+            iterations: 10,
+            allocator: &resolved,
+        };
+        let mut agent: Agent = CfrAgent::new(
+            cursor,
+            ActionSelection::Sample,
+            fallback,
+            StdRng::seed_from_u64(0),
+        );
+
+        agent.choose(AgentInput::new(phase, state, hidden[0], Player::Me));
+        assert!(!agent.is_in_tree());
+        assert_eq!(resolved.allocated_bytes(), 0);
+    }
+
+    #[test]
+    fn nearest_states_are_entered_in_the_main_phase() {
+        let allocator = Bump::new();
+        let scope = generated_scope(&allocator, blueprint_state());
+        let cursor = past_the_horizon(&scope);
+        let (state, phase) = (cursor.state(), cursor.phase());
+
+        // Only the score differs
+        let mut nearest = state;
+        nearest.score = Score(nearest.score.0 + 1);
+        let library = trained_scope(&allocator, nearest, 10);
+
+        // Some position later in the turn
+        let (_, later) = ScopeCursor::new(&library, nearest, phase)
+            .children(rules())
+            .next()
+            .unwrap();
+        assert_ne!(later.phase().tag(), PhaseTag::Main);
+
+        let fallback = Fallback::Nearest(vec![(nearest, &library)]);
+        let mut agent: Agent = CfrAgent::new(
+            cursor,
+            ActionSelection::Sample,
+            fallback,
+            StdRng::seed_from_u64(0),
+        );
+
+        let hidden = later
+            .phase()
+            .valid_hidden_states(later.state().to_summary())[0];
+        agent.choose(AgentInput::new(
+            later.phase(),
+            later.state(),
+            hidden[0],
+            Player::Me,
+        ));
+        assert!(!agent.is_in_tree());
+
+        let hidden = phase.valid_hidden_states(state.to_summary())[0];
+        agent.choose(AgentInput::new(phase, state, hidden[0], Player::Me));
+        assert!(agent.is_in_tree());
+    }
+}
+// }}}
@@ -0,0 +1,251 @@
+use super::echo_ai::EchoAgent;
+use super::match_runner::{Deal, MatchReport};
+use crate::game::rules::RuleSet;
+use crate::game::types::{Player, Score};
+use crate::helpers::statistics::RunningStats;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rayon::prelude::*;
+use std::fmt::{self, Display};
+use tracing::Level;
+
+// {{{ Paired results
+/// The results of playing the same deal twice, once from each seat.
+/// Scores are given from the perspective of the first agent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DuplicateResult {
+    pub deal: Deal,
+    /// The score of the game in which the first agent sat in each seat.
+    pub scores: [Option<Score>; 2],
+}
+
+impl DuplicateResult {
+    /// The net score margin across the two games,
+    /// or `None` if any of the games errored out.
+    pub fn margin(&self) -> Option<i8> {
+        Some(self.scores[0]?.0 + self.scores[1]?.0)
+    }
+
+    /// The number of points gained across the two games
+    /// (win = 1, tie = 1/2, loss = 0 for each game).
+    pub fn points(&self) -> Option<f64> {
+        self.scores
+            .into_iter()
+            .map(|score| score.map(|score| (score.to_utility() as f64 + 1.0) / 2.0))
+            .sum()
+    }
+}
+// }}}
+// {{{ Reports
+/// Aggregated results of a duplicate match, from the perspective of the first agent.
+///
+/// Statistics are computed over pairs of games, which cancels out most
+/// of the luck of the deal, and thus gives tighter confidence intervals.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DuplicateReport {
+    /// Statistics over every individual game, ignoring the pairing.
+    pub games: MatchReport,
+    /// Deals on which both games were won.
+    pub sweeps: usize,
+    /// Deals on which both games were lost.
+    pub swept: usize,
+    /// Deals on which at least one of the games errored out.
+    pub errors: usize,
+    /// Points per game, averaged over each pair.
+    pub points: RunningStats,
+    /// Score margins, averaged over each pair.
+    pub margins: RunningStats,
+}
+
+impl DuplicateReport {
+    pub fn record(&mut self, result: &DuplicateResult) {
+        for score in result.scores {
+            self.games.record(score);
+        }
+
+        let (Some(points), Some(margin)) = (result.points(), result.margin()) else {
+            self.errors += 1;
+            return;
+        };
+
+        if points == 2.0 {
+            self.sweeps += 1;
+        } else if points == 0.0 {
+            self.swept += 1;
+        }
+
+        self.points.push(points / 2.0);
+        self.margins.push(margin as f64 / 2.0);
+    }
+
+    /// The number of deals for which both games finished properly.
+    #[inline(always)]
+    pub fn deals(&self) -> usize {
+        self.points.count()
+    }
+
+    /// The average number of points gained per game
+    /// (win = 1, tie = 1/2, loss = 0).
+    #[inline(always)]
+    pub fn expected_score(&self) -> f64 {
+        self.points.mean()
+    }
+
+    #[inline(always)]
+    pub fn average_margin(&self) -> f64 {
+        self.margins.mean()
+    }
+}
+
+impl Display for DuplicateReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", self.games)?;
+        writeln!(
+            f,
+            "Deals: {} (swept {}, split {}, got swept {}, errored {})",
+            self.deals(),
+            self.sweeps,
+            self.deals() - self.sweeps - self.swept,
+            self.swept,
+            self.errors
+        )?;
+        writeln!(
+            f,
+            "Paired expected score: {:.3} ± {:.3}",
+            self.expected_score(),
+            self.points.confidence_radius()
+        )?;
+        write!(
+            f,
+            "Paired average margin: {:.2} ± {:.2}",
+            self.average_margin(),
+            self.margins.confidence_radius()
+        )
+    }
+}
+// }}}
+// {{{ Runner
+/// Plays every deal twice, swapping the seats (and thus the hands) of the
+/// agents between the two games. Like `MatchRunner`, agents are produced by
+/// seeded factories, and the results do not depend on how rayon schedules
+/// the games. Both games of a deal use the same seeds.
+pub struct DuplicateRunner<FA, FB> {
+    factories: (FA, FB),
+    deals: usize,
+    seed: u64,
+    rules: RuleSet,
+}
+
+impl<A, B, FA, FB> DuplicateRunner<FA, FB>
+where
+    A: EchoAgent,
+    B: EchoAgent,
+    FA: Fn(u64) -> A + Sync,
+    FB: Fn(u64) -> B + Sync,
+{
+    pub fn new(first: FA, second: FB, deals: usize) -> Self {
+        Self {
+            factories: (first, second),
+            deals,
+            seed: 0,
+            rules: RuleSet::default(),
+        }
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Plays every deal under some variant of the rules.
+    pub fn with_rules(mut self, rules: RuleSet) -> Self {
+        self.rules = rules;
+        self
+    }
+
+    /// Plays a deal with the first agent sitting in the given seat.
+    /// Returns the score from the perspective of the first agent.
+    fn play_seated(&self, deal: &Deal, seat: Player, seeds: [u64; 2]) -> Option<Score> {
+        let first = (self.factories.0)(seeds[0]);
+        let second = (self.factories.1)(seeds[1]);
+
+        let score = match seat {
+            Player::Me => deal.play((first, second), self.rules),
+            Player::You => deal.play((second, first), self.rules),
+        };
+
+        score.map(|score| score.from_perspective(seat))
+    }
+
+    fn play_deal(&self, index: usize) -> DuplicateResult {
+        let mut rng = StdRng::seed_from_u64(self.seed.wrapping_add(index as u64));
+        let deal = Deal::random(&mut rng);
+        let seeds = [rng.gen(), rng.gen()];
+        let scores = Player::PLAYERS.map(|seat| self.play_seated(&deal, seat, seeds));
+
+        if scores.contains(&None) {
+            tracing::event!(Level::WARN, ?deal, "Game errored out");
+        }
+
+        DuplicateResult { deal, scores }
+    }
+
+    /// Plays every deal, returning the paired results in order.
+    pub fn results(&self) -> Vec<DuplicateResult> {
+        (0..self.deals)
+            .into_par_iter()
+            .map(|index| self.play_deal(index))
+            .collect()
+    }
+
+    pub fn run(&self) -> DuplicateReport {
+        let mut report = DuplicateReport::default();
+
+        for result in self.results() {
+            report.record(&result);
+        }
+
+        report
+    }
+}
+// }}}
+// {{{ Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::random_agent::RandomAgent;
+
+    #[test]
+    fn duplicate_matches_are_reproducible() {
+        let random = |seed| RandomAgent::new(StdRng::seed_from_u64(seed));
+        let first = DuplicateRunner::new(random, random, 30)
+            .with_seed(3)
+            .results();
+        let second = DuplicateRunner::new(random, random, 30)
+            .with_seed(3)
+            .results();
+
+        assert_eq!(first, second);
+        assert!(first.iter().all(|result| result.margin().is_some()));
+    }
+
+    #[test]
+    fn paired_statistics_use_both_games() {
+        let result = DuplicateResult {
+            deal: Deal::random(&mut StdRng::seed_from_u64(0)),
+            scores: [Some(Score(5)), Some(Score(-2))],
+        };
+
+        assert_eq!(result.margin(), Some(3));
+        assert_eq!(result.points(), Some(1.0));
+
+        let mut report = DuplicateReport::default();
+        report.record(&result);
+
+        assert_eq!(report.deals(), 1);
+        assert_eq!(report.games.games(), 2);
+        assert_eq!((report.sweeps, report.swept), (0, 0));
+        assert_eq!(report.average_margin(), 1.5);
+    }
+}
+// }}}
@@ -1,15 +1,19 @@
+use std::sync::mpsc::Sender;
 use tracing::Level;
 
+use super::game_record::{GameRecord, PhaseRecord, RecordedChoice};
 use crate::cfr::decision_index::DecisionIndex;
 use crate::cfr::hidden_index::{self, HiddenState};
-use crate::cfr::phase::SomePhase;
+use crate::cfr::phase::{PerPhase, SomePhase};
 use crate::cfr::reveal_index::RevealIndex;
 use crate::game::known_state::KnownState;
+use crate::game::rules::RuleSet;
 use crate::game::types::{BattleResult, Player, Score, TurnResult};
 use crate::helpers::pair::Pair;
+use serde::{Deserialize, Serialize};
 
 // {{{ Agent input
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct AgentInput {
     pub phase: SomePhase,
     pub state: KnownState,
@@ -46,6 +50,43 @@ pub trait EchoAgent {
     #[inline(always)]
     fn game_finished(&mut self) {}
 }
+
+/// Allows agents to be reused across multiple games
+/// (for instance, to keep knowledge gathered in previous games around).
+impl<A: EchoAgent + ?Sized> EchoAgent for &mut A {
+    #[inline(always)]
+    fn choose(&mut self, agent_input: AgentInput) -> DecisionIndex {
+        (**self).choose(agent_input)
+    }
+
+    #[inline(always)]
+    fn reveal_info(&mut self, reveal_index: RevealIndex, updated_score: Score) {
+        (**self).reveal_info(reveal_index, updated_score)
+    }
+
+    #[inline(always)]
+    fn game_finished(&mut self) {
+        (**self).game_finished()
+    }
+}
+
+/// Allows agents of different types to be stored together.
+impl<A: EchoAgent + ?Sized> EchoAgent for Box<A> {
+    #[inline(always)]
+    fn choose(&mut self, agent_input: AgentInput) -> DecisionIndex {
+        (**self).choose(agent_input)
+    }
+
+    #[inline(always)]
+    fn reveal_info(&mut self, reveal_index: RevealIndex, updated_score: Score) {
+        (**self).reveal_info(reveal_index, updated_score)
+    }
+
+    #[inline(always)]
+    fn game_finished(&mut self) {
+        (**self).game_finished()
+    }
+}
 // }}}
 // {{{ Game runner
 /// Struct containing the data required to make two agents fight eachother.
@@ -54,6 +95,10 @@ pub struct EchoRunner<A, B> {
     phase: SomePhase,
     agents: (A, B),
     hidden_state: Pair<hidden_index::EncodingInfo>,
+    rules: RuleSet,
+
+    /// Where to send the record of the game (if anywhere).
+    recorder: Option<Sender<GameRecord>>,
 }
 
 impl<A: EchoAgent, B: EchoAgent> EchoRunner<A, B> {
@@ -68,9 +113,24 @@ impl<A: EchoAgent, B: EchoAgent> EchoRunner<A, B> {
             phase,
             agents,
             hidden_state,
+            rules: RuleSet::default(),
+            recorder: None,
         }
     }
 
+    /// Plays the game under some variant of the rules.
+    pub fn with_rules(mut self, rules: RuleSet) -> Self {
+        self.rules = rules;
+        self
+    }
+
+    /// Sends a record of the game to the given channel once the game is over.
+    /// Only games starting in the main phase can be recorded.
+    pub fn record_to(mut self, recorder: Sender<GameRecord>) -> Self {
+        self.recorder = Some(recorder);
+        self
+    }
+
     fn input_for(&self, player: Player) -> Option<AgentInput> {
         let hidden = player.select(self.hidden_state);
         let input = AgentInput::new(self.phase, self.state, hidden, player);
@@ -78,8 +138,31 @@ impl<A: EchoAgent, B: EchoAgent> EchoRunner<A, B> {
         Some(input)
     }
 
-    pub fn run_game(mut self) -> Option<BattleResult> {
+    pub fn run_game(self) -> Option<BattleResult> {
+        self.run_game_with_score().map(Score::to_battle_result)
+    }
+
+    /// Same as `run_game`, but returns the final score (from the perspective
+    /// of the first agent) instead of just the winner.
+    pub fn run_game_with_score(mut self) -> Option<Score> {
         let _guard = tracing::span!(Level::DEBUG, "Echo fight");
+
+        let mut record = match (&self.recorder, self.phase) {
+            (Some(_), PerPhase::Main(_)) => Some(GameRecord::new(
+                self.rules,
+                self.state,
+                self.hidden_state.map(|hidden| hidden.get_main()),
+            )),
+            (Some(_), _) => {
+                tracing::event!(
+                    Level::WARN,
+                    "Games not starting in the main phase cannot be recorded"
+                );
+                None
+            }
+            (None, _) => None,
+        };
+
         loop {
             let _guard = tracing::span!(
                 Level::DEBUG,
@@ -97,7 +180,7 @@ impl<A: EchoAgent, B: EchoAgent> EchoRunner<A, B> {
                 self.state,
                 self.hidden_state.map(HiddenState::from_encoding_info),
                 decisions,
-                false,
+                self.rules,
             )?;
 
             tracing::event!(Level::DEBUG, "Advanced state");
@@ -107,6 +190,35 @@ impl<A: EchoAgent, B: EchoAgent> EchoRunner<A, B> {
                 TurnResult::Unfinished((state, _, _)) => state.score,
             };
 
+            if let Some(recorded) = &mut record {
+                let choices = Player::PLAYERS.try_map(|player| {
+                    RecordedChoice::decode(
+                        &self.phase,
+                        &self.state,
+                        player,
+                        player.select(self.hidden_state),
+                        player.select(decisions),
+                    )
+                });
+
+                // A broken record should never cost us the game itself
+                if let Some(choices) = choices {
+                    recorded.phases.push(PhaseRecord {
+                        decisions,
+                        choices,
+                        reveal: reveal_index,
+                        score,
+                    });
+                } else {
+                    tracing::event!(
+                        Level::WARN,
+                        phase = ?self.phase.tag(),
+                        "Failed to decode the decisions, the game will not be recorded"
+                    );
+                    record = None;
+                }
+            }
+
             self.agents.0.reveal_info(reveal_index, score);
             self.agents.1.reveal_info(reveal_index, score);
             tracing::event!(Level::DEBUG, "Pushed reveal indices");
@@ -118,7 +230,15 @@ impl<A: EchoAgent, B: EchoAgent> EchoRunner<A, B> {
                     self.agents.0.game_finished();
                     self.agents.1.game_finished();
 
-                    return Some(score.to_battle_result());
+                    if let (Some(recorder), Some(mut record)) = (&self.recorder, record.take()) {
+                        record.result = Some(score);
+
+                        if recorder.send(record).is_err() {
+                            tracing::event!(Level::WARN, "Game record receiver has been dropped");
+                        }
+                    }
+
+                    return Some(score);
                 }
                 TurnResult::Unfinished((state, hidden, phase)) => {
                     self.state = state;
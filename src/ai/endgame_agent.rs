@@ -0,0 +1,176 @@
+use super::echo_ai::{AgentInput, EchoAgent};
+use crate::cfr::belief::PublicBelief;
+use crate::cfr::decision_index::DecisionIndex;
+use crate::cfr::endgame::{Endgame, EndgameSolver, SolveEndgame};
+use crate::cfr::hidden_index::HiddenIndex;
+use crate::cfr::reveal_index::RevealIndex;
+use crate::game::types::Score;
+use crate::helpers::roulette;
+use rand::Rng;
+use tracing::Level;
+
+// {{{ Agent
+/// Wraps another agent, taking over once the game reaches the last battlefield.
+/// At that point, the remainder of the game gets solved to equilibrium, and the
+/// agent keeps following the resulting strategy until the game ends.
+///
+/// Unless a belief is provided using [EndgameAgent::set_belief], every
+/// combination of hands consistent with the public information is assumed
+/// to be equally likely once the endgame starts.
+///
+/// The endgame is solved using CFR+ by default. Any other [SolveEndgame]
+/// implementation (like the exact [LastTurnSolver](crate::cfr::last_turn::LastTurnSolver))
+/// can be used instead.
+pub struct EndgameAgent<R, A, S = EndgameSolver> {
+    inner: A,
+    solver: S,
+    rng: R,
+
+    /// The solved endgame, together with the reveals which
+    /// happened since the state it was solved from.
+    endgame: Option<(Endgame, Vec<RevealIndex>)>,
+}
+
+impl<R: Rng, A: EchoAgent, S: SolveEndgame> EndgameAgent<R, A, S> {
+    /// The solver should use the same rules the game is played under
+    /// (which usually means no hopeless surrenders).
+    pub fn new(inner: A, solver: S, rng: R) -> Self {
+        Self {
+            inner,
+            solver,
+            rng,
+            endgame: None,
+        }
+    }
+
+    /// Solves the endgame right away, using a belief computed elsewhere
+    /// (for instance, by following a blueprint through the game).
+    ///
+    /// Returns `None` (and keeps the current endgame around) unless
+    /// the belief refers to a state on the last battlefield.
+    pub fn set_belief(&mut self, belief: &PublicBelief) -> Option<&Endgame> {
+        let endgame = self.solver.solve(belief)?;
+        self.endgame = Some((endgame, Vec::new()));
+
+        self.endgame.as_ref().map(|(endgame, _)| endgame)
+    }
+
+    /// Attempts to pick a decision using the solved endgame.
+    fn decide(&mut self, agent_input: AgentInput) -> Option<DecisionIndex> {
+        if !agent_input.state.battlefields.is_last() {
+            return None;
+        }
+
+        if self.endgame.is_none() {
+            let belief = PublicBelief::new(agent_input.state, agent_input.phase);
+            let endgame = self.set_belief(&belief)?;
+
+            tracing::event!(
+                Level::DEBUG,
+                value = endgame.value(),
+                exploitability = endgame.exploitability(),
+                "Solved the endgame"
+            );
+        }
+
+        let (endgame, path) = self.endgame.as_ref()?;
+        let hidden =
+            HiddenIndex::encode(&agent_input.state, agent_input.player, agent_input.hidden);
+        let strategy = endgame.strategy(agent_input.player, path, hidden)?;
+
+        Some(DecisionIndex(roulette(&strategy, &mut self.rng)))
+    }
+}
+
+impl<R: Rng, A: EchoAgent, S: SolveEndgame> EchoAgent for EndgameAgent<R, A, S> {
+    fn choose(&mut self, agent_input: AgentInput) -> DecisionIndex {
+        self.decide(agent_input)
+            .unwrap_or_else(|| self.inner.choose(agent_input))
+    }
+
+    fn reveal_info(&mut self, reveal_index: RevealIndex, updated_score: Score) {
+        if let Some((_, path)) = &mut self.endgame {
+            path.push(reveal_index);
+        }
+
+        self.inner.reveal_info(reveal_index, updated_score);
+    }
+
+    fn game_finished(&mut self) {
+        self.endgame = None;
+        self.inner.game_finished();
+    }
+}
+// }}}
+// {{{ Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::echo_ai::EchoRunner;
+    use crate::cfr::last_turn::LastTurnSolver;
+    use crate::cfr::phase::{MainPhase, PerPhase};
+    use crate::game::known_state_summary::KnownStateEssentials;
+    use crate::game::notation::Position;
+    use crate::game::rules::RuleSet;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    /// Only ever gets asked about decisions the endgame has no say in.
+    struct ForcedOnly;
+
+    impl EchoAgent for ForcedOnly {
+        fn choose(&mut self, agent_input: AgentInput) -> DecisionIndex {
+            let counts = agent_input.phase.decision_counts(&agent_input.state);
+            assert_eq!(agent_input.player.select(counts), 1);
+
+            DecisionIndex(0)
+        }
+    }
+
+    fn agent(seed: u64) -> EndgameAgent<StdRng, ForcedOnly> {
+        let solver = EndgameSolver::new(RuleSet::default());
+        EndgameAgent::new(ForcedOnly, solver, StdRng::seed_from_u64(seed))
+    }
+
+    #[test]
+    fn plays_the_last_turn() {
+        let state = Position::parse("PPPL 3 +0 WSRBDG RS/RS S/-").unwrap().state;
+        let phase = PerPhase::Main(MainPhase::new());
+        let deals = phase.valid_hidden_states(state.to_summary());
+
+        for (seed, deal) in deals.into_iter().enumerate().step_by(7) {
+            let seed = seed as u64;
+            let runner = EchoRunner::new(state, phase, (agent(seed), agent(seed + 1)), deal);
+
+            // Illegal decisions would stop the game
+            assert!(runner.run_game().is_some());
+        }
+    }
+
+    #[test]
+    fn plays_the_last_turn_exactly() {
+        let state = Position::parse("PPPL 3 +0 WSRBDG RS/RS S/-").unwrap().state;
+        let phase = PerPhase::Main(MainPhase::new());
+        let deals = phase.valid_hidden_states(state.to_summary());
+        let exact = |seed| {
+            let solver = LastTurnSolver::new(RuleSet::default());
+            EndgameAgent::new(ForcedOnly, solver, StdRng::seed_from_u64(seed))
+        };
+
+        for (seed, deal) in deals.into_iter().enumerate().step_by(7) {
+            let seed = seed as u64;
+            let runner = EchoRunner::new(state, phase, (exact(seed), agent(seed + 1)), deal);
+
+            assert!(runner.run_game().is_some());
+        }
+    }
+
+    #[test]
+    fn beliefs_must_be_on_the_last_battlefield() {
+        let state = Position::parse("PPPL 2 +0 WSRB RSG/RSG S/-").unwrap().state;
+        let belief = PublicBelief::new(state, PerPhase::Main(MainPhase::new()));
+
+        assert!(agent(0).set_belief(&belief).is_none());
+    }
+}
+// }}}
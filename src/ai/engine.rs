@@ -0,0 +1,764 @@
+use super::echo_ai::{AgentInput, EchoAgent};
+use super::game_record::{
+    parse_named, parse_number, parse_pair, parse_score, parse_set, tag_name, RecordedChoice,
+    SetDisplay,
+};
+use crate::cfr::decision_index::DecisionIndex;
+use crate::cfr::hidden_index::{HiddenIndex, HiddenState};
+use crate::cfr::phase::{MainPhase, PerPhase, SabotagePhase, SeerPhase, SomePhase};
+use crate::cfr::reveal_index::RevealIndex;
+use crate::game::battlefield::{Battlefield, Battlefields};
+use crate::game::creature::{Creature, CreatureSet};
+use crate::game::edict::{Edict, EdictSet};
+use crate::game::known_state::{KnownPlayerState, KnownState};
+use crate::game::status_effect::{StatusEffect, StatusEffectSet};
+use crate::game::types::{Player, Score};
+use crate::helpers::pair::Pair;
+use itertools::Itertools;
+use std::fmt::{self, Display};
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use tracing::Level;
+
+// {{{ Commands
+/// Messages sent by the host (the program running the game) to an engine.
+///
+/// Every message is a single line of text. The engine answers `echo` with
+/// `echook` (optionally preceded by `id name <name>`), `isready` with `readyok`,
+/// and `go` with `choose <index>`. Every other command gets no answer.
+/// Engines can send `info <anything>` lines at any point, which get logged.
+///
+/// Before every decision, the host sends the entire position:
+/// ```text
+/// position battlefields=Night,Glade,Urban,LastStrand turn=0 score=0 graveyard=- edicts=<set>|<set> effects=-|-
+/// hidden seat=Me hand=Wall,Seer,Rogue,Bard,Diplomat choice=-
+/// phase main
+/// moves 25
+/// move 0 Wall/RileThePublic
+/// move 1 Seer/RileThePublic
+/// ...
+/// go
+/// ```
+///
+/// Sets are comma separated lists of names (or `-` when empty), and pairs
+/// are written as `<first>|<second>`. The `choice` of a player is the set of
+/// creatures they've played during the main phase of this turn (`-` during the
+/// main phase). The other phases are written as
+/// `phase sabotage edicts=<edict>|<edict>` and
+/// `phase seer edicts=<edict>|<edict> sabotage=<guess>|<guess> revealed=<creature>`.
+///
+/// The `move` lines list every legal decision together with it's meaning,
+/// so engines do not have to reimplement the decision encoding.
+/// After both players have decided, the host sends `reveal <index> <score>`,
+/// and once the game is over, `gameover`. The `quit` command stops the engine.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EngineCommand {
+    Echo,
+    IsReady,
+    NewGame,
+    Position(KnownState),
+    Hidden(Player, HiddenState),
+    Phase(PhaseDescription),
+    Moves(usize),
+    /// A legal decision, together with it's human readable meaning.
+    Move(DecisionIndex, String),
+    Go,
+    Reveal(RevealIndex, Score),
+    GameOver,
+    Quit,
+}
+
+/// Owned, comparable version of `SomePhase`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PhaseDescription {
+    Main,
+    Sabotage(Pair<Edict>),
+    Seer(Pair<Edict>, Pair<Option<Creature>>, Creature),
+}
+
+impl PhaseDescription {
+    pub fn from_phase(phase: &SomePhase) -> Self {
+        match phase {
+            PerPhase::Main(_) => Self::Main,
+            PerPhase::Sabotage(sabotage) => Self::Sabotage(sabotage.edict_choices),
+            PerPhase::Seer(seer) => Self::Seer(
+                seer.edict_choices,
+                seer.sabotage_choices,
+                seer.revealed_creature,
+            ),
+        }
+    }
+
+    pub fn to_phase(self) -> SomePhase {
+        match self {
+            Self::Main => PerPhase::Main(MainPhase::new()),
+            Self::Sabotage(edict_choices) => PerPhase::Sabotage(SabotagePhase { edict_choices }),
+            Self::Seer(edicts, guesses, revealed) => {
+                PerPhase::Seer(SeerPhase::new(edicts, guesses, revealed))
+            }
+        }
+    }
+
+    /// The creature revealed during the seer phase (if any).
+    pub fn revealed(self) -> Option<Creature> {
+        match self {
+            Self::Seer(_, _, revealed) => Some(revealed),
+            _ => None,
+        }
+    }
+}
+
+impl EngineCommand {
+    pub fn parse(input: &str) -> Result<Self, String> {
+        let words: Vec<_> = input.split_whitespace().collect();
+        let field = |key: &str| -> Result<&str, String> {
+            words
+                .iter()
+                .find_map(|word| word.strip_prefix(key)?.strip_prefix('='))
+                .ok_or_else(|| format!("Missing field {key:?} in {input:?}"))
+        };
+        let index =
+            |input: &str| parse_number(input).ok_or_else(|| format!("Invalid index {input:?}"));
+
+        let command = match words.as_slice() {
+            ["echo"] => Self::Echo,
+            ["isready"] => Self::IsReady,
+            ["newgame"] => Self::NewGame,
+            ["position", ..] => {
+                let battlefields: [Battlefield; 4] = field("battlefields")?
+                    .split(',')
+                    .map(|name| parse_named(name, &Battlefield::BATTLEFIELDS))
+                    .collect::<Result<Vec<_>, _>>()?
+                    .try_into()
+                    .map_err(|_| "Expected exactly 4 battlefields".to_string())?;
+
+                let turn = field("turn")?;
+                let current = parse_number(turn)
+                    .filter(|turn| *turn < 4)
+                    .ok_or_else(|| format!("Invalid turn {turn:?}"))?;
+
+                let edicts = parse_pair(field("edicts")?, |set| {
+                    parse_set::<EdictSet>(set, &Edict::EDICTS)
+                })?;
+                let effects = parse_pair(field("effects")?, |set| {
+                    parse_set::<StatusEffectSet>(set, &StatusEffect::STATUS_EFFECTS)
+                })?;
+
+                Self::Position(KnownState {
+                    player_states: [0, 1].map(|i| KnownPlayerState {
+                        edicts: edicts[i],
+                        effects: effects[i],
+                    }),
+                    battlefields: Battlefields {
+                        all: battlefields,
+                        current,
+                    },
+                    graveyard: parse_set(field("graveyard")?, &Creature::CREATURES)?,
+                    score: parse_score(field("score")?)?,
+                })
+            }
+            ["hidden", ..] => {
+                let seat = parse_named(field("seat")?, &Player::PLAYERS)?;
+                let hand = parse_set(field("hand")?, &Creature::CREATURES)?;
+                let choice = match field("choice")? {
+                    "-" => None,
+                    set => Some(parse_set::<CreatureSet>(set, &Creature::CREATURES)?),
+                };
+
+                Self::Hidden(seat, HiddenState::new(hand, choice))
+            }
+            ["phase", "main"] => Self::Phase(PhaseDescription::Main),
+            ["phase", "sabotage", ..] => {
+                Self::Phase(PhaseDescription::Sabotage(parse_edicts(field("edicts")?)?))
+            }
+            ["phase", "seer", ..] => {
+                let guesses = parse_pair(field("sabotage")?, |guess| match guess {
+                    "-" => Ok(None),
+                    creature => parse_named(creature, &Creature::CREATURES).map(Some),
+                })?;
+
+                Self::Phase(PhaseDescription::Seer(
+                    parse_edicts(field("edicts")?)?,
+                    guesses,
+                    parse_named(field("revealed")?, &Creature::CREATURES)?,
+                ))
+            }
+            ["moves", count] => Self::Moves(index(count)?),
+            ["move", decision, meaning] => {
+                Self::Move(DecisionIndex(index(decision)?), meaning.to_string())
+            }
+            ["go"] => Self::Go,
+            ["reveal", reveal, score] => {
+                Self::Reveal(RevealIndex(index(reveal)?), parse_score(score)?)
+            }
+            ["gameover"] => Self::GameOver,
+            ["quit"] => Self::Quit,
+            _ => return Err(format!("Unknown command {input:?}")),
+        };
+
+        Ok(command)
+    }
+}
+
+fn parse_edicts(input: &str) -> Result<Pair<Edict>, String> {
+    parse_pair(input, |edict| parse_named(edict, &Edict::EDICTS))
+}
+
+impl Display for EngineCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Echo => write!(f, "echo"),
+            Self::IsReady => write!(f, "isready"),
+            Self::NewGame => write!(f, "newgame"),
+            Self::Position(state) => {
+                let [me, you] = state.player_states;
+
+                write!(
+                    f,
+                    "position battlefields={} turn={} score={} graveyard={} edicts={}|{} effects={}|{}",
+                    state.battlefields.all.iter().join(","),
+                    state.battlefields.current,
+                    state.score.0,
+                    SetDisplay(state.graveyard),
+                    SetDisplay(me.edicts),
+                    SetDisplay(you.edicts),
+                    SetDisplay(me.effects),
+                    SetDisplay(you.effects),
+                )
+            }
+            Self::Hidden(seat, hidden) => {
+                write!(
+                    f,
+                    "hidden seat={seat:?} hand={} choice=",
+                    SetDisplay(hidden.hand)
+                )?;
+
+                match hidden.choice {
+                    Some(choice) => write!(f, "{}", SetDisplay(choice)),
+                    None => write!(f, "-"),
+                }
+            }
+            Self::Phase(PhaseDescription::Main) => write!(f, "phase main"),
+            Self::Phase(PhaseDescription::Sabotage([first, second])) => {
+                write!(f, "phase sabotage edicts={first}|{second}")
+            }
+            Self::Phase(PhaseDescription::Seer([first, second], guesses, revealed)) => {
+                let [a, b] = guesses.map(|guess| match guess {
+                    Some(creature) => creature.to_string(),
+                    None => "-".to_string(),
+                });
+
+                write!(
+                    f,
+                    "phase seer edicts={first}|{second} sabotage={a}|{b} revealed={revealed}"
+                )
+            }
+            Self::Moves(count) => write!(f, "moves {count}"),
+            Self::Move(decision, meaning) => write!(f, "move {} {meaning}", decision.0),
+            Self::Go => write!(f, "go"),
+            Self::Reveal(reveal, score) => write!(f, "reveal {} {}", reveal.0, score.0),
+            Self::GameOver => write!(f, "gameover"),
+            Self::Quit => write!(f, "quit"),
+        }
+    }
+}
+// }}}
+// {{{ Replies
+/// Messages sent by an engine to the host.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EngineReply {
+    Id(String),
+    EchoOk,
+    ReadyOk,
+    Choose(DecisionIndex),
+    Info(String),
+}
+
+impl EngineReply {
+    pub fn parse(input: &str) -> Result<Self, String> {
+        let input = input.trim();
+
+        let reply = match input.split_whitespace().collect::<Vec<_>>().as_slice() {
+            ["id", "name", ..] => Self::Id(input["id name".len()..].trim().to_string()),
+            ["echook"] => Self::EchoOk,
+            ["readyok"] => Self::ReadyOk,
+            ["choose", index] => Self::Choose(DecisionIndex(
+                parse_number(index).ok_or_else(|| format!("Invalid index {index:?}"))?,
+            )),
+            ["info", ..] => Self::Info(input["info".len()..].trim().to_string()),
+            _ => return Err(format!("Unknown reply {input:?}")),
+        };
+
+        Ok(reply)
+    }
+}
+
+impl Display for EngineReply {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Id(name) => write!(f, "id name {name}"),
+            Self::EchoOk => write!(f, "echook"),
+            Self::ReadyOk => write!(f, "readyok"),
+            Self::Choose(decision) => write!(f, "choose {}", decision.0),
+            Self::Info(info) => write!(f, "info {info}"),
+        }
+    }
+}
+// }}}
+// {{{ Engine side
+/// Exposes an agent as an engine, reading commands from `input`
+/// and writing replies to `output` until `quit` (or the end of the input).
+pub fn serve<A: EchoAgent>(
+    name: &str,
+    mut agent: A,
+    input: impl BufRead,
+    mut output: impl Write,
+) -> io::Result<()> {
+    let mut state = None;
+    let mut hidden = None;
+    let mut phase = None;
+    let mut in_game = false;
+
+    for line in input.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let command = match EngineCommand::parse(&line) {
+            Ok(command) => command,
+            Err(message) => {
+                reply(&mut output, EngineReply::Info(format!("error {message}")))?;
+                continue;
+            }
+        };
+
+        match command {
+            EngineCommand::Echo => {
+                reply(&mut output, EngineReply::Id(name.to_string()))?;
+                reply(&mut output, EngineReply::EchoOk)?;
+            }
+            EngineCommand::IsReady => reply(&mut output, EngineReply::ReadyOk)?,
+            EngineCommand::NewGame => {
+                if in_game {
+                    agent.game_finished();
+                    in_game = false;
+                }
+            }
+            EngineCommand::Position(new_state) => state = Some(new_state),
+            EngineCommand::Hidden(seat, new_hidden) => hidden = Some((seat, new_hidden)),
+            EngineCommand::Phase(new_phase) => phase = Some(new_phase),
+            EngineCommand::Moves(_) | EngineCommand::Move(_, _) => {}
+            EngineCommand::Go => {
+                let (Some(state), Some((player, hidden)), Some(phase)) = (state, hidden, phase)
+                else {
+                    reply(
+                        &mut output,
+                        EngineReply::Info("error position, hidden and phase required".to_string()),
+                    )?;
+                    continue;
+                };
+
+                let input = match agent_input(state, player, hidden, phase) {
+                    Ok(input) => input,
+                    Err(message) => {
+                        reply(&mut output, EngineReply::Info(format!("error {message}")))?;
+                        continue;
+                    }
+                };
+
+                in_game = true;
+                reply(&mut output, EngineReply::Choose(agent.choose(input)))?;
+            }
+            EngineCommand::Reveal(reveal, score) => agent.reveal_info(reveal, score),
+            EngineCommand::GameOver => {
+                agent.game_finished();
+                in_game = false;
+            }
+            EngineCommand::Quit => break,
+        }
+    }
+
+    Ok(())
+}
+
+/// Puts together the input of an agent, making sure the hidden
+/// information sent by the host actually fits the position.
+fn agent_input(
+    state: KnownState,
+    player: Player,
+    hidden: HiddenState,
+    phase: PhaseDescription,
+) -> Result<AgentInput, String> {
+    let phase_name = tag_name(phase.to_phase().tag());
+    if hidden.choice.is_some() == (phase == PhaseDescription::Main) {
+        return Err(format!("choice does not fit the {phase_name} phase"));
+    }
+
+    let info = hidden
+        .try_to_encoding_info(phase.revealed())
+        .ok_or_else(|| format!("choice required during the {phase_name} phase"))?;
+
+    if HiddenIndex::try_encode(&state, player, info).is_none() {
+        return Err(format!(
+            "hand {} (choice {}) does not fit the position",
+            SetDisplay(hidden.hand),
+            SetDisplay(hidden.choice.unwrap_or_default())
+        ));
+    }
+
+    Ok(AgentInput::new(phase.to_phase(), state, info, player))
+}
+
+fn reply(output: &mut impl Write, reply: EngineReply) -> io::Result<()> {
+    writeln!(output, "{reply}")?;
+    output.flush()
+}
+// }}}
+// {{{ Host side
+/// An agent forwarding every decision to an external engine.
+pub struct EngineAgent<W: Write, R: BufRead> {
+    name: String,
+    writer: W,
+    reader: R,
+    /// The engine process (if we've spawned it ourselves).
+    child: Option<Child>,
+}
+
+impl EngineAgent<ChildStdin, BufReader<ChildStdout>> {
+    /// Spawns an engine process, and waits for it to be ready.
+    pub fn spawn(command: &mut Command) -> io::Result<Self> {
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+
+        let writer = child.stdin.take().unwrap();
+        let reader = BufReader::new(child.stdout.take().unwrap());
+
+        let mut agent = Self::new(writer, reader)?;
+        agent.child = Some(child);
+        Ok(agent)
+    }
+}
+
+impl<W: Write, R: BufRead> EngineAgent<W, R> {
+    /// Talks to an engine using the given streams. Performs the initial handshake.
+    pub fn new(writer: W, reader: R) -> io::Result<Self> {
+        let mut agent = Self {
+            name: String::new(),
+            writer,
+            reader,
+            child: None,
+        };
+
+        agent.send(EngineCommand::Echo)?;
+        loop {
+            match agent.receive()? {
+                EngineReply::Id(name) => agent.name = name,
+                EngineReply::EchoOk => break,
+                _ => {}
+            }
+        }
+
+        Ok(agent)
+    }
+
+    /// The name the engine has introduced itself with.
+    #[inline(always)]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn send(&mut self, command: EngineCommand) -> io::Result<()> {
+        writeln!(self.writer, "{command}")?;
+        self.writer.flush()
+    }
+
+    /// Reads the next meaningful reply, logging `info` lines along the way.
+    fn receive(&mut self) -> io::Result<EngineReply> {
+        loop {
+            let mut line = String::new();
+            if self.reader.read_line(&mut line)? == 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "The engine has closed it's output",
+                ));
+            }
+
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            match EngineReply::parse(&line) {
+                Ok(EngineReply::Info(info)) => {
+                    tracing::event!(Level::DEBUG, engine = self.name, info)
+                }
+                Ok(reply) => return Ok(reply),
+                Err(message) => tracing::event!(Level::WARN, engine = self.name, message),
+            }
+        }
+    }
+
+    fn try_choose(&mut self, agent_input: AgentInput) -> io::Result<DecisionIndex> {
+        let AgentInput {
+            phase,
+            state,
+            player,
+            hidden,
+        } = agent_input;
+
+        let count = player.select(phase.decision_counts(&state));
+
+        self.send(EngineCommand::Position(state))?;
+        self.send(EngineCommand::Hidden(
+            player,
+            HiddenState::from_encoding_info(hidden),
+        ))?;
+        self.send(EngineCommand::Phase(PhaseDescription::from_phase(&phase)))?;
+        self.send(EngineCommand::Moves(count))?;
+
+        for index in 0..count {
+            let decision = DecisionIndex(index);
+            let meaning = RecordedChoice::decode(&phase, &state, player, hidden, decision)
+                .map_or_else(|| "?".to_string(), |choice| choice.to_string());
+
+            self.send(EngineCommand::Move(decision, meaning))?;
+        }
+
+        self.send(EngineCommand::Go)?;
+
+        loop {
+            if let EngineReply::Choose(decision) = self.receive()? {
+                if decision.0 < count {
+                    return Ok(decision);
+                }
+
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Illegal decision {} (out of {count})", decision.0),
+                ));
+            }
+        }
+    }
+}
+
+impl<W: Write, R: BufRead> EchoAgent for EngineAgent<W, R> {
+    fn choose(&mut self, agent_input: AgentInput) -> DecisionIndex {
+        self.try_choose(agent_input).unwrap_or_else(|error| {
+            tracing::event!(
+                Level::ERROR,
+                engine = self.name,
+                %error,
+                "Engine failed to choose, defaulting to the first decision"
+            );
+            DecisionIndex::default()
+        })
+    }
+
+    fn reveal_info(&mut self, reveal_index: RevealIndex, updated_score: Score) {
+        if let Err(error) = self.send(EngineCommand::Reveal(reveal_index, updated_score)) {
+            tracing::event!(Level::ERROR, engine = self.name, %error);
+        }
+    }
+
+    fn game_finished(&mut self) {
+        let result = self
+            .send(EngineCommand::GameOver)
+            .and_then(|_| self.send(EngineCommand::NewGame));
+
+        if let Err(error) = result {
+            tracing::event!(Level::ERROR, engine = self.name, %error);
+        }
+    }
+}
+
+impl<W: Write, R: BufRead> Drop for EngineAgent<W, R> {
+    fn drop(&mut self) {
+        let _ = self.send(EngineCommand::Quit);
+
+        if let Some(child) = &mut self.child {
+            // Engines which ignore the quit command get killed
+            if !matches!(child.try_wait(), Ok(Some(_))) {
+                let _ = child.kill();
+            }
+
+            let _ = child.wait();
+        }
+    }
+}
+// }}}
+// {{{ Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::greedy_agent::GreedyAgent;
+    use crate::ai::match_runner::Deal;
+    use crate::ai::random_agent::RandomAgent;
+    use crate::helpers::bitfield::Bitfield;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use std::io::Cursor;
+
+    #[test]
+    fn commands_roundtrip_through_text() {
+        let deal = Deal::random(&mut StdRng::seed_from_u64(0));
+        let mut state = deal.state();
+        state.graveyard.insert(Creature::Wall);
+        state.score = Score(-3);
+
+        let commands = [
+            EngineCommand::Echo,
+            EngineCommand::NewGame,
+            EngineCommand::Position(state),
+            EngineCommand::Hidden(Player::You, HiddenState::new(deal.hands[1], None)),
+            EngineCommand::Hidden(
+                Player::Me,
+                HiddenState::new(deal.hands[0], Some(CreatureSet::singleton(Creature::Seer))),
+            ),
+            EngineCommand::Phase(PhaseDescription::Main),
+            EngineCommand::Phase(PhaseDescription::Seer(
+                [Edict::RileThePublic, Edict::DivertAttention],
+                [None, Some(Creature::Seer)],
+                Creature::Bard,
+            )),
+            EngineCommand::Move(DecisionIndex(3), "Wall/RileThePublic".to_string()),
+            EngineCommand::Reveal(RevealIndex(7), Score(4)),
+            EngineCommand::Quit,
+        ];
+
+        for command in commands {
+            let text = command.to_string();
+            assert_eq!(EngineCommand::parse(&text), Ok(command), "{text}");
+        }
+    }
+
+    #[test]
+    fn engines_answer_the_host() {
+        let deal = Deal::random(&mut StdRng::seed_from_u64(1));
+        let commands = [
+            EngineCommand::Echo,
+            EngineCommand::Position(deal.state()),
+            EngineCommand::Hidden(Player::Me, HiddenState::new(deal.hands[0], None)),
+            EngineCommand::Phase(PhaseDescription::Main),
+            EngineCommand::Go,
+            EngineCommand::Quit,
+        ];
+        let input = commands.iter().join("\n");
+
+        let mut output = Vec::new();
+        let agent = RandomAgent::new(StdRng::seed_from_u64(0));
+        serve("random", agent, Cursor::new(input), &mut output).unwrap();
+
+        let replies: Vec<_> = String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| EngineReply::parse(line).unwrap())
+            .collect();
+
+        assert_eq!(
+            replies[..2],
+            [EngineReply::Id("random".to_string()), EngineReply::EchoOk]
+        );
+        assert!(matches!(replies[2], EngineReply::Choose(_)));
+    }
+
+    /// Runs an engine on a single decision, returning it's final reply.
+    fn answer(state: KnownState, hidden: HiddenState, phase: PhaseDescription) -> EngineReply {
+        let commands = [
+            EngineCommand::Position(state),
+            EngineCommand::Hidden(Player::Me, hidden),
+            EngineCommand::Phase(phase),
+            EngineCommand::Go,
+        ];
+        let input = commands.iter().join("\n");
+
+        let mut output = Vec::new();
+        let agent = GreedyAgent::default();
+        serve("greedy", agent, Cursor::new(input), &mut output).unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        EngineReply::parse(output.lines().last().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn malformed_hidden_information_is_reported() {
+        let deal = Deal::random(&mut StdRng::seed_from_u64(3));
+        let state = deal.state();
+        let hand = deal.hands[0];
+        let seer = PhaseDescription::Seer(
+            [Edict::RileThePublic, Edict::RileThePublic],
+            [None, None],
+            deal.hands[1].into_iter().next().unwrap(),
+        );
+
+        let mut overlapping = state;
+        overlapping
+            .graveyard
+            .insert(hand.into_iter().next().unwrap());
+        let mut smaller = hand;
+        smaller.remove(hand.into_iter().next().unwrap());
+
+        let cases = [
+            // No choice during the seer phase
+            (state, HiddenState::new(hand, None), seer),
+            // A choice during the main phase
+            (
+                state,
+                HiddenState::new(hand, Some(hand)),
+                PhaseDescription::Main,
+            ),
+            // A hand of the wrong size
+            (
+                state,
+                HiddenState::new(smaller, None),
+                PhaseDescription::Main,
+            ),
+            // A hand overlapping the graveyard
+            (
+                overlapping,
+                HiddenState::new(hand, None),
+                PhaseDescription::Main,
+            ),
+        ];
+
+        for (state, hidden, phase) in cases {
+            let reply = answer(state, hidden, phase);
+            assert!(
+                matches!(&reply, EngineReply::Info(info) if info.starts_with("error")),
+                "Got {reply:?} for {hidden:?} during {phase:?}"
+            );
+        }
+
+        let reply = answer(state, HiddenState::new(hand, None), PhaseDescription::Main);
+        assert!(matches!(reply, EngineReply::Choose(_)));
+    }
+
+    #[test]
+    fn proxies_forward_decisions() {
+        let deal = Deal::random(&mut StdRng::seed_from_u64(2));
+        let replies = "id name scripted\necho ok?\nechook\ninfo thinking\nchoose 3\n";
+        let mut agent = EngineAgent::new(Vec::new(), Cursor::new(replies)).unwrap();
+
+        let input = AgentInput::new(
+            PerPhase::Main(MainPhase::new()),
+            deal.state(),
+            deal.hidden()[0],
+            Player::Me,
+        );
+
+        assert_eq!(agent.name(), "scripted");
+        assert_eq!(agent.choose(input), DecisionIndex(3));
+
+        let sent = String::from_utf8(agent.writer.clone()).unwrap();
+        let commands: Vec<_> = sent
+            .lines()
+            .map(|line| EngineCommand::parse(line).unwrap())
+            .collect();
+
+        assert_eq!(commands.first(), Some(&EngineCommand::Echo));
+        assert_eq!(commands.last(), Some(&EngineCommand::Go));
+        assert!(commands.contains(&EngineCommand::Position(deal.state())));
+    }
+}
+// }}}
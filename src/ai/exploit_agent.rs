@@ -0,0 +1,349 @@
+use super::echo_ai::{AgentInput, EchoAgent};
+use super::greedy_agent::{GreedyEvaluator, OpponentModel};
+use crate::cfr::decision::Probability;
+use crate::cfr::decision_index::DecisionIndex;
+use crate::cfr::phase::PerPhase;
+use crate::cfr::reveal_index::RevealIndex;
+use crate::game::choice::FinalMainPhaseChoice;
+use crate::game::creature::{Creature, CreatureSet};
+use crate::game::edict::{Edict, EdictSet};
+use crate::game::known_state::KnownState;
+use crate::game::known_state_summary::KnownStateEssentials;
+use crate::game::types::{Player, Score};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use tracing::Level;
+
+// {{{ Opponent statistics
+/// Tendencies of an opponent, gathered over the course of multiple games.
+///
+/// Every propensity is computed with a small prior pulling it towards
+/// uniform play, so a handful of observations cannot skew the model too much.
+#[derive(Debug, Clone, Default)]
+pub struct OpponentStats {
+    /// The number of games observed so far.
+    pub games: usize,
+
+    /// How many times each edict has been played.
+    edicts_played: [f32; 5],
+    /// How many times each edict has been in the hand of the opponent.
+    edicts_available: [f32; 5],
+
+    /// How many times each creature has been played, for each turn.
+    creatures_played: [[f32; 11]; 4],
+    /// How many times each creature could have been played, for each turn.
+    creatures_possible: [[f32; 11]; 4],
+
+    /// How many times each creature has been guessed during the sabotage phase.
+    creatures_guessed: [f32; 11],
+    /// The number of sabotage guesses made against us.
+    guesses: f32,
+}
+
+impl OpponentStats {
+    /// Returns how likely the opponent is to play an edict, when it's available.
+    pub fn edict_propensity(&self, edict: Edict) -> Probability {
+        let index = edict as usize;
+        (self.edicts_played[index] + 1.0) / (self.edicts_available[index] + 2.0)
+    }
+
+    /// Returns how likely the opponent is to play a creature on a given turn,
+    /// when it's possibly in their hand.
+    pub fn creature_propensity(&self, turn: usize, creature: Creature) -> Probability {
+        let index = creature as usize;
+        (self.creatures_played[turn][index] + 1.0) / (self.creatures_possible[turn][index] + 2.0)
+    }
+
+    /// Returns how likely the sabotage guesses of the opponent are to name each creature
+    /// (indexed by creature). Creatures in the graveyard cannot be guessed anymore.
+    pub fn guess_frequencies(&self, state: &KnownState) -> [Probability; 11] {
+        let uniform = OpponentModel::uniform_guess_accuracy(state);
+        let mut frequencies = [0.0; 11];
+
+        for creature in !state.graveyard {
+            let index = creature as usize;
+            frequencies[index] =
+                (self.creatures_guessed[index] + 2.0 * uniform) / (self.guesses + 2.0);
+        }
+
+        frequencies
+    }
+
+    /// Builds a model of the plays the opponent might make this turn.
+    pub fn model(&self, state: &KnownState, player: Player, hand: CreatureSet) -> OpponentModel {
+        let turn = state.battlefields.current;
+        let creatures = OpponentModel::possible_creatures(state, hand);
+        let edicts = state.player_edicts(!player);
+
+        let mut plays: Vec<_> = creatures
+            .into_iter()
+            .flat_map(|creature| {
+                edicts.into_iter().map(move |edict| {
+                    let weight =
+                        self.creature_propensity(turn, creature) * self.edict_propensity(edict);
+
+                    (FinalMainPhaseChoice::new(creature, edict), weight)
+                })
+            })
+            .collect();
+
+        let total: Probability = plays.iter().map(|(_, weight)| weight).sum();
+        for (_, weight) in &mut plays {
+            *weight /= total;
+        }
+
+        OpponentModel::new(plays, self.guess_frequencies(state))
+    }
+
+    /// Records the edict played by the opponent.
+    fn record_edict(&mut self, available: EdictSet, played: Edict) {
+        for edict in available {
+            self.edicts_available[edict as usize] += 1.0;
+        }
+
+        self.edicts_played[played as usize] += 1.0;
+    }
+
+    /// Records the creature played by the opponent on some turn.
+    fn record_creature(&mut self, turn: usize, possible: CreatureSet, played: Creature) {
+        for creature in possible {
+            self.creatures_possible[turn][creature as usize] += 1.0;
+        }
+
+        self.creatures_played[turn][played as usize] += 1.0;
+    }
+
+    /// Records a sabotage guess made against us.
+    fn record_guess(&mut self, guess: Creature) {
+        self.guesses += 1.0;
+        self.creatures_guessed[guess as usize] += 1.0;
+    }
+}
+// }}}
+// {{{ Settings
+/// Controls how aggressively the agent exploits it's opponent.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct ExploitSettings {
+    /// The evaluator used for computing best responses.
+    pub evaluator: GreedyEvaluator,
+    /// The number of games to observe before deviating from the base strategy.
+    pub min_games: usize,
+    /// After this many games, we follow the best response half of the time.
+    pub confidence_games: f32,
+    /// Deviations from the base strategy are only allowed if they lose at most
+    /// this many points against an opponent playing uniformly at random.
+    pub safety_margin: f32,
+}
+
+impl Default for ExploitSettings {
+    fn default() -> Self {
+        Self {
+            evaluator: GreedyEvaluator::default(),
+            min_games: 3,
+            confidence_games: 10.0,
+            safety_margin: 0.5,
+        }
+    }
+}
+// }}}
+// {{{ Agent
+/// An echo agent which starts off playing like some base agent (usually a blueprint),
+/// while gathering statistics about the tendencies of it's opponent.
+/// As evidence accumulates, the agent shifts towards a best response to said tendencies.
+///
+/// The statistics persist across games, so the same agent should be
+/// reused for an entire match.
+pub struct ExploitAgent<A, R> {
+    base: A,
+    stats: OpponentStats,
+    settings: ExploitSettings,
+    rng: R,
+
+    /// The last input we've received, used for decoding reveals.
+    last_input: Option<AgentInput>,
+}
+
+impl<A: EchoAgent, R: Rng> ExploitAgent<A, R> {
+    pub fn new(base: A, settings: ExploitSettings, rng: R) -> Self {
+        Self {
+            base,
+            stats: OpponentStats::default(),
+            settings,
+            rng,
+            last_input: None,
+        }
+    }
+
+    /// Returns the statistics gathered about the opponent so far.
+    #[inline(always)]
+    pub fn stats(&self) -> &OpponentStats {
+        &self.stats
+    }
+
+    /// Returns how likely we are to follow the best response
+    /// when it's deemed safe to do so.
+    pub fn confidence(&self) -> Probability {
+        let games = self.stats.games as f32;
+        games / (games + self.settings.confidence_games)
+    }
+
+    /// Updates the statistics given the information revealed at the end of a phase.
+    fn observe(&mut self, agent_input: AgentInput, reveal_index: RevealIndex) -> Option<()> {
+        let AgentInput {
+            phase,
+            state,
+            player,
+            hidden,
+        } = agent_input;
+
+        match phase {
+            PerPhase::Main(_) => {
+                let edicts = reveal_index.decode_main_phase_reveal(state.edict_sets())?;
+                self.stats
+                    .record_edict(state.player_edicts(!player), (!player).select(edicts));
+            }
+            PerPhase::Sabotage(_) => {
+                let PerPhase::Seer(next) = phase.advance_phase(&state, reveal_index)? else {
+                    return None;
+                };
+
+                if let Some(guess) = (!player).select(next.sabotage_choices) {
+                    self.stats.record_guess(guess);
+                }
+            }
+            PerPhase::Seer(seer) => {
+                let revealer = state.last_creature_revealer();
+                let revealer_creature = reveal_index
+                    .decode_seer_phase_reveal(state.graveyard, seer.revealed_creature)?;
+                let creatures = revealer.order_as([revealer_creature, seer.revealed_creature]);
+                let theirs = (!player).select(creatures);
+
+                let possible = OpponentModel::possible_creatures(&state, hidden.get_main());
+                self.stats
+                    .record_creature(state.battlefields.current, possible, theirs);
+            }
+        }
+
+        Some(())
+    }
+}
+
+impl<A: EchoAgent, R: Rng> EchoAgent for ExploitAgent<A, R> {
+    fn choose(&mut self, agent_input: AgentInput) -> DecisionIndex {
+        self.last_input = Some(agent_input);
+
+        let base = self.base.choose(agent_input);
+
+        if self.stats.games < self.settings.min_games {
+            return base;
+        }
+
+        let evaluator = self.settings.evaluator;
+        let hand = agent_input.hidden.get_main();
+        let model = self
+            .stats
+            .model(&agent_input.state, agent_input.player, hand);
+        let best = evaluator.choose(agent_input, &model);
+
+        if best == base {
+            return base;
+        }
+
+        // {{{ Safety check
+        let uniform = OpponentModel::uniform(&agent_input.state, agent_input.player, hand);
+        let loss = evaluator.decision_value(agent_input, &uniform, base)
+            - evaluator.decision_value(agent_input, &uniform, best);
+
+        if loss > self.settings.safety_margin {
+            tracing::event!(
+                Level::DEBUG,
+                loss,
+                "Best response deemed unsafe, sticking to the base strategy"
+            );
+
+            return base;
+        }
+        // }}}
+
+        if self.rng.gen::<f32>() < self.confidence() {
+            tracing::event!(
+                Level::DEBUG,
+                ?base,
+                ?best,
+                "Deviating from the base strategy"
+            );
+            best
+        } else {
+            base
+        }
+    }
+
+    fn reveal_info(&mut self, reveal_index: RevealIndex, updated_score: Score) {
+        if let Some(agent_input) = self.last_input {
+            if self.observe(agent_input, reveal_index).is_none() {
+                tracing::event!(Level::WARN, "Could not decode reveal index");
+            }
+        }
+
+        self.base.reveal_info(reveal_index, updated_score);
+    }
+
+    fn game_finished(&mut self) {
+        self.stats.games += 1;
+        self.last_input = None;
+
+        self.base.game_finished();
+    }
+}
+// }}}
+// {{{ Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::greedy_agent::GreedyAgent;
+    use crate::cfr::hidden_index::EncodingInfo;
+    use crate::cfr::phase::MainPhase;
+    use crate::game::notation::Position;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    /// Returns the creature played against an opponent
+    /// who always sabotages, guessing the same creature.
+    fn played_against(guess: Creature) -> Creature {
+        let position = Position::parse("PPPL 3 +0 WSRBDG RG/SG -/- m MK").unwrap();
+        let (state, hand) = (position.state, position.hidden.unwrap().1.hand);
+
+        let settings = ExploitSettings {
+            min_games: 0,
+            confidence_games: 0.0,
+            safety_margin: f32::INFINITY,
+            ..ExploitSettings::default()
+        };
+        let mut agent =
+            ExploitAgent::new(GreedyAgent::default(), settings, StdRng::seed_from_u64(0));
+
+        for _ in 0..30 {
+            agent.stats.games += 1;
+            agent
+                .stats
+                .record_edict(state.player_edicts(Player::You), Edict::Sabotage);
+            agent.stats.record_guess(guess);
+        }
+
+        let phase = PerPhase::Main(MainPhase::new());
+        let input = AgentInput::new(phase, state, EncodingInfo::Main(hand), Player::Me);
+        let (creatures, _) = agent
+            .choose(input)
+            .decode_main_phase_index(&state, Player::Me, hand)
+            .unwrap();
+
+        creatures.into_iter().next().unwrap()
+    }
+
+    #[test]
+    fn habitual_guesses_are_avoided() {
+        assert_eq!(played_against(Creature::Mercenary), Creature::Monarch);
+        assert_eq!(played_against(Creature::Monarch), Creature::Mercenary);
+    }
+}
+// }}}
@@ -0,0 +1,607 @@
+use crate::cfr::decision_index::DecisionIndex;
+use crate::cfr::hidden_index::EncodingInfo;
+use crate::cfr::phase::{PerPhase, PhaseTag, SomePhase};
+use crate::cfr::reveal_index::RevealIndex;
+use crate::game::battlefield::{Battlefield, Battlefields};
+use crate::game::creature::{Creature, CreatureSet};
+use crate::game::edict::{Edict, EdictSet};
+use crate::game::known_state::{KnownPlayerState, KnownState};
+use crate::game::rules::RuleSet;
+use crate::game::status_effect::{StatusEffect, StatusEffectSet};
+use crate::game::types::{Player, Score};
+use crate::helpers::bitfield::Bitfield;
+use crate::helpers::pair::Pair;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Debug, Display};
+use std::str::FromStr;
+
+/// The first line of every record. Bump the version when changing the format!
+pub const RECORD_HEADER: &str = "echo-record 2";
+
+/// The first line of records written before the rules were stored
+/// in them. Such records are played under the standard rules.
+const LEGACY_RECORD_HEADER: &str = "echo-record 1";
+
+// {{{ Choices
+/// A decision made by a player, in human readable form.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RecordedChoice {
+    /// The creatures and edict played during the main phase.
+    Main(CreatureSet, Edict),
+    /// The sabotage guess (if any).
+    Sabotage(Option<Creature>),
+    /// The creature kept during the seer phase.
+    Seer(Creature),
+}
+
+impl RecordedChoice {
+    /// Decodes the decision a player has made during some phase.
+    pub fn decode(
+        phase: &SomePhase,
+        state: &KnownState,
+        player: Player,
+        hidden: EncodingInfo,
+        decision: DecisionIndex,
+    ) -> Option<Self> {
+        let hand = hidden.get_main();
+
+        let choice = match phase {
+            PerPhase::Main(_) => {
+                let (creatures, edict) = decision.decode_main_phase_index(state, player, hand)?;
+                Self::Main(creatures, edict)
+            }
+            PerPhase::Sabotage(_) => Self::Sabotage(decision.decode_sabotage_index(
+                state,
+                hand,
+                phase.sabotage_status(player),
+            )?),
+            PerPhase::Seer(_) => Self::Seer(decision.decode_seer_index(hidden.get_sabotage()?)?),
+        };
+
+        Some(choice)
+    }
+
+    /// The phase this choice has been made in.
+    pub fn tag(self) -> PhaseTag {
+        match self {
+            Self::Main(_, _) => PhaseTag::Main,
+            Self::Sabotage(_) => PhaseTag::Sabotage,
+            Self::Seer(_) => PhaseTag::Seer,
+        }
+    }
+
+    pub(crate) fn parse(tag: PhaseTag, input: &str) -> Result<Self, String> {
+        match tag {
+            PhaseTag::Main => {
+                let (creatures, edict) = input
+                    .split_once('/')
+                    .ok_or_else(|| format!("Expected <creatures>/<edict>, got {input:?}"))?;
+
+                Ok(Self::Main(
+                    parse_set(creatures, &Creature::CREATURES)?,
+                    parse_named(edict, &Edict::EDICTS)?,
+                ))
+            }
+            PhaseTag::Sabotage if input == "-" => Ok(Self::Sabotage(None)),
+            PhaseTag::Sabotage => Ok(Self::Sabotage(Some(parse_named(
+                input,
+                &Creature::CREATURES,
+            )?))),
+            PhaseTag::Seer => Ok(Self::Seer(parse_named(input, &Creature::CREATURES)?)),
+        }
+    }
+}
+
+impl Display for RecordedChoice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Main(creatures, edict) => write!(f, "{}/{edict}", SetDisplay(*creatures)),
+            Self::Sabotage(None) => write!(f, "-"),
+            Self::Sabotage(Some(creature)) | Self::Seer(creature) => write!(f, "{creature}"),
+        }
+    }
+}
+// }}}
+// {{{ Records
+/// Everything that happened during a single phase.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PhaseRecord {
+    pub decisions: Pair<DecisionIndex>,
+    pub choices: Pair<RecordedChoice>,
+    pub reveal: RevealIndex,
+    /// The score at the end of the phase.
+    pub score: Score,
+}
+
+impl PhaseRecord {
+    #[inline(always)]
+    pub fn tag(&self) -> PhaseTag {
+        self.choices[0].tag()
+    }
+}
+
+/// Everything required to replay a game, starting from the main phase of some turn.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GameRecord {
+    /// The rules the game has been played under.
+    #[serde(default)]
+    pub rules: RuleSet,
+    /// The state the game has started in.
+    pub state: KnownState,
+    /// The hands dealt to the players.
+    pub hands: Pair<CreatureSet>,
+    pub phases: Vec<PhaseRecord>,
+    /// The final score, if the game has finished.
+    pub result: Option<Score>,
+}
+
+impl GameRecord {
+    pub fn new(rules: RuleSet, state: KnownState, hands: Pair<CreatureSet>) -> Self {
+        Self {
+            rules,
+            state,
+            hands,
+            phases: Vec::new(),
+            result: None,
+        }
+    }
+
+    /// The creature dealt to neither player.
+    pub fn overseer(&self) -> Option<Creature> {
+        (!(self.state.graveyard | self.hands[0] | self.hands[1]))
+            .into_iter()
+            .exactly_one()
+            .ok()
+    }
+
+    /// The hidden information each player started with.
+    #[inline(always)]
+    pub fn initial_hidden(&self) -> Pair<EncodingInfo> {
+        self.hands.map(EncodingInfo::Main)
+    }
+
+    /// Parses a record written using the `Display` implementation.
+    pub fn parse(input: &str) -> Result<Self, ParseError> {
+        let mut lines = input
+            .lines()
+            .enumerate()
+            .map(|(index, line)| (index + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'));
+
+        let mut next = |key: &str| -> Result<(usize, &str), ParseError> {
+            let (line, content) = lines.next().ok_or_else(|| ParseError {
+                line: input.lines().count(),
+                message: format!("Unexpected end of record, expected {key:?}"),
+            })?;
+
+            let rest = content.strip_prefix(key).ok_or_else(|| ParseError {
+                line,
+                message: format!("Expected line starting with {key:?}, got {content:?}"),
+            })?;
+
+            Ok((line, rest.trim()))
+        };
+
+        let (line, header) = next("echo-record")?;
+        let rules = match format!("echo-record {header}").as_str() {
+            RECORD_HEADER => {
+                let (line, rules) = next("rules")?;
+                parse_rules(rules).map_err(|message| ParseError::new(line, message))?
+            }
+            LEGACY_RECORD_HEADER => RuleSet::default(),
+            _ => {
+                return Err(ParseError::new(
+                    line,
+                    format!("Unsupported version {header:?}"),
+                ))
+            }
+        };
+
+        // {{{ Initial state
+        let (line, battlefields) = next("battlefields")?;
+        let battlefields: [Battlefield; 4] = battlefields
+            .split_whitespace()
+            .map(|name| parse_named(name, &Battlefield::BATTLEFIELDS))
+            .collect::<Result<Vec<_>, _>>()
+            .and_then(|all| {
+                all.try_into()
+                    .map_err(|_| "Expected exactly 4 battlefields".to_string())
+            })
+            .map_err(|message| ParseError::new(line, message))?;
+
+        let (line, turn) = next("turn")?;
+        let current = parse_number(turn)
+            .filter(|turn| *turn < 4)
+            .ok_or_else(|| ParseError::new(line, format!("Invalid turn {turn:?}")))?;
+
+        let (line, score) = next("score")?;
+        let score = parse_score(score).map_err(|message| ParseError::new(line, message))?;
+
+        let (line, graveyard) = next("graveyard")?;
+        let graveyard = parse_set::<CreatureSet>(graveyard, &Creature::CREATURES)
+            .map_err(|message| ParseError::new(line, message))?;
+
+        let (line, edicts) = next("edicts")?;
+        let edicts = parse_pair(edicts, |set| parse_set::<EdictSet>(set, &Edict::EDICTS))
+            .map_err(|message| ParseError::new(line, message))?;
+
+        let (line, effects) = next("effects")?;
+        let effects = parse_pair(effects, |set| {
+            parse_set::<StatusEffectSet>(set, &StatusEffect::STATUS_EFFECTS)
+        })
+        .map_err(|message| ParseError::new(line, message))?;
+
+        let (line, hands) = next("hands")?;
+        let hands = parse_pair(hands, |set| {
+            parse_set::<CreatureSet>(set, &Creature::CREATURES)
+        })
+        .map_err(|message| ParseError::new(line, message))?;
+
+        let state = KnownState {
+            player_states: [0, 1].map(|i| KnownPlayerState {
+                edicts: edicts[i],
+                effects: effects[i],
+            }),
+            battlefields: Battlefields {
+                all: battlefields,
+                current,
+            },
+            graveyard,
+            score,
+        };
+
+        let mut record = Self::new(rules, state, hands);
+
+        let (line, overseer) = next("overseer")?;
+        let overseer = parse_named(overseer, &Creature::CREATURES)
+            .map_err(|message| ParseError::new(line, message))?;
+
+        if record.overseer() != Some(overseer) {
+            return Err(ParseError::new(
+                line,
+                format!("The overseer does not match the hands ({overseer} given)"),
+            ));
+        }
+        // }}}
+        // {{{ Phases
+        for (line, content) in lines {
+            let result = if let Some(result) = content.strip_prefix("result") {
+                parse_score(result.trim()).map(|score| record.result = Some(score))
+            } else if record.result.is_some() {
+                Err("No phases can follow the result of the game".to_string())
+            } else {
+                parse_phase(content).map(|phase| record.phases.push(phase))
+            };
+
+            result.map_err(|message| ParseError::new(line, message))?;
+        }
+        // }}}
+
+        Ok(record)
+    }
+}
+
+impl Display for GameRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [me, you] = self.state.player_states;
+
+        writeln!(f, "{RECORD_HEADER}")?;
+        if self.rules == RuleSet::standard() {
+            writeln!(f, "rules standard")?;
+        } else {
+            let rules = serde_json::to_string(&self.rules).map_err(|_| fmt::Error)?;
+            writeln!(f, "rules {rules}")?;
+        }
+        writeln!(
+            f,
+            "battlefields {}",
+            self.state.battlefields.all.iter().join(" ")
+        )?;
+        writeln!(f, "turn {}", self.state.battlefields.current)?;
+        writeln!(f, "score {}", self.state.score.0)?;
+        writeln!(f, "graveyard {}", SetDisplay(self.state.graveyard))?;
+        writeln!(
+            f,
+            "edicts {} | {}",
+            SetDisplay(me.edicts),
+            SetDisplay(you.edicts)
+        )?;
+        writeln!(
+            f,
+            "effects {} | {}",
+            SetDisplay(me.effects),
+            SetDisplay(you.effects)
+        )?;
+        writeln!(
+            f,
+            "hands {} | {}",
+            SetDisplay(self.hands[0]),
+            SetDisplay(self.hands[1])
+        )?;
+
+        match self.overseer() {
+            Some(overseer) => writeln!(f, "overseer {overseer}")?,
+            None => writeln!(f, "overseer -")?,
+        }
+
+        for phase in &self.phases {
+            writeln!(
+                f,
+                "{} decisions {} {} choices {} {} reveal {} score {}",
+                tag_name(phase.tag()),
+                phase.decisions[0].0,
+                phase.decisions[1].0,
+                phase.choices[0],
+                phase.choices[1],
+                phase.reveal.0,
+                phase.score.0
+            )?;
+        }
+
+        if let Some(result) = self.result {
+            writeln!(f, "result {}", result.0)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for GameRecord {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+// }}}
+// {{{ Parsing helpers
+/// Error returned when a record cannot be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    /// The (1-indexed) line the error occured on.
+    pub line: usize,
+    pub message: String,
+}
+
+impl ParseError {
+    pub(crate) fn new(line: usize, message: String) -> Self {
+        Self { line, message }
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Displays a bitfield as a comma separated list (or `-` if empty).
+pub(crate) struct SetDisplay<B>(pub B);
+
+impl<B: Bitfield> Display for SetDisplay<B>
+where
+    B::Element: Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0.len() == 0 {
+            write!(f, "-")
+        } else {
+            write!(f, "{}", self.0.into_iter().join(","))
+        }
+    }
+}
+
+pub(crate) fn tag_name(tag: PhaseTag) -> &'static str {
+    match tag {
+        PhaseTag::Main => "main",
+        PhaseTag::Sabotage => "sabotage",
+        PhaseTag::Seer => "seer",
+    }
+}
+
+pub fn parse_named<T: Debug + Copy>(name: &str, all: &[T]) -> Result<T, String> {
+    all.iter()
+        .copied()
+        .find(|value| format!("{value:?}") == name)
+        .ok_or_else(|| format!("Unknown name {name:?}"))
+}
+
+pub fn parse_set<B: Bitfield>(input: &str, all: &[B::Element]) -> Result<B, String>
+where
+    B::Element: Debug + Copy,
+{
+    let mut result = B::empty();
+
+    if input != "-" {
+        for name in input.split(',') {
+            let element = parse_named(name, all)?;
+
+            if result.has(element) {
+                return Err(format!("Duplicate name {name:?} in {input:?}"));
+            }
+
+            result.insert(element);
+        }
+    }
+
+    Ok(result)
+}
+
+pub fn parse_pair<T>(
+    input: &str,
+    parse: impl Fn(&str) -> Result<T, String>,
+) -> Result<Pair<T>, String> {
+    let (me, you) = input
+        .split_once('|')
+        .ok_or_else(|| format!("Expected <first> | <second>, got {input:?}"))?;
+
+    Ok([parse(me.trim())?, parse(you.trim())?])
+}
+
+pub(crate) fn parse_number(input: &str) -> Option<usize> {
+    input.parse().ok()
+}
+
+/// Parses the rules a record has been played under, which are either
+/// `standard`, or a variant written in JSON.
+fn parse_rules(input: &str) -> Result<RuleSet, String> {
+    match input {
+        "standard" => Ok(RuleSet::standard()),
+        _ => serde_json::from_str(input).map_err(|error| format!("Invalid rules: {error}")),
+    }
+}
+
+pub(crate) fn parse_score(input: &str) -> Result<Score, String> {
+    input
+        .parse()
+        .map(Score)
+        .map_err(|_| format!("Invalid score {input:?}"))
+}
+
+fn parse_phase(input: &str) -> Result<PhaseRecord, String> {
+    let tokens: Vec<_> = input.split_whitespace().collect();
+
+    let [tag, "decisions", d1, d2, "choices", c1, c2, "reveal", reveal, "score", score] =
+        tokens[..]
+    else {
+        return Err(format!(
+            "Expected <phase> decisions <a> <b> choices <a> <b> reveal <r> score <s>, got {input:?}"
+        ));
+    };
+
+    let tag = match tag {
+        "main" => PhaseTag::Main,
+        "sabotage" => PhaseTag::Sabotage,
+        "seer" => PhaseTag::Seer,
+        _ => return Err(format!("Unknown phase {tag:?}")),
+    };
+
+    let index = |input: &str| parse_number(input).ok_or_else(|| format!("Invalid index {input:?}"));
+
+    Ok(PhaseRecord {
+        decisions: [DecisionIndex(index(d1)?), DecisionIndex(index(d2)?)],
+        choices: [
+            RecordedChoice::parse(tag, c1)?,
+            RecordedChoice::parse(tag, c2)?,
+        ],
+        reveal: RevealIndex(index(reveal)?),
+        score: parse_score(score)?,
+    })
+}
+// }}}
+// {{{ Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::echo_ai::EchoRunner;
+    use crate::ai::random_agent::RandomAgent;
+    use crate::cfr::phase::{MainPhase, Phase};
+    use crate::game::known_state_summary::KnownStateEssentials;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+    use std::sync::mpsc;
+
+    fn random_records(count: usize) -> Vec<GameRecord> {
+        let mut rng = StdRng::seed_from_u64(0);
+        let (sender, receiver) = mpsc::channel();
+
+        for _ in 0..count {
+            let mut battlefields = Battlefield::BATTLEFIELDS;
+            for i in 0..4 {
+                battlefields.swap(i, rng.gen_range(i..6));
+            }
+
+            let state = KnownState::new_starting(battlefields[0..4].try_into().unwrap());
+            let hidden_states: Vec<_> = MainPhase::new()
+                .valid_hidden_states(state.to_summary())
+                .collect();
+            let hidden = hidden_states[rng.gen_range(0..hidden_states.len())];
+            let agents = (
+                RandomAgent::new(StdRng::seed_from_u64(rng.gen())),
+                RandomAgent::new(StdRng::seed_from_u64(rng.gen())),
+            );
+
+            EchoRunner::new(state, PerPhase::Main(MainPhase::new()), agents, hidden)
+                .record_to(sender.clone())
+                .run_game()
+                .unwrap();
+        }
+
+        drop(sender);
+        receiver.into_iter().collect()
+    }
+
+    #[test]
+    fn records_are_emitted_for_every_game() {
+        let records = random_records(20);
+
+        assert_eq!(records.len(), 20);
+
+        for record in records {
+            assert!(record.overseer().is_some());
+            assert!(record.result.is_some());
+            assert_eq!(record.phases.last().unwrap().score, record.result.unwrap());
+        }
+    }
+
+    #[test]
+    fn print_parse_roundtrip() {
+        for record in random_records(50) {
+            let printed = record.to_string();
+            let parsed = GameRecord::parse(&printed).unwrap();
+
+            assert_eq!(parsed, record, "{printed}");
+            assert_eq!(parsed.to_string(), printed);
+        }
+    }
+
+    #[test]
+    fn json_roundtrip() {
+        for record in random_records(20) {
+            let json = serde_json::to_string(&record).unwrap();
+            let parsed: GameRecord = serde_json::from_str(&json).unwrap();
+
+            assert_eq!(parsed, record, "{json}");
+        }
+    }
+
+    #[test]
+    fn parse_errors_point_at_the_right_line() {
+        let record = random_records(1).pop().unwrap().to_string();
+        let broken = record.replacen("overseer", "oversear", 1);
+        let error = GameRecord::parse(&broken).unwrap_err();
+
+        assert_eq!(error.line, 10);
+    }
+
+    #[test]
+    fn sets_reject_duplicate_names() {
+        let set = parse_set::<CreatureSet>("Wall,Seer", &Creature::CREATURES).unwrap();
+        assert_eq!(
+            set.into_iter().collect::<Vec<_>>(),
+            [Creature::Wall, Creature::Seer]
+        );
+
+        assert_eq!(
+            parse_set::<CreatureSet>("Mercenary,Wall,Mercenary", &Creature::CREATURES),
+            Err(String::from(
+                "Duplicate name \"Mercenary\" in \"Mercenary,Wall,Mercenary\""
+            ))
+        );
+    }
+
+    #[test]
+    fn legacy_records_use_the_standard_rules() {
+        let record = random_records(1).pop().unwrap();
+        let legacy = record
+            .to_string()
+            .replacen(RECORD_HEADER, LEGACY_RECORD_HEADER, 1)
+            .replacen("rules standard\n", "", 1);
+
+        assert_eq!(GameRecord::parse(&legacy), Ok(record));
+    }
+}
+// }}}
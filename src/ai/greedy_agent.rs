@@ -0,0 +1,512 @@
+use super::echo_ai::{AgentInput, EchoAgent};
+use crate::cfr::decision::Probability;
+use crate::cfr::decision_index::DecisionIndex;
+use crate::cfr::phase::{PerPhase, SabotagePhase, SeerPhase};
+use crate::game::choice::{FinalMainPhaseChoice, SabotagePhaseChoice};
+use crate::game::creature::{Creature, CreatureSet};
+use crate::game::edict::Edict;
+use crate::game::known_state::KnownState;
+use crate::game::known_state_summary::KnownStateEssentials;
+use crate::game::rules::RuleSet;
+use crate::game::simulate::BattleContext;
+use crate::game::types::{Player, Score, TurnResult};
+use crate::helpers::bitfield::Bitfield;
+use serde::{Deserialize, Serialize};
+
+// {{{ Opponent model
+/// A possible main phase play of the opponent, together with it's likelihood.
+pub type OpponentPlay = (FinalMainPhaseChoice, Probability);
+
+/// What we expect the opponent to do during a single turn.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OpponentModel {
+    /// Distribution over the main phase plays of the opponent.
+    pub plays: Vec<OpponentPlay>,
+    /// The probability a sabotage guess made by the opponent
+    /// names each creature (indexed by creature).
+    pub guesses: [Probability; 11],
+}
+
+impl OpponentModel {
+    pub fn new(plays: Vec<OpponentPlay>, guesses: [Probability; 11]) -> Self {
+        Self { plays, guesses }
+    }
+
+    /// The probability a sabotage guess made by the opponent hits a given creature of ours.
+    #[inline(always)]
+    pub fn guess_accuracy(&self, creature: Creature) -> Probability {
+        self.guesses[creature as usize]
+    }
+
+    /// Returns every creature the opponent might have in hand,
+    /// as seen by a player holding the given hand.
+    #[inline(always)]
+    pub fn possible_creatures(state: &KnownState, hand: CreatureSet) -> CreatureSet {
+        !(state.graveyard | hand)
+    }
+
+    /// Assumes the opponent plays every creature they might have,
+    /// together with every edict they do have, with equal probability,
+    /// and that sabotage guesses are made uniformly at random.
+    pub fn uniform(state: &KnownState, player: Player, hand: CreatureSet) -> Self {
+        let creatures = Self::possible_creatures(state, hand);
+        let edicts = state.player_edicts(!player);
+        let probability = 1.0 / (creatures.len() * edicts.len()) as Probability;
+
+        let plays = creatures
+            .into_iter()
+            .flat_map(|creature| {
+                edicts
+                    .into_iter()
+                    .map(move |edict| (FinalMainPhaseChoice::new(creature, edict), probability))
+            })
+            .collect();
+
+        Self::new(plays, Self::uniform_guesses(state))
+    }
+
+    /// The probability a uniformly random sabotage guess
+    /// made by the opponent hits the creature we play.
+    pub fn uniform_guess_accuracy(state: &KnownState) -> Probability {
+        let possibilities = (!state.graveyard).len() - state.hand_size();
+
+        1.0 / possibilities as Probability
+    }
+
+    /// Assumes sabotage guesses are made uniformly at random, which means every
+    /// creature outside the graveyard is equally likely to be guessed.
+    pub fn uniform_guesses(state: &KnownState) -> [Probability; 11] {
+        let accuracy = Self::uniform_guess_accuracy(state);
+        let mut guesses = [0.0; 11];
+
+        for creature in !state.graveyard {
+            guesses[creature as usize] = accuracy;
+        }
+
+        guesses
+    }
+
+    /// Distribution over the creatures the opponent plays,
+    /// knowing the edict they have played.
+    pub fn creatures_given_edict(&self, edict: Edict) -> Vec<(Creature, Probability)> {
+        let mut weights = [0.0; 11];
+
+        for (choice, probability) in &self.plays {
+            if choice.edict == edict {
+                weights[choice.creature as usize] += probability;
+            }
+        }
+
+        let total: Probability = weights.iter().sum();
+
+        // The model does not expect this edict at all,
+        // so we have no information to go on.
+        if total <= 0.0 {
+            for (choice, _) in &self.plays {
+                weights[choice.creature as usize] = 1.0;
+            }
+        }
+
+        Self::normalized(weights)
+    }
+
+    /// The creature we would guess with a sabotage edict,
+    /// knowing the edict the opponent has played.
+    pub fn likeliest_creature(&self, edict: Edict) -> Option<Creature> {
+        self.creatures_given_edict(edict)
+            .into_iter()
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(creature, _)| creature)
+    }
+
+    /// Drops the options which are not possible, and rescales the
+    /// probabilities of the remaining ones so they sum up to one.
+    fn renormalized<T>(
+        options: impl IntoIterator<Item = (T, Probability)>,
+        mut possible: impl FnMut(&T) -> bool,
+    ) -> Vec<(T, Probability)> {
+        let options: Vec<_> = options
+            .into_iter()
+            .filter(|(option, _)| possible(option))
+            .collect();
+        let total: Probability = options.iter().map(|(_, probability)| probability).sum();
+
+        if total <= 0.0 {
+            return Vec::new();
+        }
+
+        options
+            .into_iter()
+            .map(|(option, probability)| (option, probability / total))
+            .collect()
+    }
+
+    fn normalized(weights: [Probability; 11]) -> Vec<(Creature, Probability)> {
+        let total: Probability = weights.iter().sum();
+
+        Creature::CREATURES
+            .into_iter()
+            .filter(|creature| weights[*creature as usize] > 0.0)
+            .map(|creature| (creature, weights[creature as usize] / total))
+            .collect()
+    }
+}
+// }}}
+// {{{ Evaluator
+/// One step lookahead evaluation of the choices a player can make.
+///
+/// A choice is worth the expected score delta of the current battle,
+/// minus the value of the creature we give up by playing it
+/// (unless the current battle is the last one).
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct GreedyEvaluator {
+    /// How many points a single point of creature strength is worth
+    /// while the creature is still in our hand.
+    pub hand_weight: f32,
+    /// The rules battles are resolved with.
+    #[serde(default)]
+    pub rules: RuleSet,
+}
+
+impl Default for GreedyEvaluator {
+    fn default() -> Self {
+        Self::new(0.25)
+    }
+}
+
+impl GreedyEvaluator {
+    pub fn new(hand_weight: f32) -> Self {
+        Self {
+            hand_weight,
+            rules: RuleSet::default(),
+        }
+    }
+
+    // {{{ Battles
+    /// Computes the value of a fully determined battle, from the perspective of a given player.
+    pub fn battle_value(
+        &self,
+        state: &KnownState,
+        player: Player,
+        choices: [FinalMainPhaseChoice; 2],
+        guesses: [SabotagePhaseChoice; 2],
+    ) -> f32 {
+        let context = BattleContext::new(
+            player.order_as(choices),
+            player.order_as(guesses),
+            *state,
+            self.rules,
+        );
+
+        let score = match context.advance_known_state().1 {
+            TurnResult::Finished(score) => score,
+            TurnResult::Unfinished(state) => state.score,
+        };
+
+        let delta = Score(score.0 - state.score.0).from_perspective(player).0 as f32;
+
+        delta - self.creature_cost(state, choices[0].creature)
+    }
+
+    /// The value we lose by giving up a creature.
+    #[inline(always)]
+    pub fn creature_cost(&self, state: &KnownState, creature: Creature) -> f32 {
+        if state.battlefields.is_last() {
+            0.0
+        } else {
+            self.hand_weight * self.rules.strength(creature) as f32
+        }
+    }
+
+    /// Evaluates a battle where the opponent might make a sabotage guess
+    /// which hits our creature with the given probability.
+    fn battle_value_against_guess(
+        &self,
+        state: &KnownState,
+        player: Player,
+        choices: [FinalMainPhaseChoice; 2],
+        guess: SabotagePhaseChoice,
+        guess_accuracy: Probability,
+    ) -> f32 {
+        let missed = self.battle_value(state, player, choices, [guess, None]);
+
+        if choices[1].edict != Edict::Sabotage {
+            return missed;
+        }
+
+        let hit = self.battle_value(state, player, choices, [guess, Some(choices[0].creature)]);
+
+        guess_accuracy * hit + (1.0 - guess_accuracy) * missed
+    }
+    // }}}
+    // {{{ Phases
+    /// Evaluates a main phase choice against a distribution of opponent plays.
+    /// When playing two creatures (because of the seer status effect),
+    /// we assume we'll pick the better one once the opponent reveals theirs.
+    ///
+    /// When playing the sabotage edict, we assume we'll guess the creature the
+    /// opponent most likely plays once their edict gets revealed.
+    pub fn main_phase_value(
+        &self,
+        state: &KnownState,
+        player: Player,
+        creatures: CreatureSet,
+        edict: Edict,
+        opponent: &OpponentModel,
+    ) -> f32 {
+        let plays = opponent.plays.iter().copied();
+
+        OpponentModel::renormalized(plays, |theirs| !creatures.has(theirs.creature))
+            .into_iter()
+            .map(|(theirs, probability)| {
+                let guess = match edict {
+                    Edict::Sabotage => opponent.likeliest_creature(theirs.edict),
+                    _ => None,
+                };
+
+                let best = creatures
+                    .into_iter()
+                    .map(|creature| {
+                        let mine = FinalMainPhaseChoice::new(creature, edict);
+                        self.battle_value_against_guess(
+                            state,
+                            player,
+                            [mine, theirs],
+                            guess,
+                            opponent.guess_accuracy(creature),
+                        )
+                    })
+                    .fold(f32::NEG_INFINITY, f32::max);
+
+                probability * best
+            })
+            .sum()
+    }
+
+    /// Evaluates a sabotage guess against the creatures the opponent might have played.
+    pub fn sabotage_phase_value(
+        &self,
+        state: &KnownState,
+        player: Player,
+        phase: &SabotagePhase,
+        creatures: CreatureSet,
+        guess: SabotagePhaseChoice,
+        opponent: &OpponentModel,
+    ) -> f32 {
+        let edicts = player.order_as(phase.edict_choices);
+        let possible = opponent.creatures_given_edict(edicts[1]);
+
+        OpponentModel::renormalized(possible, |theirs| !creatures.has(*theirs))
+            .into_iter()
+            .map(|(theirs, probability)| {
+                let theirs = FinalMainPhaseChoice::new(theirs, edicts[1]);
+                let best = creatures
+                    .into_iter()
+                    .map(|creature| {
+                        let mine = FinalMainPhaseChoice::new(creature, edicts[0]);
+                        self.battle_value_against_guess(
+                            state,
+                            player,
+                            [mine, theirs],
+                            guess,
+                            opponent.guess_accuracy(creature),
+                        )
+                    })
+                    .fold(f32::NEG_INFINITY, f32::max);
+
+                probability * best
+            })
+            .sum()
+    }
+
+    /// Evaluates the creature we keep during the seer phase.
+    /// Everything is known at this point, so no guessing is required.
+    pub fn seer_phase_value(
+        &self,
+        state: &KnownState,
+        player: Player,
+        phase: &SeerPhase,
+        creature: Creature,
+    ) -> f32 {
+        let edicts = player.order_as(phase.edict_choices);
+        let choices = [
+            FinalMainPhaseChoice::new(creature, edicts[0]),
+            FinalMainPhaseChoice::new(phase.revealed_creature, edicts[1]),
+        ];
+
+        self.battle_value(
+            state,
+            player,
+            choices,
+            player.order_as(phase.sabotage_choices),
+        )
+    }
+    // }}}
+    // {{{ Decisions
+    /// Picks the best decision according to some evaluation function.
+    pub fn best_decision(
+        count: usize,
+        mut value: impl FnMut(DecisionIndex) -> f32,
+    ) -> DecisionIndex {
+        (0..count)
+            .map(DecisionIndex)
+            .map(|index| (index, value(index)))
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map_or(DecisionIndex::default(), |(index, _)| index)
+    }
+
+    /// Evaluates a single decision for some input, given a model of the opponent.
+    pub fn decision_value(
+        &self,
+        agent_input: AgentInput,
+        opponent: &OpponentModel,
+        index: DecisionIndex,
+    ) -> f32 {
+        let AgentInput {
+            phase,
+            state,
+            player,
+            hidden,
+        } = agent_input;
+
+        let hand = hidden.get_main();
+
+        match phase {
+            PerPhase::Main(_) => {
+                let (creatures, edict) =
+                    index.decode_main_phase_index(&state, player, hand).unwrap();
+
+                self.main_phase_value(&state, player, creatures, edict, opponent)
+            }
+            PerPhase::Sabotage(phase) => {
+                let creatures = hidden.get_sabotage().unwrap();
+                let guess = index
+                    .decode_sabotage_index(&state, hand, phase.sabotage_status(player))
+                    .unwrap();
+
+                self.sabotage_phase_value(&state, player, &phase, creatures, guess, opponent)
+            }
+            PerPhase::Seer(phase) => {
+                let creatures = hidden.get_sabotage().unwrap();
+                let creature = index.decode_seer_index(creatures).unwrap();
+
+                self.seer_phase_value(&state, player, &phase, creature)
+            }
+        }
+    }
+
+    /// Picks the best decision for some input, given a model of the opponent.
+    pub fn choose(&self, agent_input: AgentInput, opponent: &OpponentModel) -> DecisionIndex {
+        let count = agent_input
+            .player
+            .select(agent_input.phase.decision_counts(&agent_input.state));
+
+        if count == 1 {
+            return DecisionIndex::default();
+        }
+
+        Self::best_decision(count, |index| {
+            self.decision_value(agent_input, opponent, index)
+        })
+    }
+    // }}}
+}
+// }}}
+// {{{ Agent
+/// An echo agent which greedily picks the choice with the best one step lookahead
+/// evaluation, assuming the opponent plays uniformly at random.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GreedyAgent {
+    evaluator: GreedyEvaluator,
+}
+
+impl GreedyAgent {
+    pub fn new(evaluator: GreedyEvaluator) -> Self {
+        Self { evaluator }
+    }
+
+    /// Evaluates battles under some variant of the rules.
+    pub fn with_rules(mut self, rules: RuleSet) -> Self {
+        self.evaluator.rules = rules;
+        self
+    }
+}
+
+impl EchoAgent for GreedyAgent {
+    fn choose(&mut self, agent_input: AgentInput) -> DecisionIndex {
+        let hand = agent_input.hidden.get_main();
+        let opponent = OpponentModel::uniform(&agent_input.state, agent_input.player, hand);
+
+        self.evaluator.choose(agent_input, &opponent)
+    }
+}
+// }}}
+// {{{ Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::match_runner::MatchRunner;
+    use crate::ai::random_agent::RandomAgent;
+    use crate::game::notation::Position;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn last_battle() -> KnownState {
+        Position::parse("PPPL 3 +0 WSRBDG SG/SG -/-").unwrap().state
+    }
+
+    #[test]
+    fn sabotage_is_worth_our_guess() {
+        let state = last_battle();
+        let evaluator = GreedyEvaluator::default();
+        let theirs = FinalMainPhaseChoice::new(Creature::Mercenary, Edict::Gambit);
+        let opponent = OpponentModel::new(vec![(theirs, 1.0)], [0.0; 11]);
+        let creatures = CreatureSet::singleton(Creature::Barbarian);
+        let value =
+            |edict| evaluator.main_phase_value(&state, Player::Me, creatures, edict, &opponent);
+
+        // The opponent is known to play the mercenary, so our guess hits
+        let mine = FinalMainPhaseChoice::new(Creature::Barbarian, Edict::Sabotage);
+        let guessed = [Some(Creature::Mercenary), None];
+        let hit = evaluator.battle_value(&state, Player::Me, [mine, theirs], guessed);
+
+        assert_eq!(value(Edict::Sabotage), hit);
+        assert!(value(Edict::Sabotage) > value(Edict::Gambit));
+    }
+
+    #[test]
+    fn impossible_plays_are_ignored() {
+        let state = last_battle();
+        let evaluator = GreedyEvaluator::default();
+        let creatures = CreatureSet::singleton(Creature::Barbarian);
+        let value = |plays| {
+            let opponent = OpponentModel::new(plays, [0.0; 11]);
+            evaluator.main_phase_value(&state, Player::Me, creatures, Edict::Gambit, &opponent)
+        };
+
+        let possible = FinalMainPhaseChoice::new(Creature::Mercenary, Edict::Gambit);
+        let impossible = FinalMainPhaseChoice::new(Creature::Barbarian, Edict::Gambit);
+
+        assert_ne!(value(vec![(possible, 1.0)]), 0.0);
+        assert_eq!(
+            value(vec![(possible, 0.5), (impossible, 0.5)]),
+            value(vec![(possible, 1.0)])
+        );
+    }
+
+    #[test]
+    fn beats_random_agents() {
+        let random = |seed| RandomAgent::new(StdRng::seed_from_u64(seed));
+        let report = MatchRunner::new(|_| GreedyAgent::default(), random, 200)
+            .with_seed(0)
+            .run();
+
+        assert_eq!(report.errors, 0);
+        assert!(
+            report.win_rate() > 0.75,
+            "Greedy only won {:.1}% of the games",
+            report.win_rate() * 100.0
+        );
+    }
+}
+// }}}
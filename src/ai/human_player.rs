@@ -7,6 +7,7 @@ use crate::game::battlefield::Battlefield;
 use crate::game::creature::{Creature, CreatureSet};
 use crate::game::edict::{Edict, EdictSet};
 use crate::game::known_state_summary::KnownStateEssentials;
+use crate::game::rules::RuleSet;
 use crate::game::status_effect::{StatusEffect, StatusEffectSet};
 use crate::game::types::{Player, Score};
 use crate::helpers::bitfield::Bitfield;
@@ -94,6 +95,9 @@ struct UIState {
     input: AgentInput,
     game_finished: bool,
 
+    /// The rules the game is played under.
+    rules: RuleSet,
+
     // Internal state
     history: [HistoryEntry; 4],
     partial_main_choice: Option<PartialMainPhaseChoice>,
@@ -443,6 +447,9 @@ impl UIState {
 
                 let entry = &mut self.history[self.input.state.battlefields.current];
 
+                // The gui plays without a trained tree, so there is no scope to follow
+                // (unlike agents using a `ScopeCursor`). Advancing the phase on its own
+                // is enough to decode what got revealed.
                 match self
                     .input
                     .phase
@@ -846,14 +853,14 @@ impl egui_dock::TabViewer for UIState {
                         HoveredCard::Creature(creature) => {
                             ui.label(format!(
                                 "Strength = {} + bonuses from:",
-                                creature.strength()
+                                self.rules.strength(creature)
                             ));
 
                             let bonus_image_size = ui.available_width() / 4.0;
 
                             ui.horizontal(|ui| {
                                 for battlefield in Battlefield::BATTLEFIELDS {
-                                    if battlefield.bonus(creature) {
+                                    if self.rules.bonus(battlefield, creature) {
                                         let is_current =
                                             self.input.state.battlefields.current() == battlefield;
                                         let size_multiplier = if is_current { 1.5 } else { 1.0 };
@@ -878,7 +885,7 @@ impl egui_dock::TabViewer for UIState {
                             let mut creatures = CreatureSet::empty();
 
                             for creature in Creature::CREATURES {
-                                if battlefield.bonus(creature) {
+                                if self.rules.bonus(battlefield, creature) {
                                     creatures.insert(creature);
                                 }
                             }
@@ -904,7 +911,7 @@ impl egui_dock::TabViewer for UIState {
                                 ui.separator();
                             }
 
-                            ui.label(format!("Reward: {}", battlefield.reward()));
+                            ui.label(format!("Reward: {}", self.rules.reward(battlefield)));
                         }
                         // }}}
                         _ => {}
@@ -934,9 +941,10 @@ impl egui_dock::TabViewer for UIState {
 // {{{ GUIApp stuff
 impl GUIApp {
     /// Called once before the first frame.
-    pub fn new(_cc: &eframe::CreationContext<'_>, communication: UIBus) -> Self {
+    pub fn new(_cc: &eframe::CreationContext<'_>, communication: UIBus, rules: RuleSet) -> Self {
         let ui_state = UIState {
             input: communication.receiver.recv().unwrap().get_input().unwrap(),
+            rules,
             history: [HistoryEntry::default(); 4],
             partial_main_choice: Some(PartialMainPhaseChoice::default()),
             decision_sent: false,
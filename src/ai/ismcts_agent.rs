@@ -0,0 +1,425 @@
+use super::echo_ai::{AgentInput, EchoAgent};
+use crate::cfr::decision::Utility;
+use crate::cfr::decision_index::DecisionIndex;
+use crate::cfr::hidden_index::{EncodingInfo, HiddenIndex, HiddenState};
+use crate::cfr::phase::{PerPhase, SomePhase};
+use crate::cfr::reveal_index::RevealIndex;
+use crate::game::known_state::KnownState;
+use crate::game::rules::RuleSet;
+use crate::game::types::{Player, TurnResult};
+use crate::helpers::bitfield::Bitfield;
+use crate::helpers::pair::Pair;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tracing::Level;
+
+// {{{ Bandits
+/// The bandit algorithm used to pick decisions at every node of the search tree.
+/// Since both players move simultaneously, each player runs it's own bandit
+/// independently (the decoupled approach).
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Bandit {
+    /// UCB1 with the given exploration constant.
+    Uct { exploration: f32 },
+    /// EXP3 with the given exploration rate (in `(0, 1]`).
+    Exp3 { gamma: f32 },
+}
+
+impl Default for Bandit {
+    fn default() -> Self {
+        Self::Uct { exploration: 0.7 }
+    }
+}
+
+/// Statistics kept by a single player about the decisions at a node.
+#[derive(Debug, Clone)]
+struct BanditStats {
+    visits: Vec<u32>,
+    /// For UCT, the total reward collected after each decision.
+    /// For EXP3, the total importance weighted reward of each decision.
+    rewards: Vec<f32>,
+    total_visits: u32,
+}
+
+impl BanditStats {
+    fn new(count: usize) -> Self {
+        Self {
+            visits: vec![0; count],
+            rewards: vec![0.0; count],
+            total_visits: 0,
+        }
+    }
+
+    /// Computes the EXP3 probability distribution.
+    fn exp3_probabilities(&self, gamma: f32) -> Vec<f32> {
+        let count = self.visits.len() as f32;
+        let eta = gamma / count;
+        let max = self
+            .rewards
+            .iter()
+            .copied()
+            .fold(f32::NEG_INFINITY, f32::max);
+        let weights: Vec<_> = self
+            .rewards
+            .iter()
+            .map(|reward| (eta * (reward - max)).exp())
+            .collect();
+        let total: f32 = weights.iter().sum();
+
+        weights
+            .into_iter()
+            .map(|weight| (1.0 - gamma) * weight / total + gamma / count)
+            .collect()
+    }
+
+    fn select<R: Rng>(&self, bandit: Bandit, rng: &mut R) -> usize {
+        match bandit {
+            Bandit::Uct { exploration } => {
+                let unvisited = self.visits.iter().filter(|v| **v == 0).count();
+
+                if unvisited > 0 {
+                    let nth = rng.gen_range(0..unvisited);
+                    return self
+                        .visits
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, v)| **v == 0)
+                        .nth(nth)
+                        .unwrap()
+                        .0;
+                }
+
+                let log_total = (self.total_visits as f32).ln();
+
+                (0..self.visits.len())
+                    .map(|index| {
+                        let visits = self.visits[index] as f32;
+                        let mean = self.rewards[index] / visits;
+
+                        (index, mean + exploration * (log_total / visits).sqrt())
+                    })
+                    .max_by(|a, b| a.1.total_cmp(&b.1))
+                    .unwrap()
+                    .0
+            }
+            Bandit::Exp3 { gamma } => {
+                let probabilities = self.exp3_probabilities(gamma);
+                let mut sample = rng.gen::<f32>();
+
+                for (index, probability) in probabilities.iter().enumerate() {
+                    sample -= probability;
+                    if sample <= 0.0 {
+                        return index;
+                    }
+                }
+
+                probabilities.len() - 1
+            }
+        }
+    }
+
+    /// Records the reward (in `[0, 1]`) obtained after taking some decision.
+    fn update(&mut self, bandit: Bandit, index: usize, reward: f32) {
+        let reward = match bandit {
+            Bandit::Uct { .. } => reward,
+            Bandit::Exp3 { gamma } => reward / self.exp3_probabilities(gamma)[index],
+        };
+
+        self.visits[index] += 1;
+        self.total_visits += 1;
+        self.rewards[index] += reward;
+    }
+
+    /// The decision we trust the most — the one visited most often.
+    fn most_visited(&self) -> usize {
+        (0..self.visits.len())
+            .max_by_key(|index| self.visits[*index])
+            .unwrap_or(0)
+    }
+}
+// }}}
+// {{{ Search tree
+/// A node of the search tree. Nodes correspond to information sets of the
+/// searching player, which are fully determined by the decisions said player
+/// has taken, together with the information revealed along the way.
+///
+/// The opponent does not share our information set, so their statistics are
+/// kept separately for every hidden state they might be in.
+#[derive(Debug, Clone)]
+struct Node {
+    mine: BanditStats,
+    theirs: HashMap<HiddenIndex, BanditStats>,
+    children: HashMap<(DecisionIndex, RevealIndex), usize>,
+}
+
+impl Node {
+    fn new(count: usize) -> Self {
+        Self {
+            mine: BanditStats::new(count),
+            theirs: HashMap::new(),
+            children: HashMap::new(),
+        }
+    }
+}
+
+/// A fully determined game, played forward during the search.
+#[derive(Debug, Clone, Copy)]
+struct SimulatedGame {
+    state: KnownState,
+    phase: SomePhase,
+    hidden: Pair<EncodingInfo>,
+    rules: RuleSet,
+}
+
+impl SimulatedGame {
+    /// Moves the game forward, returning the final score (if the game ends).
+    fn advance(
+        &mut self,
+        decisions: Pair<DecisionIndex>,
+    ) -> Option<(RevealIndex, Option<Utility>)> {
+        let (reveal_index, result) = self.phase.advance(
+            self.state,
+            self.hidden.map(HiddenState::from_encoding_info),
+            decisions,
+            self.rules,
+        )?;
+
+        let utility = match result {
+            TurnResult::Finished(score) => Some(score.to_utility()),
+            TurnResult::Unfinished((state, hidden, phase)) => {
+                *self = Self {
+                    state,
+                    phase,
+                    hidden,
+                    rules: self.rules,
+                };
+
+                None
+            }
+        };
+
+        Some((reveal_index, utility))
+    }
+}
+// }}}
+// {{{ Agent
+/// An echo agent running information set monte carlo tree search.
+///
+/// Before every decision, a fresh search tree is built by repeatedly guessing
+/// the hidden information of the opponent (uniformly among the possibilities
+/// consistent with what we know), and playing the game forward.
+/// Leaves get evaluated using uniformly random playouts.
+pub struct IsmctsAgent<R> {
+    iterations: usize,
+    bandit: Bandit,
+    rules: RuleSet,
+    rng: R,
+}
+
+impl<R: Rng> IsmctsAgent<R> {
+    pub fn new(iterations: usize, bandit: Bandit, rng: R) -> Self {
+        Self {
+            iterations,
+            bandit,
+            rules: RuleSet::default(),
+            rng,
+        }
+    }
+
+    /// Searches under some variant of the rules.
+    pub fn with_rules(mut self, rules: RuleSet) -> Self {
+        self.rules = rules;
+        self
+    }
+
+    /// Lists every hidden state the opponent might be in.
+    fn determinizations(agent_input: &AgentInput) -> Vec<EncodingInfo> {
+        let AgentInput {
+            phase,
+            state,
+            player,
+            hidden,
+        } = *agent_input;
+
+        let tag = phase.tag();
+        let decoding_info = phase.hidden_index_decoding_info();
+        let revealed = match phase {
+            PerPhase::Seer(seer) => Some(seer.revealed_creature),
+            _ => None,
+        };
+
+        let hand = hidden.get_main();
+
+        (0..HiddenIndex::count(&state, !player, tag))
+            .filter_map(|index| HiddenIndex::from(index).decode(&state, !player, decoding_info))
+            .filter(|theirs| theirs.hand.is_disjoint_from(hand))
+            .map(|theirs| theirs.to_encoding_info(revealed))
+            .collect()
+    }
+
+    /// Plays the game to completion by making uniformly random decisions.
+    fn playout(&mut self, mut game: SimulatedGame) -> Option<Utility> {
+        loop {
+            let counts = game.phase.decision_counts(&game.state);
+            let decisions = counts.map(|count| DecisionIndex(self.rng.gen_range(0..count)));
+
+            if let (_, Some(utility)) = game.advance(decisions)? {
+                return Some(utility);
+            }
+        }
+    }
+
+    /// Runs a single iteration of the search, starting from a fully determined game.
+    /// Returns `None` if the game could not be simulated.
+    fn iterate(&mut self, tree: &mut Vec<Node>, player: Player, root: SimulatedGame) -> Option<()> {
+        let mut game = root;
+        let mut node = 0;
+        let mut path = Vec::new();
+
+        let utility = loop {
+            let counts = game.phase.decision_counts(&game.state);
+            let their_hidden =
+                HiddenIndex::encode(&game.state, !player, (!player).select(game.hidden));
+
+            let mine = tree[node].mine.select(self.bandit, &mut self.rng);
+            let theirs = tree[node]
+                .theirs
+                .entry(their_hidden)
+                .or_insert_with(|| BanditStats::new((!player).select(counts)))
+                .select(self.bandit, &mut self.rng);
+
+            let decisions = player.order_as([DecisionIndex(mine), DecisionIndex(theirs)]);
+            let (reveal_index, utility) = game.advance(decisions)?;
+
+            path.push((node, mine, their_hidden, theirs));
+
+            if let Some(utility) = utility {
+                break utility;
+            }
+
+            let key = (DecisionIndex(mine), reveal_index);
+            match tree[node].children.get(&key) {
+                Some(child) => node = *child,
+                None => {
+                    let count = player.select(game.phase.decision_counts(&game.state));
+                    tree.push(Node::new(count));
+                    let child = tree.len() - 1;
+                    tree[node].children.insert(key, child);
+
+                    break self.playout(game)?;
+                }
+            }
+        };
+
+        // Map utilities from [-1, 1] to [0, 1]
+        let reward = (utility * player.select([1.0, -1.0]) + 1.0) / 2.0;
+
+        for (node, mine, their_hidden, theirs) in path {
+            let node = &mut tree[node];
+            node.mine.update(self.bandit, mine, reward);
+            node.theirs
+                .get_mut(&their_hidden)
+                .unwrap()
+                .update(self.bandit, theirs, 1.0 - reward);
+        }
+
+        Some(())
+    }
+}
+
+impl<R: Rng> EchoAgent for IsmctsAgent<R> {
+    fn choose(&mut self, agent_input: AgentInput) -> DecisionIndex {
+        let player = agent_input.player;
+        let count = player.select(agent_input.phase.decision_counts(&agent_input.state));
+
+        if count == 1 {
+            return DecisionIndex::default();
+        }
+
+        let determinizations = Self::determinizations(&agent_input);
+
+        if determinizations.is_empty() {
+            tracing::event!(
+                Level::WARN,
+                "No hidden state is consistent with our information"
+            );
+            return DecisionIndex::default();
+        }
+
+        let mut tree = vec![Node::new(count)];
+
+        for _ in 0..self.iterations {
+            let theirs = determinizations[self.rng.gen_range(0..determinizations.len())];
+            let root = SimulatedGame {
+                state: agent_input.state,
+                phase: agent_input.phase,
+                hidden: player.order_as([agent_input.hidden, theirs]),
+                rules: self.rules,
+            };
+
+            self.iterate(&mut tree, player, root);
+        }
+
+        DecisionIndex(tree[0].mine.most_visited())
+    }
+}
+// }}}
+// {{{ Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cfr::phase::{MainPhase, PhaseTag};
+    use crate::game::edict::Edict;
+    use crate::game::known_state_summary::KnownStateEssentials;
+    use crate::game::notation::Position;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn decisions_are_legal_in_every_phase() {
+        // We are under the effect of the seer, so we get to pick our creature in the seer phase
+        let mut state = Position::parse("PPPL 3 +0 WSRBDG RS/RS S/-").unwrap().state;
+        let mut phase = PerPhase::Main(MainPhase::new());
+        let mut hidden = phase.valid_hidden_states(state.to_summary())[0];
+        let mut agent = IsmctsAgent::new(100, Bandit::default(), StdRng::seed_from_u64(0));
+
+        for tag in [PhaseTag::Main, PhaseTag::Sabotage, PhaseTag::Seer] {
+            assert_eq!(phase.tag(), tag);
+
+            let count = phase.decision_counts(&state)[0];
+            let input = AgentInput::new(phase, state, hidden[0], Player::Me);
+            let mut decision = agent.choose(input);
+            assert!(count > 1);
+            assert!(decision.0 < count, "Decision {decision:?} is out of range");
+
+            // Make sure we get to guess during the sabotage phase
+            if tag == PhaseTag::Main {
+                let hand = hidden[0].get_main();
+                decision = (0..count)
+                    .map(DecisionIndex)
+                    .find(|index| {
+                        let (_, edict) = index
+                            .decode_main_phase_index(&state, Player::Me, hand)
+                            .unwrap();
+                        edict == Edict::Sabotage
+                    })
+                    .unwrap();
+            }
+
+            let (_, result) = phase
+                .advance(
+                    state,
+                    hidden.map(HiddenState::from_encoding_info),
+                    [decision, DecisionIndex(0)],
+                    RuleSet::default(),
+                )
+                .unwrap();
+            match result {
+                TurnResult::Unfinished(next) => (state, hidden, phase) = next,
+                TurnResult::Finished(_) => assert_eq!(tag, PhaseTag::Seer),
+            }
+        }
+    }
+}
+// }}}
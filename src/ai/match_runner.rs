@@ -0,0 +1,319 @@
+use super::echo_ai::{EchoAgent, EchoRunner};
+use crate::cfr::hidden_index::EncodingInfo;
+use crate::cfr::phase::{MainPhase, PerPhase};
+use crate::game::battlefield::Battlefield;
+use crate::game::creature::{Creature, CreatureSet};
+use crate::game::known_state::KnownState;
+use crate::game::rules::RuleSet;
+use crate::game::types::{BattleResult, Score};
+use crate::helpers::bitfield::Bitfield;
+use crate::helpers::pair::Pair;
+use crate::helpers::statistics::{wilson_interval, RunningStats};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Display};
+use tracing::Level;
+
+// {{{ Deals
+/// Everything needed to start a fresh game:
+/// the battlefields the game is played on, and the hands of both players.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Deal {
+    pub battlefields: [Battlefield; 4],
+    pub hands: Pair<CreatureSet>,
+}
+
+impl Deal {
+    /// Samples a deal uniformly at random. The creature
+    /// left out of both hands ends up as the overseer.
+    pub fn random<R: Rng>(rng: &mut R) -> Self {
+        let mut battlefields = Battlefield::BATTLEFIELDS;
+        battlefields.shuffle(rng);
+
+        let mut creatures = Creature::CREATURES;
+        creatures.shuffle(rng);
+
+        let mut hands = [CreatureSet::empty(); 2];
+        for (index, creature) in creatures.into_iter().take(10).enumerate() {
+            hands[index % 2].insert(creature);
+        }
+
+        Self {
+            battlefields: [
+                battlefields[0],
+                battlefields[1],
+                battlefields[2],
+                battlefields[3],
+            ],
+            hands,
+        }
+    }
+
+    #[inline(always)]
+    pub fn state(&self) -> KnownState {
+        KnownState::new_starting(self.battlefields)
+    }
+
+    #[inline(always)]
+    pub fn hidden(&self) -> Pair<EncodingInfo> {
+        self.hands.map(EncodingInfo::Main)
+    }
+
+    /// Plays a single game from this deal under the given rules,
+    /// returning the final score from the perspective of the first agent.
+    pub fn play<A: EchoAgent, B: EchoAgent>(
+        &self,
+        agents: (A, B),
+        rules: RuleSet,
+    ) -> Option<Score> {
+        EchoRunner::new(
+            self.state(),
+            PerPhase::Main(MainPhase::new()),
+            agents,
+            self.hidden(),
+        )
+        .with_rules(rules)
+        .run_game_with_score()
+    }
+}
+// }}}
+// {{{ Reports
+/// Aggregated results of a match, from the perspective of the first agent.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MatchReport {
+    pub wins: usize,
+    pub ties: usize,
+    pub losses: usize,
+    /// Games which could not be simulated to completion.
+    pub errors: usize,
+    /// Win = 1, tie = 1/2, loss = 0.
+    pub points: RunningStats,
+    /// Final score margins.
+    pub margins: RunningStats,
+}
+
+impl MatchReport {
+    pub fn record(&mut self, score: Option<Score>) {
+        let Some(score) = score else {
+            self.errors += 1;
+            return;
+        };
+
+        match score.to_battle_result() {
+            BattleResult::Won => self.wins += 1,
+            BattleResult::Tied => self.ties += 1,
+            BattleResult::Lost => self.losses += 1,
+        }
+
+        self.points.push((score.to_utility() as f64 + 1.0) / 2.0);
+        self.margins.push(score.0 as f64);
+    }
+
+    /// The number of games which finished properly.
+    #[inline(always)]
+    pub fn games(&self) -> usize {
+        self.wins + self.ties + self.losses
+    }
+
+    #[inline(always)]
+    pub fn win_rate(&self) -> f64 {
+        self.wins as f64 / self.games().max(1) as f64
+    }
+
+    #[inline(always)]
+    pub fn tie_rate(&self) -> f64 {
+        self.ties as f64 / self.games().max(1) as f64
+    }
+
+    #[inline(always)]
+    pub fn loss_rate(&self) -> f64 {
+        self.losses as f64 / self.games().max(1) as f64
+    }
+
+    /// 95% confidence interval for the win rate.
+    #[inline(always)]
+    pub fn win_rate_interval(&self) -> (f64, f64) {
+        wilson_interval(self.wins as f64, self.games() as f64)
+    }
+
+    /// 95% confidence interval for the loss rate.
+    #[inline(always)]
+    pub fn loss_rate_interval(&self) -> (f64, f64) {
+        wilson_interval(self.losses as f64, self.games() as f64)
+    }
+
+    /// The average number of points gained per game
+    /// (win = 1, tie = 1/2, loss = 0).
+    #[inline(always)]
+    pub fn expected_score(&self) -> f64 {
+        self.points.mean()
+    }
+
+    #[inline(always)]
+    pub fn average_margin(&self) -> f64 {
+        self.margins.mean()
+    }
+}
+
+impl Display for MatchReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (win_low, win_high) = self.win_rate_interval();
+        let (loss_low, loss_high) = self.loss_rate_interval();
+
+        writeln!(
+            f,
+            "Games: {} (won {}, tied {}, lost {}, errored {})",
+            self.games(),
+            self.wins,
+            self.ties,
+            self.losses,
+            self.errors
+        )?;
+        writeln!(
+            f,
+            "Win rate: {:.1}% [{:.1}%, {:.1}%]",
+            100.0 * self.win_rate(),
+            100.0 * win_low,
+            100.0 * win_high
+        )?;
+        writeln!(f, "Tie rate: {:.1}%", 100.0 * self.tie_rate())?;
+        writeln!(
+            f,
+            "Loss rate: {:.1}% [{:.1}%, {:.1}%]",
+            100.0 * self.loss_rate(),
+            100.0 * loss_low,
+            100.0 * loss_high
+        )?;
+        writeln!(
+            f,
+            "Expected score: {:.3} ± {:.3}",
+            self.expected_score(),
+            self.points.confidence_radius()
+        )?;
+        write!(
+            f,
+            "Average margin: {:.2} ± {:.2}",
+            self.average_margin(),
+            self.margins.confidence_radius()
+        )
+    }
+}
+// }}}
+// {{{ Runner
+/// Plays many games between agents produced by two factories.
+///
+/// Factories receive a seed, which they can use for initializing
+/// the rng of the agents they create. Each game uses a fresh pair of
+/// agents, and a deal sampled from it's own seeded rng, so the results
+/// do not depend on how rayon schedules the games.
+pub struct MatchRunner<FA, FB> {
+    factories: (FA, FB),
+    games: usize,
+    seed: u64,
+    rules: RuleSet,
+}
+
+impl<A, B, FA, FB> MatchRunner<FA, FB>
+where
+    A: EchoAgent,
+    B: EchoAgent,
+    FA: Fn(u64) -> A + Sync,
+    FB: Fn(u64) -> B + Sync,
+{
+    pub fn new(first: FA, second: FB, games: usize) -> Self {
+        Self {
+            factories: (first, second),
+            games,
+            seed: 0,
+            rules: RuleSet::default(),
+        }
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Plays every game under some variant of the rules.
+    pub fn with_rules(mut self, rules: RuleSet) -> Self {
+        self.rules = rules;
+        self
+    }
+
+    /// Plays a single game, returning the deal and the final score
+    /// from the perspective of the first agent.
+    fn play_game(&self, index: usize) -> (Deal, Option<Score>) {
+        let mut rng = StdRng::seed_from_u64(self.seed.wrapping_add(index as u64));
+        let deal = Deal::random(&mut rng);
+        let agents = ((self.factories.0)(rng.gen()), (self.factories.1)(rng.gen()));
+        let score = deal.play(agents, self.rules);
+
+        if score.is_none() {
+            tracing::event!(Level::WARN, ?deal, "Game errored out");
+        }
+
+        (deal, score)
+    }
+
+    /// Plays every game, returning the final scores in order.
+    pub fn scores(&self) -> Vec<(Deal, Option<Score>)> {
+        (0..self.games)
+            .into_par_iter()
+            .map(|index| self.play_game(index))
+            .collect()
+    }
+
+    pub fn run(&self) -> MatchReport {
+        let mut report = MatchReport::default();
+
+        for (_, score) in self.scores() {
+            report.record(score);
+        }
+
+        report
+    }
+}
+// }}}
+// {{{ Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::random_agent::RandomAgent;
+
+    #[test]
+    fn random_deals_are_valid() {
+        let mut rng = StdRng::seed_from_u64(0);
+
+        for _ in 0..100 {
+            let deal = Deal::random(&mut rng);
+            let [mine, yours] = deal.hands;
+
+            assert_eq!(mine.len(), 5);
+            assert_eq!(yours.len(), 5);
+            assert!(mine.is_disjoint_from(yours));
+
+            for (index, battlefield) in deal.battlefields.into_iter().enumerate() {
+                assert!(!deal.battlefields[index + 1..].contains(&battlefield));
+            }
+        }
+    }
+
+    #[test]
+    fn matches_are_reproducible() {
+        let random = |seed| RandomAgent::new(StdRng::seed_from_u64(seed));
+        let first = MatchRunner::new(random, random, 50).with_seed(7).run();
+        let second = MatchRunner::new(random, random, 50).with_seed(7).run();
+
+        assert_eq!(first.games() + first.errors, 50);
+        assert_eq!(first.errors, 0);
+        assert_eq!(
+            (first.wins, first.ties, first.losses),
+            (second.wins, second.ties, second.losses)
+        );
+        assert_eq!(first.margins, second.margins);
+    }
+}
+// }}}
@@ -3,3 +3,19 @@ mod textures;
 pub mod human_player;
 pub mod random_agent;
 pub mod always_zero_agent;
+pub mod cfr_agent;
+pub mod greedy_agent;
+pub mod ismcts_agent;
+pub mod exploit_agent;
+pub mod game_record;
+pub mod replay;
+pub mod match_runner;
+pub mod duplicate;
+pub mod tournament;
+pub mod engine;
+pub mod network;
+pub mod terminal_agent;
+pub mod policy_export;
+pub mod strategy_query;
+pub mod strategy_diff;
+pub mod endgame_agent;
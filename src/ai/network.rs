@@ -0,0 +1,114 @@
+use super::echo_ai::EchoAgent;
+use super::engine::{serve, EngineAgent};
+use super::match_runner::Deal;
+use crate::game::rules::RuleSet;
+use crate::game::types::Score;
+use std::io::{self, BufReader};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use tracing::Level;
+
+/// An agent sitting on the other side of a network connection.
+pub type RemoteAgent = EngineAgent<TcpStream, BufReader<TcpStream>>;
+
+// {{{ Server
+/// Hosts games for agents connecting over TCP.
+///
+/// The server speaks the engine protocol (see `EngineCommand`), with the
+/// server acting as the host, and every connected client acting as an engine.
+/// This means any engine can be exposed over the network with the `connect`
+/// function, and humans can play remotely by connecting the gui to a seat.
+pub struct GameServer {
+    listener: TcpListener,
+}
+
+impl GameServer {
+    /// Starts listening for connections. Binding to port 0 picks a free port.
+    pub fn bind(address: impl ToSocketAddrs) -> io::Result<Self> {
+        Ok(Self {
+            listener: TcpListener::bind(address)?,
+        })
+    }
+
+    #[inline(always)]
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Waits for a client to connect, and performs the protocol handshake.
+    pub fn accept(&self) -> io::Result<RemoteAgent> {
+        let (stream, address) = self.listener.accept()?;
+        stream.set_nodelay(true)?;
+
+        let agent = EngineAgent::new(stream.try_clone()?, BufReader::new(stream))?;
+        tracing::event!(Level::INFO, %address, name = agent.name(), "Client connected");
+
+        Ok(agent)
+    }
+
+    /// Plays a number of games on random deals, reusing the same agents.
+    /// Returns the final score of each game, from the perspective of the first agent.
+    ///
+    /// The rules are not part of the protocol, so remote
+    /// agents must be told about variants some other way.
+    pub fn host_games<A: EchoAgent, B: EchoAgent>(
+        mut agents: (A, B),
+        deals: impl IntoIterator<Item = Deal>,
+        rules: RuleSet,
+    ) -> Vec<Option<Score>> {
+        deals
+            .into_iter()
+            .map(|deal| {
+                let score = deal.play((&mut agents.0, &mut agents.1), rules);
+                tracing::event!(Level::INFO, ?score, "Game finished");
+                score
+            })
+            .collect()
+    }
+}
+// }}}
+// {{{ Client
+/// Connects to a game server, and lets the given agent play
+/// on the seat it's given until the server closes the connection.
+pub fn connect<A: EchoAgent>(address: impl ToSocketAddrs, name: &str, agent: A) -> io::Result<()> {
+    let stream = TcpStream::connect(address)?;
+    stream.set_nodelay(true)?;
+
+    serve(name, agent, BufReader::new(stream.try_clone()?), stream)
+}
+// }}}
+// {{{ Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::random_agent::RandomAgent;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use std::thread;
+
+    #[test]
+    fn games_can_be_played_over_localhost() {
+        let server = GameServer::bind("127.0.0.1:0").unwrap();
+        let address = server.local_addr().unwrap();
+
+        let client = thread::spawn(move || {
+            let agent = RandomAgent::new(StdRng::seed_from_u64(0));
+            connect(address, "remote-random", agent).unwrap();
+        });
+
+        let remote = server.accept().unwrap();
+        assert_eq!(remote.name(), "remote-random");
+
+        let mut rng = StdRng::seed_from_u64(1);
+        let deals: Vec<_> = (0..5).map(|_| Deal::random(&mut rng)).collect();
+        let local = RandomAgent::new(StdRng::seed_from_u64(2));
+        let scores = GameServer::host_games((remote, local), deals, RuleSet::default());
+
+        assert_eq!(scores.len(), 5);
+        assert!(scores.iter().all(Option::is_some));
+
+        // The remote agent got dropped at the end of the games,
+        // which tells the client to quit.
+        client.join().unwrap();
+    }
+}
+// }}}
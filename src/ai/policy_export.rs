@@ -0,0 +1,281 @@
+use super::game_record::{tag_name, RecordedChoice, SetDisplay};
+use crate::cfr::decision::{Probability, Scope, Utility};
+use crate::cfr::decision_index::DecisionIndex;
+use crate::cfr::phase::{PhaseTag, SomePhase};
+use crate::cfr::scope_cursor::ScopeCursor;
+use crate::game::creature::{Creature, CreatureSet};
+use crate::game::known_state::KnownState;
+use crate::game::notation::Position;
+use crate::game::rules::RuleSet;
+use crate::game::types::Player;
+use serde::Serialize;
+use std::io::{self, Write};
+use std::str::FromStr;
+
+// {{{ Rows
+/// A single entry of an exported policy table: how likely a player is to take
+/// some action (and how much they regret not taking it) given everything they
+/// know at some point in the game.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PolicyRow {
+    /// Pre-order index of the tree node this row belongs to.
+    pub node: usize,
+    /// The public state, in compact notation (see `Position`).
+    pub position: String,
+    pub turn: usize,
+    pub score: i8,
+    pub phase: PhaseTag,
+    pub player: Player,
+    pub hand: CreatureSet,
+    pub choice: Option<CreatureSet>,
+    pub revealed: Option<Creature>,
+    /// The decoded action, written like in game records.
+    pub action: String,
+    pub probability: Probability,
+    pub regret: Utility,
+}
+
+impl PolicyRow {
+    pub const CSV_HEADER: &'static str =
+        "node,position,turn,score,phase,player,hand,choice,revealed,action,probability,regret";
+
+    pub fn write_csv<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let choice = self
+            .choice
+            .map_or_else(String::new, |choice| SetDisplay(choice).to_string());
+        let revealed = self
+            .revealed
+            .map_or_else(String::new, |creature| creature.to_string());
+
+        writeln!(
+            writer,
+            "{},{},{},{},{},{:?},{},{},{},{},{},{}",
+            self.node,
+            self.position,
+            self.turn,
+            self.score,
+            tag_name(self.phase),
+            self.player,
+            csv_field(&SetDisplay(self.hand).to_string()),
+            csv_field(&choice),
+            revealed,
+            csv_field(&self.action),
+            self.probability,
+            self.regret
+        )
+    }
+}
+
+/// Quotes fields containing separators.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+// }}}
+// {{{ Tree traversal
+fn visit(cursor: ScopeCursor, rules: RuleSet, node: &mut usize, f: &mut impl FnMut(PolicyRow)) {
+    let Some(matrices) = cursor.matrices() else {
+        return;
+    };
+
+    let index = *node;
+    *node += 1;
+
+    let (state, phase) = (cursor.state(), cursor.phase());
+    let position = Position::new(state).to_string();
+    let decoding_info = phase.hidden_index_decoding_info();
+    let revealed = decoding_info.get_seer();
+
+    for player in Player::PLAYERS {
+        for (hidden_index, vector) in matrices.get_matrix(player).nodes() {
+            let Some(hidden) = hidden_index.decode(&state, player, decoding_info) else {
+                continue;
+            };
+
+            let Some(info) = hidden.try_to_encoding_info(revealed) else {
+                continue;
+            };
+
+            let strategy = vector.get_average_strategy();
+            for (action, probability) in strategy.into_iter().enumerate() {
+                let decision = DecisionIndex(action);
+                let Some(choice) = RecordedChoice::decode(&phase, &state, player, info, decision)
+                else {
+                    continue;
+                };
+
+                f(PolicyRow {
+                    node: index,
+                    position: position.clone(),
+                    turn: state.battlefields.current,
+                    score: state.score.0,
+                    phase: phase.tag(),
+                    player,
+                    hand: hidden.hand,
+                    choice: hidden.choice,
+                    revealed,
+                    action: choice.to_string(),
+                    probability,
+                    regret: vector.regret_sum[action],
+                });
+            }
+        }
+    }
+
+    for (_, child) in cursor.children(rules) {
+        visit(child, rules, node, f);
+    }
+}
+
+/// Calls a function on every row of the policy table of a trained scope,
+/// generated from the given state and phase using the given rules.
+pub fn for_each_row(
+    scope: &Scope,
+    state: KnownState,
+    phase: SomePhase,
+    rules: RuleSet,
+    mut f: impl FnMut(PolicyRow),
+) {
+    visit(ScopeCursor::new(scope, state, phase), rules, &mut 0, &mut f);
+}
+// }}}
+// {{{ Exporting
+/// The formats policy tables can be exported in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ExportFormat {
+    #[default]
+    Csv,
+    /// A JSON object on every line.
+    JsonLines,
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input {
+            "csv" => Ok(Self::Csv),
+            "jsonl" => Ok(Self::JsonLines),
+            _ => Err(format!("Unknown export format {input:?}")),
+        }
+    }
+}
+
+/// Writes the policy table of a trained scope, returning the number of rows written.
+pub fn export_policy<W: Write>(
+    scope: &Scope,
+    state: KnownState,
+    phase: SomePhase,
+    rules: RuleSet,
+    format: ExportFormat,
+    mut writer: W,
+) -> io::Result<usize> {
+    if format == ExportFormat::Csv {
+        writeln!(writer, "{}", PolicyRow::CSV_HEADER)?;
+    }
+
+    let mut rows = 0;
+    let mut result = Ok(());
+
+    for_each_row(scope, state, phase, rules, |row| {
+        if result.is_err() {
+            return;
+        }
+
+        result = match format {
+            ExportFormat::Csv => row.write_csv(&mut writer),
+            ExportFormat::JsonLines => serde_json::to_writer(&mut writer, &row)
+                .map_err(io::Error::from)
+                .and_then(|()| writeln!(writer)),
+        };
+
+        if result.is_ok() {
+            rows += 1;
+        }
+    });
+
+    result.map(|()| rows)
+}
+// }}}
+// {{{ Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cfr::phase::{MainPhase, PerPhase};
+    use crate::cfr::train::fixtures::{rules, trained_scope};
+    use bumpalo::Bump;
+    use std::collections::HashMap;
+
+    fn root_state() -> KnownState {
+        Position::parse("PPPL 3 +0 WSRBDG RS/RS -/-").unwrap().state
+    }
+
+    #[test]
+    fn strategies_sum_to_one() {
+        let allocator = Bump::new();
+        let state = root_state();
+        let scope = trained_scope(&allocator, state, 20);
+        let mut totals: HashMap<_, f32> = HashMap::new();
+        let mut phases = Vec::new();
+
+        for_each_row(
+            &scope,
+            state,
+            PerPhase::Main(MainPhase::new()),
+            rules(),
+            |row| {
+                phases.push(row.phase);
+                let hidden = format!("{:?} {:?} {:?}", row.player, row.hand, row.choice);
+                let key = (row.node, hidden);
+                *totals.entry(key).or_default() += row.probability;
+            },
+        );
+
+        assert!(!totals.is_empty());
+        assert!(phases.contains(&PhaseTag::Main));
+        assert!(phases.contains(&PhaseTag::Sabotage));
+
+        for (key, total) in totals {
+            assert!((total - 1.0).abs() < 1e-3, "{key:?} sums up to {total}");
+        }
+    }
+
+    #[test]
+    fn exports_one_line_per_row() {
+        let allocator = Bump::new();
+        let state = root_state();
+        let scope = trained_scope(&allocator, state, 20);
+        let phase = PerPhase::Main(MainPhase::new());
+
+        let mut csv = Vec::new();
+        let rows =
+            export_policy(&scope, state, phase, rules(), ExportFormat::Csv, &mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let mut lines = csv.lines();
+
+        assert_eq!(lines.next(), Some(PolicyRow::CSV_HEADER));
+        assert_eq!(lines.count(), rows);
+
+        let mut json = Vec::new();
+        let json_rows = export_policy(
+            &scope,
+            state,
+            phase,
+            rules(),
+            ExportFormat::JsonLines,
+            &mut json,
+        )
+        .unwrap();
+        let json = String::from_utf8(json).unwrap();
+
+        assert_eq!(json_rows, rows);
+        for line in json.lines() {
+            let row: serde_json::Value = serde_json::from_str(line).unwrap();
+            assert_eq!(row["turn"], 3);
+        }
+    }
+}
+// }}}
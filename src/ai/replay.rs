@@ -0,0 +1,419 @@
+use super::game_record::{GameRecord, ParseError, RecordedChoice, RECORD_HEADER};
+use crate::cfr::decision_index::DecisionIndex;
+use crate::cfr::hidden_index::HiddenState;
+use crate::cfr::phase::{MainPhase, PerPhase, PhaseTag, SomePhase};
+use crate::cfr::reveal_index::RevealIndex;
+use crate::game::known_state::KnownState;
+use crate::game::known_state_summary::KnownStateEssentials;
+use crate::game::types::{Player, Score, TurnResult};
+use crate::helpers::bitfield::Bitfield;
+use crate::helpers::pair::Pair;
+use std::fmt::{self, Display};
+
+// {{{ Errors
+/// Reasons a record might not match the rules of the game.
+/// Phases are 0-indexed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplayError {
+    /// The hands do not form a valid deal for the starting state.
+    InvalidDeal,
+    /// The record says a phase of a different kind comes next.
+    UnexpectedPhase {
+        phase: usize,
+        expected: PhaseTag,
+        recorded: PhaseTag,
+    },
+    /// A decision index is out of range, or cannot be decoded.
+    IllegalDecision {
+        phase: usize,
+        player: Player,
+        decision: DecisionIndex,
+    },
+    /// A decision index does not decode to the recorded choice.
+    ChoiceMismatch {
+        phase: usize,
+        player: Player,
+        recorded: RecordedChoice,
+        actual: RecordedChoice,
+    },
+    RevealMismatch {
+        phase: usize,
+        recorded: RevealIndex,
+        actual: RevealIndex,
+    },
+    ScoreMismatch {
+        phase: usize,
+        recorded: Score,
+        actual: Score,
+    },
+    /// The game has ended, yet the record keeps going.
+    PhasesAfterEnd { phase: usize },
+    /// The recorded result does not match the result of the re-simulation.
+    /// A result of `None` means the game has not finished.
+    ResultMismatch {
+        recorded: Option<Score>,
+        actual: Option<Score>,
+    },
+}
+
+impl Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidDeal => write!(f, "The hands do not form a valid deal"),
+            Self::UnexpectedPhase {
+                phase,
+                expected,
+                recorded,
+            } => write!(
+                f,
+                "Phase {phase}: expected a {expected:?} phase, found a {recorded:?} phase"
+            ),
+            Self::IllegalDecision {
+                phase,
+                player,
+                decision,
+            } => write!(
+                f,
+                "Phase {phase}: decision {} of player {player:?} is illegal",
+                decision.0
+            ),
+            Self::ChoiceMismatch {
+                phase,
+                player,
+                recorded,
+                actual,
+            } => write!(
+                f,
+                "Phase {phase}: player {player:?} was recorded playing {recorded}, but their decision means {actual}"
+            ),
+            Self::RevealMismatch {
+                phase,
+                recorded,
+                actual,
+            } => write!(
+                f,
+                "Phase {phase}: recorded reveal index {}, but the game reveals {}",
+                recorded.0, actual.0
+            ),
+            Self::ScoreMismatch {
+                phase,
+                recorded,
+                actual,
+            } => write!(
+                f,
+                "Phase {phase}: recorded score {}, but the game ends up at {}",
+                recorded.0, actual.0
+            ),
+            Self::PhasesAfterEnd { phase } => {
+                write!(f, "Phase {phase}: the game has already ended")
+            }
+            Self::ResultMismatch { recorded, actual } => write!(
+                f,
+                "Recorded result {:?}, but the game result is {:?}",
+                recorded.map(|s| s.0),
+                actual.map(|s| s.0)
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+// }}}
+// {{{ Replay
+/// The position of the game at the start of some phase.
+#[derive(Debug, Clone, Copy)]
+pub struct ReplayStep {
+    pub state: KnownState,
+    pub phase: SomePhase,
+    pub hidden: Pair<HiddenState>,
+}
+
+/// Re-simulates a record from scratch, checking every decision is legal,
+/// and that the choices, reveals and scores in the record are the ones
+/// the rules the game has been played under produce.
+///
+/// Returns the position at the start of every recorded phase.
+pub fn replay(record: &GameRecord) -> Result<Vec<ReplayStep>, ReplayError> {
+    // {{{ Validate the deal
+    let state = record.state;
+    let [first, second] = record.hands;
+    let hand_size = state.hand_size();
+
+    if !first.is_disjoint_from(second)
+        || !(first | second).is_disjoint_from(state.graveyard)
+        || first.len() != hand_size
+        || second.len() != hand_size
+    {
+        return Err(ReplayError::InvalidDeal);
+    }
+    // }}}
+
+    let mut steps = Vec::with_capacity(record.phases.len());
+    let mut state = record.state;
+    let mut phase = PerPhase::Main(MainPhase::new());
+    let mut hidden = record.initial_hidden();
+    let mut result = None;
+
+    for (index, recorded) in record.phases.iter().enumerate() {
+        if result.is_some() {
+            return Err(ReplayError::PhasesAfterEnd { phase: index });
+        }
+
+        if phase.tag() != recorded.tag() {
+            return Err(ReplayError::UnexpectedPhase {
+                phase: index,
+                expected: phase.tag(),
+                recorded: recorded.tag(),
+            });
+        }
+
+        // {{{ Check decisions
+        let counts = phase.decision_counts(&state);
+
+        for player in Player::PLAYERS {
+            let decision = player.select(recorded.decisions);
+            let illegal = ReplayError::IllegalDecision {
+                phase: index,
+                player,
+                decision,
+            };
+
+            if decision.0 >= player.select(counts) {
+                return Err(illegal);
+            }
+
+            let actual =
+                RecordedChoice::decode(&phase, &state, player, player.select(hidden), decision)
+                    .ok_or(illegal)?;
+
+            let choice = player.select(recorded.choices);
+            if actual != choice {
+                return Err(ReplayError::ChoiceMismatch {
+                    phase: index,
+                    player,
+                    recorded: choice,
+                    actual,
+                });
+            }
+        }
+        // }}}
+
+        let hidden_states = hidden.map(HiddenState::from_encoding_info);
+        steps.push(ReplayStep {
+            state,
+            phase,
+            hidden: hidden_states,
+        });
+
+        // {{{ Advance
+        let (reveal, advanced) = phase
+            .advance(state, hidden_states, recorded.decisions, record.rules)
+            .ok_or(ReplayError::IllegalDecision {
+                phase: index,
+                player: Player::Me,
+                decision: recorded.decisions[0],
+            })?;
+
+        if reveal != recorded.reveal {
+            return Err(ReplayError::RevealMismatch {
+                phase: index,
+                recorded: recorded.reveal,
+                actual: reveal,
+            });
+        }
+
+        let score = match advanced {
+            TurnResult::Finished(score) => {
+                result = Some(score);
+                score
+            }
+            TurnResult::Unfinished((next_state, next_hidden, next_phase)) => {
+                state = next_state;
+                hidden = next_hidden;
+                phase = next_phase;
+                state.score
+            }
+        };
+
+        if score != recorded.score {
+            return Err(ReplayError::ScoreMismatch {
+                phase: index,
+                recorded: recorded.score,
+                actual: score,
+            });
+        }
+        // }}}
+    }
+
+    if result != record.result {
+        return Err(ReplayError::ResultMismatch {
+            recorded: record.result,
+            actual: result,
+        });
+    }
+
+    Ok(steps)
+}
+
+/// Same as `replay`, but only reports whether the record is valid.
+#[inline(always)]
+pub fn verify(record: &GameRecord) -> Result<(), ReplayError> {
+    replay(record).map(|_| ())
+}
+// }}}
+// {{{ Record collections
+/// Parses a collection of records written one after the other
+/// (like the ones in the test corpus). Line numbers in errors
+/// are relative to the entire input.
+pub fn parse_records(input: &str) -> Result<Vec<GameRecord>, ParseError> {
+    let mut records = Vec::new();
+    let mut current: Option<(usize, String)> = None;
+
+    for (index, line) in input.lines().enumerate() {
+        // The record parser takes care of checking the version
+        if line.trim().starts_with("echo-record") {
+            if let Some(record) = current.take() {
+                records.push(record);
+            }
+
+            current = Some((index, String::new()));
+        }
+
+        match &mut current {
+            Some((_, text)) => {
+                text.push_str(line);
+                text.push('\n');
+            }
+            None if line.trim().is_empty() || line.trim().starts_with('#') => {}
+            None => {
+                return Err(ParseError {
+                    line: index + 1,
+                    message: format!("Expected {RECORD_HEADER:?}, got {line:?}"),
+                })
+            }
+        }
+    }
+
+    records.extend(current);
+
+    records
+        .into_iter()
+        .map(|(offset, text)| {
+            GameRecord::parse(&text).map_err(|error| ParseError {
+                line: error.line + offset,
+                ..error
+            })
+        })
+        .collect()
+}
+// }}}
+// {{{ Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::echo_ai::EchoRunner;
+    use crate::ai::greedy_agent::GreedyAgent;
+    use crate::ai::match_runner::Deal;
+    use crate::game::rules::RuleSet;
+    use crate::game::types::Score;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use std::sync::mpsc;
+
+    const CORPUS: &str = include_str!("../../tests/records/random.txt");
+
+    fn corpus() -> Vec<GameRecord> {
+        parse_records(CORPUS).unwrap()
+    }
+
+    #[test]
+    fn corpus_replays_cleanly() {
+        let records = corpus();
+        assert!(!records.is_empty());
+
+        for record in records {
+            let steps = replay(&record).unwrap();
+            assert_eq!(steps.len(), record.phases.len());
+        }
+    }
+
+    #[test]
+    fn tampered_scores_are_detected() {
+        let mut record = corpus().swap_remove(0);
+        record.phases[2].score = record.phases[2].score + 1;
+
+        assert!(matches!(
+            verify(&record),
+            Err(ReplayError::ScoreMismatch { phase: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn tampered_decisions_are_detected() {
+        let mut record = corpus().swap_remove(0);
+        record.phases[0].decisions[1] = DecisionIndex(10_000);
+
+        assert!(matches!(
+            verify(&record),
+            Err(ReplayError::IllegalDecision {
+                phase: 0,
+                player: Player::You,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn tampered_results_are_detected() {
+        let mut record = corpus().swap_remove(0);
+        record.result = record.result.map(|score| Score(score.0 + 1));
+
+        assert!(matches!(
+            verify(&record),
+            Err(ReplayError::ResultMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn records_are_replayed_under_their_own_rules() {
+        let rules = RuleSet {
+            rewards: [5; 6],
+            ..RuleSet::default()
+        };
+
+        let deal = Deal::random(&mut StdRng::seed_from_u64(0));
+        let (sender, receiver) = mpsc::channel();
+        let agents = (
+            GreedyAgent::default().with_rules(rules),
+            GreedyAgent::default().with_rules(rules),
+        );
+
+        EchoRunner::new(
+            deal.state(),
+            PerPhase::Main(MainPhase::new()),
+            agents,
+            deal.hidden(),
+        )
+        .with_rules(rules)
+        .record_to(sender)
+        .run_game()
+        .unwrap();
+
+        let mut record = receiver.recv().unwrap();
+        let parsed = GameRecord::parse(&record.to_string()).unwrap();
+        assert_eq!(parsed.rules, rules);
+        assert_eq!(verify(&parsed), Ok(()));
+
+        record.rules = RuleSet::default();
+        assert!(verify(&record).is_err());
+    }
+
+    #[test]
+    fn invalid_deals_are_detected() {
+        let mut record = corpus().swap_remove(0);
+        record.hands[1] = record.hands[0];
+
+        assert_eq!(verify(&record), Err(ReplayError::InvalidDeal));
+    }
+}
+// }}}
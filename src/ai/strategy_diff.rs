@@ -0,0 +1,410 @@
+use super::game_record::{tag_name, RecordedChoice, SetDisplay};
+use crate::cfr::decision::{DecisionMatrices, Probability, Scope};
+use crate::cfr::decision_index::DecisionIndex;
+use crate::cfr::hidden_index::HiddenState;
+use crate::cfr::phase::{PhaseTag, SomePhase};
+use crate::cfr::scope_cursor::ScopeCursor;
+use crate::game::creature::Creature;
+use crate::game::known_state::KnownState;
+use crate::game::notation::Position;
+use crate::game::rules::RuleSet;
+use crate::game::types::Player;
+use std::collections::BTreeMap;
+use std::fmt::{self, Display};
+
+/// Probabilities below this value are treated as this value when computing
+/// the KL divergence, so strategies which never take some action do not
+/// result in infinite divergences.
+const KL_EPSILON: f64 = 1e-6;
+
+// {{{ Statistics
+/// Divergences aggregated over a set of infosets.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct DivergenceStats {
+    pub infosets: usize,
+    pub total_variation_sum: f64,
+    pub kl_sum: f64,
+    pub max_total_variation: f64,
+}
+
+impl DivergenceStats {
+    fn record(&mut self, total_variation: f64, kl: f64) {
+        self.infosets += 1;
+        self.total_variation_sum += total_variation;
+        self.kl_sum += kl;
+        self.max_total_variation = self.max_total_variation.max(total_variation);
+    }
+
+    pub fn mean_total_variation(&self) -> f64 {
+        self.total_variation_sum / self.infosets.max(1) as f64
+    }
+
+    pub fn mean_kl(&self) -> f64 {
+        self.kl_sum / self.infosets.max(1) as f64
+    }
+}
+
+/// Computes the total variation distance and the KL divergence
+/// between two distributions over the same set of actions.
+pub fn distances(first: &[Probability], second: &[Probability]) -> (f64, f64) {
+    debug_assert_eq!(first.len(), second.len());
+
+    let mut total_variation = 0.0;
+    let mut kl = 0.0;
+
+    for (&p, &q) in first.iter().zip(second) {
+        let (p, q) = (p as f64, q as f64);
+        total_variation += (p - q).abs();
+
+        if p > 0.0 {
+            kl += p * (p / q.max(KL_EPSILON)).ln();
+        }
+    }
+
+    (total_variation / 2.0, kl.max(0.0))
+}
+// }}}
+// {{{ Infosets
+/// The strategies two blueprints play at a single infoset.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InfosetDivergence {
+    /// The public state, in compact notation (see `Position`).
+    pub position: String,
+    pub phase: PhaseTag,
+    pub player: Player,
+    pub hidden: HiddenState,
+    pub revealed: Option<Creature>,
+    pub total_variation: f64,
+    pub kl: f64,
+    /// Every action, together with it's probability under both strategies.
+    pub actions: Vec<(RecordedChoice, Probability, Probability)>,
+}
+
+impl Display for InfosetDivergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "TV {:.4}, KL {:.4} at {} ({} phase, {:?} holding {}",
+            self.total_variation,
+            self.kl,
+            self.position,
+            tag_name(self.phase),
+            self.player,
+            SetDisplay(self.hidden.hand)
+        )?;
+
+        if let Some(choice) = self.hidden.choice {
+            write!(f, ", choice {}", SetDisplay(choice))?;
+        }
+
+        if let Some(revealed) = self.revealed {
+            write!(f, ", revealed {revealed}")?;
+        }
+
+        writeln!(f, ")")?;
+
+        for (choice, first, second) in &self.actions {
+            writeln!(f, "    {first:.4} -> {second:.4} {choice}")?;
+        }
+
+        Ok(())
+    }
+}
+// }}}
+// {{{ Diffing
+/// The result of comparing two blueprints.
+///
+/// The KL divergence is computed from the first strategy to the second one.
+#[derive(Debug, Clone, Default)]
+pub struct StrategyDiff {
+    pub total: DivergenceStats,
+    /// Statistics for every (turn, phase) pair.
+    pub per_phase: BTreeMap<(usize, PhaseTag), DivergenceStats>,
+    /// The infosets with the largest total variation, in decreasing order.
+    pub largest: Vec<InfosetDivergence>,
+    /// The number of scopes only explored in one of the trees.
+    pub unmatched_scopes: usize,
+}
+
+struct DiffContext {
+    diff: StrategyDiff,
+    rules: RuleSet,
+    keep: usize,
+}
+
+impl DiffContext {
+    /// Returns true if an infoset with the given divergence
+    /// would make it into the list of largest divergences.
+    fn is_among_largest(&self, total_variation: f64) -> bool {
+        self.keep > 0
+            && (self.diff.largest.len() < self.keep
+                || self.diff.largest.last().unwrap().total_variation < total_variation)
+    }
+
+    fn insert_largest(&mut self, divergence: InfosetDivergence) {
+        let largest = &mut self.diff.largest;
+        let index =
+            largest.partition_point(|other| other.total_variation >= divergence.total_variation);
+
+        largest.insert(index, divergence);
+        largest.truncate(self.keep);
+    }
+
+    fn visit(&mut self, first: ScopeCursor, second: ScopeCursor) {
+        let (first_matrices, second_matrices) = match (first.matrices(), second.matrices()) {
+            (Some(first), Some(second)) => (first, second),
+            (None, None) => return,
+            _ => {
+                self.diff.unmatched_scopes += 1;
+                return;
+            }
+        };
+
+        let (state, phase) = (first.state(), first.phase());
+        let decoding_info = phase.hidden_index_decoding_info();
+        let revealed = decoding_info.get_seer();
+        let key = (state.battlefields.current, phase.tag());
+
+        // Both players share the same matrix in symmetrical
+        // scopes, so there's no point in comparing it twice
+        let players = match first_matrices {
+            DecisionMatrices::Symmetrical(_) => &Player::PLAYERS[..1],
+            DecisionMatrices::Asymmetrical(_) => &Player::PLAYERS[..],
+        };
+
+        for &player in players {
+            let nodes = first_matrices
+                .get_matrix(player)
+                .nodes()
+                .zip(second_matrices.get_matrix(player).nodes());
+
+            for ((hidden_index, first_vector), (_, second_vector)) in nodes {
+                let Some(hidden) = hidden_index.decode(&state, player, decoding_info) else {
+                    continue;
+                };
+
+                if hidden.try_to_encoding_info(revealed).is_none() {
+                    continue;
+                }
+
+                let first_strategy = first_vector.get_average_strategy();
+                let second_strategy = second_vector.get_average_strategy();
+                let (total_variation, kl) = distances(&first_strategy, &second_strategy);
+
+                self.diff.total.record(total_variation, kl);
+                self.diff
+                    .per_phase
+                    .entry(key)
+                    .or_default()
+                    .record(total_variation, kl);
+
+                if self.is_among_largest(total_variation) {
+                    let divergence = Self::describe(
+                        state,
+                        phase,
+                        player,
+                        hidden,
+                        revealed,
+                        [first_strategy, second_strategy],
+                        (total_variation, kl),
+                    );
+
+                    self.insert_largest(divergence);
+                }
+            }
+        }
+
+        for (reveal_index, first_child) in first.children(self.rules) {
+            let Some(second_child) = second
+                .advance(reveal_index, self.rules)
+                .and_then(|child| child.get_unfinished())
+            else {
+                continue;
+            };
+
+            self.visit(first_child, second_child);
+        }
+    }
+
+    /// Decodes everything there is to know about an infoset.
+    fn describe(
+        state: KnownState,
+        phase: SomePhase,
+        player: Player,
+        hidden: HiddenState,
+        revealed: Option<Creature>,
+        [first, second]: [Vec<Probability>; 2],
+        (total_variation, kl): (f64, f64),
+    ) -> InfosetDivergence {
+        let info = hidden.to_encoding_info(revealed);
+        let actions = first
+            .into_iter()
+            .zip(second)
+            .enumerate()
+            .filter_map(|(index, (first, second))| {
+                let decision = DecisionIndex(index);
+                let choice = RecordedChoice::decode(&phase, &state, player, info, decision)?;
+
+                Some((choice, first, second))
+            })
+            .collect();
+
+        InfosetDivergence {
+            position: Position::new(state).to_string(),
+            phase: phase.tag(),
+            player,
+            hidden,
+            revealed,
+            total_variation,
+            kl,
+            actions,
+        }
+    }
+}
+
+/// Walks two trained trees generated from the same state, phase and rules in
+/// parallel, comparing the average strategies at every infoset they share.
+///
+/// The `keep` infosets with the largest total variation are decoded and returned.
+pub fn diff_strategies(
+    first: &Scope,
+    second: &Scope,
+    state: KnownState,
+    phase: SomePhase,
+    rules: RuleSet,
+    keep: usize,
+) -> StrategyDiff {
+    let mut context = DiffContext {
+        diff: StrategyDiff::default(),
+        rules,
+        keep,
+    };
+
+    context.visit(
+        ScopeCursor::new(first, state, phase),
+        ScopeCursor::new(second, state, phase),
+    );
+
+    context.diff
+}
+
+impl Display for StrategyDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Infosets: {} (scopes explored in a single tree: {})",
+            self.total.infosets, self.unmatched_scopes
+        )?;
+        writeln!(
+            f,
+            "Total variation: {:.4} mean, {:.4} max",
+            self.total.mean_total_variation(),
+            self.total.max_total_variation
+        )?;
+        writeln!(f, "KL divergence: {:.4} mean", self.total.mean_kl())?;
+        writeln!(f)?;
+        writeln!(
+            f,
+            "{:<6}{:<10}{:>10}{:>10}{:>10}{:>10}",
+            "Turn", "Phase", "Infosets", "Mean TV", "Max TV", "Mean KL"
+        )?;
+
+        for ((turn, phase), stats) in &self.per_phase {
+            writeln!(
+                f,
+                "{:<6}{:<10}{:>10}{:>10.4}{:>10.4}{:>10.4}",
+                turn,
+                tag_name(*phase),
+                stats.infosets,
+                stats.mean_total_variation(),
+                stats.max_total_variation,
+                stats.mean_kl()
+            )?;
+        }
+
+        if !self.largest.is_empty() {
+            writeln!(f)?;
+            writeln!(f, "Largest divergences:")?;
+
+            for divergence in &self.largest {
+                write!(f, "{divergence}")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+// }}}
+// {{{ Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cfr::phase::{MainPhase, PerPhase};
+    use crate::cfr::train::fixtures::{rules, trained_scope};
+    use bumpalo::Bump;
+
+    #[test]
+    fn distances_of_simple_distributions() {
+        assert_eq!(distances(&[0.5, 0.5], &[0.5, 0.5]), (0.0, 0.0));
+
+        let (total_variation, kl) = distances(&[1.0, 0.0], &[0.5, 0.5]);
+        assert!((total_variation - 0.5).abs() < 1e-6);
+        assert!((kl - 2f64.ln()).abs() < 1e-6);
+
+        // Never taking an action is not infinitely surprising
+        let (total_variation, kl) = distances(&[0.5, 0.5], &[1.0, 0.0]);
+        assert!((total_variation - 0.5).abs() < 1e-6);
+        assert!(kl.is_finite());
+    }
+
+    #[test]
+    fn identical_blueprints_do_not_diverge() {
+        let state = Position::parse("PPPL 3 +0 WSRBDG RS/RS -/-").unwrap().state;
+        let phase = PerPhase::Main(MainPhase::new());
+        let [first, second] = [Bump::new(), Bump::new()];
+        let first = trained_scope(&first, state, 20);
+        let second = trained_scope(&second, state, 20);
+
+        let diff = diff_strategies(&first, &second, state, phase, rules(), 5);
+
+        assert!(diff.total.infosets > 0);
+        assert_eq!(diff.total.max_total_variation, 0.0);
+        assert_eq!(diff.unmatched_scopes, 0);
+        assert_eq!(diff.largest.len(), 5);
+    }
+
+    #[test]
+    fn reports_largest_divergences() {
+        let state = Position::parse("PPPL 3 +0 WSRBDG RS/RS S/-").unwrap().state;
+        let phase = PerPhase::Main(MainPhase::new());
+        let [first, second] = [Bump::new(), Bump::new()];
+        let first = trained_scope(&first, state, 2);
+        let second = trained_scope(&second, state, 50);
+
+        let diff = diff_strategies(&first, &second, state, phase, rules(), 3);
+
+        assert!(diff.total.max_total_variation > 0.0);
+        assert_eq!(diff.largest.len(), 3);
+        assert_eq!(
+            diff.largest[0].total_variation,
+            diff.total.max_total_variation
+        );
+        assert!(diff
+            .largest
+            .windows(2)
+            .all(|pair| pair[0].total_variation >= pair[1].total_variation));
+
+        // Every infoset has a decoded list of actions
+        for divergence in &diff.largest {
+            let total: Probability = divergence.actions.iter().map(|action| action.2).sum();
+            assert!((total - 1.0).abs() < 1e-3);
+        }
+
+        // The aggregates cover every infoset
+        let infosets: usize = diff.per_phase.values().map(|stats| stats.infosets).sum();
+        assert_eq!(infosets, diff.total.infosets);
+        assert!(diff.per_phase.contains_key(&(3, PhaseTag::Seer)));
+        assert!(diff.to_string().contains("Largest divergences"));
+    }
+}
+// }}}
@@ -0,0 +1,252 @@
+use super::game_record::RecordedChoice;
+use crate::cfr::decision::{Probability, Scope};
+use crate::cfr::decision_index::DecisionIndex;
+use crate::cfr::hidden_index::{EncodingInfo, HiddenIndex, HiddenState, PerPhaseInfo};
+use crate::cfr::phase::SomePhase;
+use crate::cfr::reveal_index::RevealIndex;
+use crate::cfr::scope_cursor::ScopeCursor;
+use crate::game::known_state::KnownState;
+use crate::game::rules::RuleSet;
+use crate::game::types::{Player, Score, TurnResult};
+use serde::Serialize;
+use std::fmt::{self, Display};
+
+// {{{ Errors
+/// Reasons a strategy cannot be looked up.
+/// Depths count the reveals applied before the error occured.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueryError {
+    /// A reveal index is out of range for the node it is applied to.
+    InvalidReveal { depth: usize, reveal: RevealIndex },
+    /// The game ends before the end of the path.
+    GameFinished { depth: usize, score: Score },
+    /// The path leaves the part of the tree which has been generated.
+    Unexplored { depth: usize },
+    /// The hidden info cannot be known by the player at the end of the path.
+    InvalidHiddenState,
+}
+
+impl Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidReveal { depth, reveal } => {
+                write!(f, "Reveal {depth}: index {} is out of range", reveal.0)
+            }
+            Self::GameFinished { depth, score } => write!(
+                f,
+                "Reveal {depth}: the game has already ended with score {}",
+                score.0
+            ),
+            Self::Unexplored { depth } => {
+                write!(f, "Reveal {depth}: the path leaves the generated tree")
+            }
+            Self::InvalidHiddenState => {
+                write!(f, "The hidden info does not match the position")
+            }
+        }
+    }
+}
+
+impl std::error::Error for QueryError {}
+// }}}
+// {{{ Queries
+/// A decoded action, together with the probability of a strategy taking it.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct ActionProbability {
+    pub choice: RecordedChoice,
+    pub probability: Probability,
+}
+
+/// Follows a path of reveals from the root of a tree,
+/// returning a cursor pointing to the explored scope it leads to.
+fn follow_path<'s, 'a>(
+    scope: &'s Scope<'a>,
+    state: KnownState,
+    phase: SomePhase,
+    rules: RuleSet,
+    path: &[RevealIndex],
+) -> Result<ScopeCursor<'s, 'a>, QueryError> {
+    let mut cursor = ScopeCursor::new(scope, state, phase);
+
+    for (depth, &reveal) in path.iter().enumerate() {
+        let explored = cursor.explored().ok_or(QueryError::Unexplored { depth })?;
+        let invalid_reveal = QueryError::InvalidReveal { depth, reveal };
+
+        if reveal.0 >= explored.next.len() {
+            return Err(invalid_reveal);
+        }
+
+        match cursor.advance(reveal, rules).ok_or(invalid_reveal)? {
+            TurnResult::Finished(score) => {
+                return Err(QueryError::GameFinished {
+                    depth: depth + 1,
+                    score,
+                })
+            }
+            TurnResult::Unfinished(next) => cursor = next,
+        }
+    }
+
+    match cursor.scope() {
+        Some(Scope::Explored(_)) => Ok(cursor),
+        Some(Scope::Completed(score)) => Err(QueryError::GameFinished {
+            depth: path.len(),
+            score: *score,
+        }),
+        _ => Err(QueryError::Unexplored { depth: path.len() }),
+    }
+}
+
+/// Looks up the average strategy of a player in a trained tree.
+///
+/// # Arguments
+///
+/// * `scope` - The root of the tree.
+/// * `state` - The state the tree has been generated from.
+/// * `phase` - The phase the tree has been generated from.
+/// * `rules` - The rules the tree has been generated with.
+/// * `path` - The reveals leading from the root to the node of interest.
+/// * `player` - The player whose strategy we want to know.
+/// * `hidden` - Everything the player knows at that node. The hand includes
+///   the creatures chosen this turn, and no choice should be given during
+///   the main phase.
+pub fn query_strategy(
+    scope: &Scope,
+    state: KnownState,
+    phase: SomePhase,
+    rules: RuleSet,
+    path: &[RevealIndex],
+    player: Player,
+    hidden: HiddenState,
+) -> Result<Vec<ActionProbability>, QueryError> {
+    let cursor = follow_path(scope, state, phase, rules, path)?;
+    let (state, phase) = (cursor.state(), cursor.phase());
+
+    let info: EncodingInfo = match (phase.hidden_index_decoding_info(), hidden.choice) {
+        (PerPhaseInfo::Main(()), None) => PerPhaseInfo::Main(hidden.hand),
+        (PerPhaseInfo::Sabotage((), ()), Some(choice)) => {
+            PerPhaseInfo::Sabotage(hidden.hand, choice)
+        }
+        (PerPhaseInfo::Seer((), (), revealed), Some(choice)) => {
+            PerPhaseInfo::Seer(hidden.hand, choice, revealed)
+        }
+        _ => return Err(QueryError::InvalidHiddenState),
+    };
+
+    let hidden_index =
+        HiddenIndex::try_encode(&state, player, info).ok_or(QueryError::InvalidHiddenState)?;
+
+    let strategy = match cursor
+        .matrix(player)
+        .and_then(|matrix| matrix.get_node(hidden_index))
+    {
+        Some(vector) => vector.get_average_strategy(),
+        None => vec![1.0],
+    };
+
+    let actions = strategy
+        .into_iter()
+        .enumerate()
+        .filter_map(|(index, probability)| {
+            let decision = DecisionIndex(index);
+            let choice = RecordedChoice::decode(&phase, &state, player, info, decision)?;
+
+            Some(ActionProbability {
+                choice,
+                probability,
+            })
+        })
+        .collect();
+
+    Ok(actions)
+}
+// }}}
+// {{{ Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cfr::phase::{MainPhase, PerPhase, PhaseTag};
+    use crate::cfr::train::fixtures::{self, trained_scope};
+    use crate::game::known_state_summary::KnownStateEssentials;
+    use crate::game::notation::Position;
+    use crate::helpers::bitfield::Bitfield;
+    use bumpalo::Bump;
+
+    fn total_probability(actions: &[ActionProbability]) -> f32 {
+        actions.iter().map(|action| action.probability).sum()
+    }
+
+    #[test]
+    fn queries_every_phase() {
+        let allocator = Bump::new();
+        let state = Position::parse("PPPL 3 +0 WSRBDG RS/RS S/-").unwrap().state;
+        let root = PerPhase::Main(MainPhase::new());
+        let rules = fixtures::rules();
+        let scope = trained_scope(&allocator, state, 20);
+
+        // The seer effect makes us play our entire hand
+        let hand = (!state.graveyard)
+            .subsets_of_size(state.hand_size())
+            .next()
+            .unwrap();
+        let query = |path: &[RevealIndex], hidden| {
+            query_strategy(&scope, state, root, rules, path, Player::Me, hidden)
+        };
+
+        // Main phase
+        let main = query(&[], HiddenState::new(hand, None)).unwrap();
+        assert_eq!(main.len(), 2);
+        assert!((total_probability(&main) - 1.0).abs() < 1e-3);
+        assert!(main
+            .iter()
+            .all(|action| action.choice.tag() == PhaseTag::Main));
+
+        // Sabotage phase
+        let cursor = ScopeCursor::new(&scope, state, root);
+        let (first, sabotage_cursor) = cursor
+            .children(rules)
+            .find(|(_, child)| child.is_explored())
+            .unwrap();
+        let sabotage = query(&[first], HiddenState::new(hand, Some(hand))).unwrap();
+        assert!(!sabotage.is_empty());
+        assert!((total_probability(&sabotage) - 1.0).abs() < 1e-3);
+        assert!(sabotage
+            .iter()
+            .all(|action| action.choice.tag() == PhaseTag::Sabotage));
+
+        // Seer phase (the revealed creature must be one of the opponent's)
+        let (second, _) = sabotage_cursor
+            .children(rules)
+            .find(|(_, child)| {
+                let revealed = child.phase().hidden_index_decoding_info().get_seer();
+                child.is_explored() && revealed.is_some_and(|revealed| !hand.has(revealed))
+            })
+            .unwrap();
+        let path = [first, second];
+        let seer = query(&path, HiddenState::new(hand, Some(hand))).unwrap();
+        assert_eq!(seer.len(), 2);
+        assert!((total_probability(&seer) - 1.0).abs() < 1e-3);
+        assert!(seer
+            .iter()
+            .all(|action| action.choice.tag() == PhaseTag::Seer));
+
+        // Errors
+        let out_of_range = RevealIndex(cursor.explored().unwrap().next.len());
+        assert_eq!(
+            query(&[], HiddenState::new(hand, Some(hand))),
+            Err(QueryError::InvalidHiddenState)
+        );
+        assert_eq!(
+            query(&[first], HiddenState::new(hand, None)),
+            Err(QueryError::InvalidHiddenState)
+        );
+        assert_eq!(
+            query(&[out_of_range], HiddenState::new(hand, None)),
+            Err(QueryError::InvalidReveal {
+                depth: 0,
+                reveal: out_of_range
+            })
+        );
+    }
+}
+// }}}
@@ -0,0 +1,322 @@
+use super::echo_ai::{AgentInput, EchoAgent};
+use super::game_record::SetDisplay;
+use crate::cfr::decision_index::DecisionIndex;
+use crate::cfr::phase::PerPhase;
+use crate::cfr::reveal_index::RevealIndex;
+use crate::game::creature::{Creature, CreatureSet};
+use crate::game::known_state_summary::KnownStateEssentials;
+use crate::game::types::{Player, Score};
+use crate::helpers::bitfield::Bitfield;
+use dialoguer::console::style;
+use dialoguer::theme::ColorfulTheme;
+use dialoguer::{MultiSelect, Select};
+use std::io;
+use tracing::Level;
+
+/// An echo agent letting a human play from the terminal.
+///
+/// Unlike `HumanAgent`, this requires no display, so games can be
+/// played over ssh on headless machines.
+#[derive(Default)]
+pub struct TerminalAgent {
+    theme: ColorfulTheme,
+    /// The last input we've received, used for describing reveals.
+    last_input: Option<AgentInput>,
+    /// The last score we've been told about.
+    score: Score,
+}
+
+impl TerminalAgent {
+    // {{{ Printing
+    fn subject(player: Player, who: Player) -> &'static str {
+        if player == who {
+            "You"
+        } else {
+            "They"
+        }
+    }
+
+    /// Prints everything the player knows about the current position.
+    fn print_position(&self, agent_input: &AgentInput) {
+        let AgentInput {
+            phase,
+            state,
+            player,
+            hidden,
+        } = *agent_input;
+
+        println!();
+        println!(
+            "{}",
+            style(format!("=== {:?} phase ===", phase.tag())).bold()
+        );
+
+        let battlefields: Vec<_> = state
+            .battlefields
+            .all
+            .iter()
+            .enumerate()
+            .map(|(index, battlefield)| {
+                if index == state.battlefields.current {
+                    style(format!("[{battlefield}]")).bold().to_string()
+                } else if index < state.battlefields.current {
+                    style(battlefield.to_string()).dim().to_string()
+                } else {
+                    battlefield.to_string()
+                }
+            })
+            .collect();
+
+        println!("Battlefields: {}", battlefields.join(" "));
+        println!("Score: {}", state.score.from_perspective(player).0);
+        println!("Graveyard: {}", SetDisplay(state.graveyard));
+
+        for (name, who) in [("Your", player), ("Their", !player)] {
+            let player_state = who.select(state.player_states);
+            println!(
+                "{name} edicts: {} — status effects: {}",
+                SetDisplay(player_state.edicts),
+                SetDisplay(player_state.effects)
+            );
+        }
+
+        println!("Your hand: {}", SetDisplay(hidden.get_main()));
+
+        match phase {
+            PerPhase::Main(_) => {}
+            PerPhase::Sabotage(sabotage) => {
+                let [mine, theirs] = player.order_as(sabotage.edict_choices);
+                println!("Edicts played: yours {mine}, theirs {theirs}");
+            }
+            PerPhase::Seer(seer) => {
+                let [mine, theirs] = player.order_as(seer.edict_choices);
+                println!("Edicts played: yours {mine}, theirs {theirs}");
+                println!(
+                    "{} revealed {}",
+                    Self::subject(player, !state.last_creature_revealer()),
+                    seer.revealed_creature
+                );
+            }
+        }
+    }
+
+    /// Describes the information revealed at the end of a phase.
+    fn print_reveal(
+        &self,
+        agent_input: &AgentInput,
+        reveal_index: RevealIndex,
+        score: Score,
+    ) -> Option<()> {
+        let AgentInput {
+            phase,
+            state,
+            player,
+            ..
+        } = *agent_input;
+
+        match phase.advance_phase(&state, reveal_index)? {
+            PerPhase::Sabotage(sabotage) => {
+                let [mine, theirs] = player.order_as(sabotage.edict_choices);
+                println!("You played {mine}, they played {theirs}");
+            }
+            PerPhase::Seer(seer) => {
+                let [_, theirs] = player.order_as(seer.sabotage_choices);
+                if let Some(guess) = theirs {
+                    println!("They tried to sabotage {guess}");
+                }
+
+                println!(
+                    "{} revealed {}",
+                    Self::subject(player, !state.last_creature_revealer()),
+                    seer.revealed_creature
+                );
+            }
+            PerPhase::Main(_) => {
+                let PerPhase::Seer(seer) = phase else {
+                    return None;
+                };
+
+                let creature = reveal_index
+                    .decode_seer_phase_reveal(state.graveyard, seer.revealed_creature)?;
+                println!(
+                    "{} revealed {creature}",
+                    Self::subject(player, state.last_creature_revealer())
+                );
+                println!(
+                    "{}",
+                    style(format!("Score: {}", score.from_perspective(player).0)).bold()
+                );
+            }
+        }
+
+        Some(())
+    }
+    // }}}
+    // {{{ Prompting
+    fn select_creature(&self, prompt: &str, creatures: CreatureSet) -> io::Result<Creature> {
+        let items: Vec<_> = creatures.into_iter().collect();
+        let index = Select::with_theme(&self.theme)
+            .with_prompt(prompt)
+            .items(&items)
+            .default(0)
+            .interact()?;
+
+        Ok(items[index])
+    }
+
+    fn prompt_main(&self, agent_input: &AgentInput) -> io::Result<Option<DecisionIndex>> {
+        let AgentInput {
+            state,
+            player,
+            hidden,
+            ..
+        } = *agent_input;
+
+        let hand = hidden.get_main();
+        let count = state.creature_choice_size(player);
+
+        let creatures = if count == 1 {
+            CreatureSet::singleton(self.select_creature("Play a creature", hand)?)
+        } else {
+            let items: Vec<_> = hand.into_iter().collect();
+
+            loop {
+                let chosen = MultiSelect::with_theme(&self.theme)
+                    .with_prompt(format!("Play {count} creatures (space to select)"))
+                    .items(&items)
+                    .interact()?;
+
+                if chosen.len() == count {
+                    let mut creatures = CreatureSet::empty();
+                    for index in chosen {
+                        creatures.insert(items[index]);
+                    }
+
+                    break creatures;
+                }
+
+                println!("Exactly {count} creatures must be chosen!");
+            }
+        };
+
+        let edicts: Vec<_> = state.player_edicts(player).into_iter().collect();
+        let edict = Select::with_theme(&self.theme)
+            .with_prompt("Play an edict")
+            .items(&edicts)
+            .default(0)
+            .interact()?;
+
+        Ok(DecisionIndex::encode_main_phase_index(
+            &state,
+            player,
+            hand,
+            creatures,
+            edicts[edict],
+        ))
+    }
+
+    fn prompt(&self, agent_input: &AgentInput) -> io::Result<Option<DecisionIndex>> {
+        let AgentInput {
+            phase,
+            state,
+            player,
+            hidden,
+        } = *agent_input;
+
+        let decision = match phase {
+            PerPhase::Main(_) => self.prompt_main(agent_input)?,
+            PerPhase::Sabotage(_) => {
+                let hand = hidden.get_main();
+                let possibilities = !(hand | state.graveyard);
+                let guess = self.select_creature("Sabotage a creature", possibilities)?;
+
+                Some(DecisionIndex::encode_sabotage_index(
+                    &state,
+                    hand,
+                    Some(guess),
+                ))
+            }
+            PerPhase::Seer(_) => {
+                let Some(choices) = hidden.get_sabotage() else {
+                    return Ok(None);
+                };
+
+                let kept = self.select_creature(
+                    "Choose the creature to reveal (the other returns to your hand)",
+                    choices,
+                )?;
+
+                DecisionIndex::encode_seer_index(choices, kept)
+            }
+        };
+
+        let count = player.select(phase.decision_counts(&state));
+        Ok(decision.filter(|decision| decision.0 < count))
+    }
+    // }}}
+}
+
+impl EchoAgent for TerminalAgent {
+    fn choose(&mut self, agent_input: AgentInput) -> DecisionIndex {
+        self.last_input = Some(agent_input);
+
+        let count = agent_input
+            .player
+            .select(agent_input.phase.decision_counts(&agent_input.state));
+
+        // Skip over decisions which do not matter
+        if count == 1 {
+            return DecisionIndex::default();
+        }
+
+        self.print_position(&agent_input);
+
+        loop {
+            match self.prompt(&agent_input) {
+                Ok(Some(decision)) => return decision,
+                Ok(None) => println!("Invalid choice, try again!"),
+                Err(error) => {
+                    tracing::event!(
+                        Level::ERROR,
+                        %error,
+                        "Could not read input, defaulting to the first decision"
+                    );
+
+                    return DecisionIndex::default();
+                }
+            }
+        }
+    }
+
+    fn reveal_info(&mut self, reveal_index: RevealIndex, updated_score: Score) {
+        self.score = updated_score;
+
+        if let Some(agent_input) = self.last_input {
+            if self
+                .print_reveal(&agent_input, reveal_index, updated_score)
+                .is_none()
+            {
+                tracing::event!(Level::WARN, "Could not decode reveal index");
+            }
+        }
+    }
+
+    fn game_finished(&mut self) {
+        let player = self.last_input.map_or(Player::Me, |input| input.player);
+        let score = self.score.from_perspective(player);
+
+        println!();
+        println!(
+            "{}",
+            style(format!(
+                "=== Game finished: {:?} with a score of {} ===",
+                score.to_battle_result(),
+                score.0
+            ))
+            .bold()
+        );
+
+        self.last_input = None;
+        self.score = Score::default();
+    }
+}
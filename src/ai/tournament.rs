@@ -0,0 +1,638 @@
+use super::duplicate::DuplicateRunner;
+use super::echo_ai::EchoAgent;
+use super::game_record::ParseError;
+use super::match_runner::MatchReport;
+use crate::game::rules::RuleSet;
+use crate::helpers::statistics::Z_95;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{self, Display};
+use std::str::FromStr;
+use tracing::Level;
+
+/// The first line of every stored tournament.
+pub const TOURNAMENT_HEADER: &str = "echo-tournament 1";
+
+// {{{ Pairings
+/// The results of every game played between two agents,
+/// from the perspective of the first one.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PairingResult {
+    pub wins: usize,
+    pub ties: usize,
+    pub losses: usize,
+    pub errors: usize,
+    /// The sum of the final score margins of every game.
+    pub margin: i64,
+}
+
+impl PairingResult {
+    #[inline(always)]
+    pub fn games(&self) -> usize {
+        self.wins + self.ties + self.losses
+    }
+
+    /// The number of points gained (win = 1, tie = 1/2, loss = 0).
+    #[inline(always)]
+    pub fn points(&self) -> f64 {
+        self.wins as f64 + self.ties as f64 / 2.0
+    }
+
+    /// The average number of points gained per game.
+    #[inline(always)]
+    pub fn expected_score(&self) -> f64 {
+        self.points() / self.games().max(1) as f64
+    }
+
+    /// The same results, from the perspective of the second agent.
+    pub fn flipped(self) -> Self {
+        Self {
+            wins: self.losses,
+            losses: self.wins,
+            margin: -self.margin,
+            ..self
+        }
+    }
+}
+
+impl From<&MatchReport> for PairingResult {
+    fn from(report: &MatchReport) -> Self {
+        Self {
+            wins: report.wins,
+            ties: report.ties,
+            losses: report.losses,
+            errors: report.errors,
+            margin: (report.margins.mean() * report.margins.count() as f64).round() as i64,
+        }
+    }
+}
+// }}}
+// {{{ Results
+/// Every pairing played so far, keyed by the names of the agents involved.
+///
+/// Results can be written to (and read back from) a simple text format,
+/// so new agents can be added to a tournament without replaying old pairings:
+/// ```text
+/// echo-tournament 1
+/// # first second wins ties losses errors margin
+/// pairing greedy random 1694 48 258 0 15460
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TournamentResults {
+    pairings: BTreeMap<(String, String), PairingResult>,
+}
+
+impl TournamentResults {
+    /// Returns the results of `first` against `second`, if they've played.
+    pub fn get(&self, first: &str, second: &str) -> Option<PairingResult> {
+        let key = |a: &str, b: &str| (a.to_string(), b.to_string());
+
+        self.pairings
+            .get(&key(first, second))
+            .copied()
+            .or_else(|| self.pairings.get(&key(second, first)).map(|r| r.flipped()))
+    }
+
+    #[inline(always)]
+    pub fn contains(&self, first: &str, second: &str) -> bool {
+        self.get(first, second).is_some()
+    }
+
+    /// Adds the results of more games played between two agents.
+    pub fn insert(&mut self, first: &str, second: &str, result: PairingResult) {
+        let reversed = (second.to_string(), first.to_string());
+
+        let (key, result) = if self.pairings.contains_key(&reversed) {
+            (reversed, result.flipped())
+        } else {
+            ((first.to_string(), second.to_string()), result)
+        };
+
+        let entry = self.pairings.entry(key).or_default();
+        entry.wins += result.wins;
+        entry.ties += result.ties;
+        entry.losses += result.losses;
+        entry.errors += result.errors;
+        entry.margin += result.margin;
+    }
+
+    /// Lists every agent taking part in at least one pairing, sorted by name.
+    pub fn names(&self) -> Vec<&str> {
+        let names: BTreeSet<_> = self
+            .pairings
+            .keys()
+            .flat_map(|(first, second)| [first.as_str(), second.as_str()])
+            .collect();
+
+        names.into_iter().collect()
+    }
+
+    /// Parses results written using the `Display` implementation.
+    pub fn parse(input: &str) -> Result<Self, ParseError> {
+        let mut lines = input
+            .lines()
+            .enumerate()
+            .map(|(index, line)| (index + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'));
+
+        match lines.next() {
+            Some((_, TOURNAMENT_HEADER)) => {}
+            Some((line, other)) => {
+                return Err(ParseError {
+                    line,
+                    message: format!("Expected {TOURNAMENT_HEADER:?}, got {other:?}"),
+                })
+            }
+            None => {
+                return Err(ParseError {
+                    line: 1,
+                    message: "Empty input".to_string(),
+                })
+            }
+        }
+
+        let mut results = Self::default();
+
+        for (line, contents) in lines {
+            let error = |message: String| ParseError { line, message };
+            let words: Vec<_> = contents.split_whitespace().collect();
+
+            let ["pairing", first, second, numbers @ ..] = words.as_slice() else {
+                return Err(error(format!("Expected a pairing, got {contents:?}")));
+            };
+
+            let [wins, ties, losses, errors, margin] = numbers else {
+                return Err(error(format!(
+                    "Expected 5 numbers after the names, got {}",
+                    numbers.len()
+                )));
+            };
+
+            let count = |input: &str| {
+                input
+                    .parse::<usize>()
+                    .map_err(|_| error(format!("Invalid count {input:?}")))
+            };
+
+            let result = PairingResult {
+                wins: count(wins)?,
+                ties: count(ties)?,
+                losses: count(losses)?,
+                errors: count(errors)?,
+                margin: margin
+                    .parse()
+                    .map_err(|_| error(format!("Invalid margin {margin:?}")))?,
+            };
+
+            if first == second {
+                return Err(error(format!("Agent {first:?} cannot play itself")));
+            }
+
+            results.insert(first, second, result);
+        }
+
+        Ok(results)
+    }
+}
+
+impl Display for TournamentResults {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{TOURNAMENT_HEADER}")?;
+        writeln!(f, "# first second wins ties losses errors margin")?;
+
+        for ((first, second), result) in &self.pairings {
+            writeln!(
+                f,
+                "pairing {first} {second} {} {} {} {} {}",
+                result.wins, result.ties, result.losses, result.errors, result.margin
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for TournamentResults {
+    type Err = ParseError;
+
+    #[inline(always)]
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+// }}}
+// {{{ Ratings
+/// The Bradley-Terry rating of an agent, expressed on the Elo scale.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rating {
+    pub name: String,
+    /// Ratings are centered around 0.
+    pub elo: f64,
+    /// The (approximate) standard error of the rating.
+    pub deviation: f64,
+    pub games: usize,
+    /// The average number of points gained per game.
+    pub score: f64,
+}
+
+/// The number of virtual tied games added to every pairing. Keeps ratings
+/// finite for agents which won (or lost) every single game they played.
+const PRIOR_GAMES: f64 = 1.0;
+
+impl TournamentResults {
+    /// Fits a Bradley-Terry model to the results (ties count as half a win
+    /// for each side) using the minorization-maximization algorithm.
+    ///
+    /// Deviations ignore the correlations between ratings, so they are only
+    /// a rough indication of how much each rating can be trusted.
+    ///
+    /// The result is sorted from the strongest agent to the weakest.
+    pub fn ratings(&self) -> Vec<Rating> {
+        let names = self.names();
+        let count = names.len();
+
+        // {{{ Gather statistics
+        let mut games = vec![vec![0.0; count]; count];
+        let mut points = vec![0.0; count];
+
+        for i in 0..count {
+            for j in 0..count {
+                if let Some(result) = self.get(names[i], names[j]) {
+                    games[i][j] = result.games() as f64 + PRIOR_GAMES;
+                    points[i] += result.points() + PRIOR_GAMES / 2.0;
+                }
+            }
+        }
+        // }}}
+        // {{{ Fit the model
+        let mut strengths = vec![1.0; count];
+
+        for _ in 0..10_000 {
+            let mut change: f64 = 0.0;
+
+            for i in 0..count {
+                let denominator: f64 = (0..count)
+                    .map(|j| games[i][j] / (strengths[i] + strengths[j]))
+                    .sum();
+
+                if denominator > 0.0 {
+                    let updated = points[i] / denominator;
+                    change = change.max((updated / strengths[i]).ln().abs());
+                    strengths[i] = updated;
+                }
+            }
+
+            // Ratings are only defined up to a constant,
+            // so we normalize the geometric mean to 1.
+            let mean = strengths.iter().map(|s: &f64| s.ln()).sum::<f64>() / count as f64;
+            for strength in &mut strengths {
+                *strength /= mean.exp();
+            }
+
+            if change < 1e-9 {
+                break;
+            }
+        }
+        // }}}
+
+        let to_elo = 400.0 / std::f64::consts::LN_10;
+
+        let mut ratings: Vec<_> = (0..count)
+            .map(|i| {
+                let information: f64 = (0..count)
+                    .map(|j| {
+                        let p = strengths[i] / (strengths[i] + strengths[j]);
+                        games[i][j] * p * (1.0 - p)
+                    })
+                    .sum();
+
+                let (played, scored) = (0..count)
+                    .filter_map(|j| self.get(names[i], names[j]))
+                    .fold((0, 0.0), |(games, points), result| {
+                        (games + result.games(), points + result.points())
+                    });
+
+                Rating {
+                    name: names[i].to_string(),
+                    elo: to_elo * strengths[i].ln(),
+                    deviation: to_elo / information.sqrt(),
+                    games: played,
+                    score: scored / played.max(1) as f64,
+                }
+            })
+            .collect();
+
+        ratings.sort_by(|a, b| b.elo.total_cmp(&a.elo));
+        ratings
+    }
+
+    /// Returns a displayable leaderboard.
+    #[inline(always)]
+    pub fn leaderboard(&self) -> Leaderboard {
+        Leaderboard(self.ratings())
+    }
+
+    /// Returns a displayable matrix of the expected score
+    /// of every agent against every other agent.
+    #[inline(always)]
+    pub fn matrix(&self) -> PairwiseMatrix<'_> {
+        PairwiseMatrix(self)
+    }
+}
+// }}}
+// {{{ Displaying
+pub struct Leaderboard(pub Vec<Rating>);
+
+impl Display for Leaderboard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let width = self
+            .0
+            .iter()
+            .map(|r| r.name.len())
+            .max()
+            .unwrap_or(0)
+            .max(5);
+
+        writeln!(
+            f,
+            "   # {:width$} {:>6} {:>6} {:>7} {:>6}",
+            "agent", "elo", "±", "games", "score"
+        )?;
+
+        for (index, rating) in self.0.iter().enumerate() {
+            writeln!(
+                f,
+                "{:>4} {:width$} {:>6.0} {:>6.0} {:>7} {:>5.1}%",
+                index + 1,
+                rating.name,
+                rating.elo,
+                Z_95 * rating.deviation,
+                rating.games,
+                100.0 * rating.score
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+pub struct PairwiseMatrix<'a>(&'a TournamentResults);
+
+impl Display for PairwiseMatrix<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names: Vec<_> = self
+            .0
+            .ratings()
+            .into_iter()
+            .map(|rating| rating.name)
+            .collect();
+        let width = names.iter().map(|n| n.len()).max().unwrap_or(0);
+        let column = names.iter().map(|n| n.len()).max().unwrap_or(0).max(6);
+
+        write!(f, "{:width$}", "")?;
+        for name in &names {
+            write!(f, " {name:>column$}")?;
+        }
+        writeln!(f)?;
+
+        for first in &names {
+            write!(f, "{first:width$}")?;
+
+            for second in &names {
+                match self.0.get(first, second) {
+                    Some(result) => write!(
+                        f,
+                        " {:>column$}",
+                        format!("{:.1}%", 100.0 * result.expected_score())
+                    )?,
+                    None => write!(f, " {:>column$}", "-")?,
+                }
+            }
+
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
+// }}}
+// {{{ Tournament
+/// Creates a fresh agent given a seed.
+pub type AgentFactory = Box<dyn Fn(u64) -> Box<dyn EchoAgent> + Sync>;
+
+/// A round robin tournament between a set of registered agents.
+/// Every pairing gets played as a duplicate match.
+///
+/// Pairings already present in the results are never replayed,
+/// so previously stored results can be reused when registering new agents.
+pub struct Tournament {
+    entrants: Vec<(String, AgentFactory)>,
+    deals: usize,
+    seed: u64,
+    rules: RuleSet,
+    results: TournamentResults,
+}
+
+impl Tournament {
+    /// Creates a tournament playing the given number of deals
+    /// (each played twice) for every pairing.
+    pub fn new(deals: usize) -> Self {
+        Self {
+            entrants: Vec::new(),
+            deals,
+            seed: 0,
+            rules: RuleSet::default(),
+            results: TournamentResults::default(),
+        }
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Plays every pairing under some variant of the rules. Results stored
+    /// under different rules should not be mixed with the new ones.
+    pub fn with_rules(mut self, rules: RuleSet) -> Self {
+        self.rules = rules;
+        self
+    }
+
+    /// Starts off from some previously stored results.
+    pub fn with_results(mut self, results: TournamentResults) -> Self {
+        self.results = results;
+        self
+    }
+
+    /// Registers a new agent. Names must be unique, and must not contain whitespace.
+    pub fn register<A, F>(&mut self, name: &str, factory: F) -> &mut Self
+    where
+        A: EchoAgent + 'static,
+        F: Fn(u64) -> A + Sync + 'static,
+    {
+        assert!(
+            !name.is_empty() && !name.contains(char::is_whitespace),
+            "Invalid agent name {name:?}"
+        );
+        assert!(
+            self.entrants.iter().all(|(other, _)| other != name),
+            "Agent {name:?} has already been registered"
+        );
+
+        self.entrants.push((
+            name.to_string(),
+            Box::new(move |seed| Box::new(factory(seed)) as Box<dyn EchoAgent>),
+        ));
+
+        self
+    }
+
+    #[inline(always)]
+    pub fn results(&self) -> &TournamentResults {
+        &self.results
+    }
+
+    /// Plays every pairing which has not been played yet.
+    /// Returns the number of pairings played.
+    pub fn run(&mut self) -> usize {
+        let mut played = 0;
+
+        for i in 0..self.entrants.len() {
+            for j in i + 1..self.entrants.len() {
+                let (first, first_factory) = &self.entrants[i];
+                let (second, second_factory) = &self.entrants[j];
+
+                if self.results.contains(first, second) {
+                    continue;
+                }
+
+                let _guard = tracing::span!(Level::INFO, "Pairing", first, second);
+
+                let report = DuplicateRunner::new(first_factory, second_factory, self.deals)
+                    .with_seed(self.seed)
+                    .with_rules(self.rules)
+                    .run();
+
+                tracing::event!(
+                    Level::INFO,
+                    score = report.expected_score(),
+                    "Pairing finished"
+                );
+
+                let result = PairingResult::from(&report.games);
+                self.results.insert(first, second, result);
+                played += 1;
+            }
+        }
+
+        played
+    }
+}
+// }}}
+// {{{ Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::always_zero_agent::AlwaysZeroAgent;
+    use crate::ai::greedy_agent::GreedyAgent;
+    use crate::ai::random_agent::RandomAgent;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn result(wins: usize, ties: usize, losses: usize) -> PairingResult {
+        PairingResult {
+            wins,
+            ties,
+            losses,
+            errors: 0,
+            margin: 3 * (wins as i64 - losses as i64),
+        }
+    }
+
+    fn sample_results() -> TournamentResults {
+        let mut results = TournamentResults::default();
+        results.insert("strong", "medium", result(70, 10, 20));
+        results.insert("medium", "weak", result(65, 5, 30));
+        results.insert("weak", "strong", result(5, 5, 90));
+        results
+    }
+
+    #[test]
+    fn results_roundtrip_through_text() {
+        let results = sample_results();
+        let text = results.to_string();
+
+        assert_eq!(TournamentResults::parse(&text), Ok(results));
+    }
+
+    #[test]
+    fn pairings_are_looked_up_in_both_directions() {
+        let mut results = sample_results();
+        assert_eq!(results.get("strong", "weak"), Some(result(90, 5, 5)));
+
+        results.insert("strong", "weak", result(1, 0, 0));
+        assert_eq!(results.get("weak", "strong"), Some(result(5, 5, 91)));
+        assert_eq!(results.names(), vec!["medium", "strong", "weak"]);
+    }
+
+    #[test]
+    fn ratings_follow_results() {
+        let ratings = sample_results().ratings();
+        let names: Vec<_> = ratings.iter().map(|r| r.name.as_str()).collect();
+
+        assert_eq!(names, vec!["strong", "medium", "weak"]);
+        assert!(ratings.iter().map(|r| r.elo).sum::<f64>().abs() < 1e-6);
+
+        // A 75% expected score corresponds to a ~190 elo gap
+        let gap = ratings[0].elo - ratings[1].elo;
+        assert!(100.0 < gap && gap < 300.0);
+    }
+
+    fn round_robin(results: TournamentResults) -> Tournament {
+        let mut tournament = Tournament::new(20).with_seed(7).with_results(results);
+        tournament
+            .register("zero", |_| AlwaysZeroAgent::default())
+            .register("random", |seed| {
+                RandomAgent::new(StdRng::seed_from_u64(seed))
+            })
+            .register("greedy", |_| GreedyAgent::default());
+
+        tournament
+    }
+
+    #[test]
+    fn every_pairing_gets_played_once() {
+        let mut tournament = round_robin(TournamentResults::default());
+        assert_eq!(tournament.run(), 3);
+        assert_eq!(tournament.run(), 0);
+
+        let results = tournament.results().clone();
+        assert_eq!(results.names(), vec!["greedy", "random", "zero"]);
+
+        // Every deal gets played from both seats
+        for (first, second) in [("zero", "random"), ("zero", "greedy"), ("random", "greedy")] {
+            let result = results.get(first, second).unwrap();
+            assert_eq!(result.games() + result.errors, 40);
+        }
+
+        // Zero and random are too close to call, but greedy beats both of them
+        let ratings = results.ratings();
+        assert_eq!(ratings[0].name, "greedy");
+        assert!(ratings
+            .windows(2)
+            .all(|pair| pair[0].score >= pair[1].score));
+
+        // Stored pairings are reused, and the agents are deterministic
+        assert_eq!(round_robin(results.clone()).run(), 0);
+
+        let mut replayed = round_robin(TournamentResults::default());
+        replayed.run();
+        assert_eq!(replayed.results(), &results);
+    }
+
+    #[test]
+    fn malformed_results_are_rejected() {
+        let input = format!("{TOURNAMENT_HEADER}\npairing a b 1 2 3\n");
+        assert_eq!(TournamentResults::parse(&input).unwrap_err().line, 2);
+    }
+}
+// }}}
@@ -0,0 +1,478 @@
+use super::decision::{DecisionMatrices, Probability, Scope};
+use super::decision_index::DecisionIndex;
+use super::hidden_index::{self, HiddenIndex, HiddenState};
+use super::phase::SomePhase;
+use super::reveal_index::RevealIndex;
+use super::scope_cursor::ScopeCursor;
+use crate::game::creature::Creature;
+use crate::game::known_state::KnownState;
+use crate::game::known_state_summary::{KnownStateEssentials, KnownStateSummary};
+use crate::game::rules::RuleSet;
+use crate::game::types::{Player, TurnResult};
+use crate::helpers::bitfield::Bitfield;
+use crate::helpers::pair::Pair;
+use std::collections::HashMap;
+
+// {{{ Strategy profiles
+/// A strategy for both players at a single point in the game.
+pub trait StrategyProfile {
+    /// Returns the probability of each decision a player can take,
+    /// given the hidden index describing everything they know.
+    ///
+    /// # Arguments
+    ///
+    /// * `player` - The player taking the decision.
+    /// * `hidden` - The hidden index of said player.
+    /// * `count` - The number of decisions said player can choose from.
+    fn decision_probabilities(
+        &self,
+        player: Player,
+        hidden: HiddenIndex,
+        count: usize,
+    ) -> Vec<Probability>;
+}
+
+/// Strategy profile where every decision is equally likely.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UniformStrategy;
+
+impl StrategyProfile for UniformStrategy {
+    fn decision_probabilities(&self, _: Player, _: HiddenIndex, count: usize) -> Vec<Probability> {
+        vec![1.0 / count as Probability; count]
+    }
+}
+
+impl<'a> StrategyProfile for DecisionMatrices<'a> {
+    fn decision_probabilities(
+        &self,
+        player: Player,
+        hidden: HiddenIndex,
+        count: usize,
+    ) -> Vec<Probability> {
+        match self.get_matrix(player).get_node(hidden) {
+            Some(node) => node.get_average_strategy(),
+            None => {
+                debug_assert_eq!(count, 1);
+                vec![1.0]
+            }
+        }
+    }
+}
+// }}}
+// {{{ Public belief state
+/// A single possible combination of hidden information.
+#[derive(Debug, Clone, Copy)]
+struct BeliefEntry {
+    hidden: Pair<hidden_index::EncodingInfo>,
+    probability: Probability,
+}
+
+/// Probability distribution over the hidden information of both players,
+/// conditioned on everything revealed so far.
+///
+/// The distribution is tracked jointly (rather than per player),
+/// as the hands of the two players are never independent.
+#[derive(Debug, Clone)]
+pub struct PublicBelief {
+    state: KnownState,
+    phase: SomePhase,
+    entries: HashMap<Pair<HiddenIndex>, BeliefEntry>,
+}
+
+impl PublicBelief {
+    // {{{ Constructors
+    /// Creates a belief where every valid combination of
+    /// hidden information is equally likely.
+    pub fn new(state: KnownState, phase: SomePhase) -> Self {
+        let summary = state.to_summary();
+        let hidden_states = phase.valid_hidden_states(summary);
+        let probability = 1.0 / hidden_states.len() as Probability;
+
+        let entries = hidden_states
+            .into_iter()
+            .map(|hidden| {
+                let indices = Self::encode(&summary, hidden);
+                let entry = BeliefEntry {
+                    hidden,
+                    probability,
+                };
+
+                (indices, entry)
+            })
+            .collect();
+
+        Self {
+            state,
+            phase,
+            entries,
+        }
+    }
+
+    /// Starts from an uniform belief, and updates it by walking
+    /// down a trained scope along the given reveal indices.
+    ///
+    /// Returns `None` if the history is not consistent with the strategy,
+    /// or if the history ends the game.
+    pub fn from_history(
+        state: KnownState,
+        phase: SomePhase,
+        scope: &Scope,
+        history: &[RevealIndex],
+        rules: RuleSet,
+    ) -> Option<Self> {
+        let mut belief = Self::new(state, phase);
+        let mut cursor = ScopeCursor::new(scope, state, phase);
+
+        for &reveal_index in history {
+            belief = belief
+                .advance(reveal_index, cursor.matrices()?, rules)?
+                .get_unfinished()?;
+            cursor = cursor.advance(reveal_index, rules)?.get_unfinished()?;
+        }
+
+        Some(belief)
+    }
+    // }}}
+    // {{{ Helpers
+    #[inline(always)]
+    fn encode(
+        summary: &KnownStateSummary,
+        hidden: Pair<hidden_index::EncodingInfo>,
+    ) -> Pair<HiddenIndex> {
+        Player::PLAYERS.map(|player| HiddenIndex::encode(summary, player, player.select(hidden)))
+    }
+
+    /// The public state this belief refers to.
+    #[inline(always)]
+    pub fn state(&self) -> KnownState {
+        self.state
+    }
+
+    /// The phase this belief refers to.
+    #[inline(always)]
+    pub fn phase(&self) -> SomePhase {
+        self.phase
+    }
+
+    /// The number of hidden information combinations
+    /// with a non-zero probability.
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns true if no combination of hidden information is possible.
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+    // }}}
+    // {{{ Bayesian updates
+    /// Updates the belief once the information at the end of
+    /// the current phase has been revealed.
+    ///
+    /// The probability of each combination of hidden information gets
+    /// multiplied by the probability of the players taking decisions
+    /// which reveal exactly the given information, and is then normalized.
+    ///
+    /// Returns `None` if the revealed information is impossible under
+    /// the current belief and strategy.
+    pub fn advance<S: StrategyProfile>(
+        &self,
+        reveal_index: RevealIndex,
+        strategy: &S,
+        rules: RuleSet,
+    ) -> Option<TurnResult<Self>> {
+        let next_state = match self.phase.advance_state(&self.state, reveal_index, rules) {
+            TurnResult::Finished(score) => return Some(TurnResult::Finished(score)),
+            TurnResult::Unfinished(state) => state,
+        };
+
+        let next_phase = self.phase.advance_phase(&self.state, reveal_index)?;
+        let summary = self.state.to_summary();
+        let counts = self.phase.decision_counts(&self.state);
+
+        let mut entries: HashMap<Pair<HiddenIndex>, BeliefEntry> = HashMap::new();
+        // Summing tens of thousands of small floats loses precision quickly
+        let mut total: f64 = 0.0;
+
+        for (indices, entry) in &self.entries {
+            let hidden = entry.hidden.map(HiddenState::from_encoding_info);
+            let [mine, yours] = Player::PLAYERS.map(|player| {
+                strategy.decision_probabilities(
+                    player,
+                    player.select(*indices),
+                    player.select(counts),
+                )
+            });
+
+            for (my_index, my_probability) in mine.iter().enumerate() {
+                for (your_index, your_probability) in yours.iter().enumerate() {
+                    let probability = entry.probability * my_probability * your_probability;
+
+                    if probability <= 0.0 {
+                        continue;
+                    }
+
+                    let decisions = [DecisionIndex(my_index), DecisionIndex(your_index)];
+                    let (next_summary, next_hidden, revealed) = self
+                        .phase
+                        .advance_hidden_indices(summary, hidden, decisions)?;
+
+                    if revealed != reveal_index {
+                        continue;
+                    }
+
+                    total += probability as f64;
+
+                    let next_indices = Self::encode(&next_summary, next_hidden);
+                    entries
+                        .entry(next_indices)
+                        .or_insert(BeliefEntry {
+                            hidden: next_hidden,
+                            probability: 0.0,
+                        })
+                        .probability += probability;
+                }
+            }
+        }
+
+        if total <= 0.0 {
+            return None;
+        }
+
+        for entry in entries.values_mut() {
+            entry.probability = (entry.probability as f64 / total) as Probability;
+        }
+
+        Some(TurnResult::Unfinished(Self {
+            state: next_state,
+            phase: next_phase,
+            entries,
+        }))
+    }
+    // }}}
+    // {{{ Queries
+    /// Every combination of hidden information with a non-zero
+    /// probability, together with said probability.
+    pub fn deals(&self) -> Vec<(Pair<hidden_index::EncodingInfo>, Probability)> {
+        self.entries
+            .values()
+            .map(|entry| (entry.hidden, entry.probability))
+            .collect()
+    }
+
+    /// Computes the distribution over the hidden information of `!player`,
+    /// as seen by `player`, who knows their own hidden information.
+    ///
+    /// The results are sorted by decreasing probability. The returned list is
+    /// empty if `own` is impossible under the current belief.
+    pub fn opponent_distribution(
+        &self,
+        player: Player,
+        own: hidden_index::EncodingInfo,
+    ) -> Vec<(HiddenState, Probability)> {
+        let summary = self.state.to_summary();
+        let own_index = HiddenIndex::encode(&summary, player, own);
+
+        let matching = self
+            .entries
+            .iter()
+            .filter(|(indices, _)| player.select(**indices) == own_index)
+            .map(|(_, entry)| {
+                let hidden = HiddenState::from_encoding_info((!player).select(entry.hidden));
+                (hidden, entry.probability)
+            })
+            .collect::<Vec<_>>();
+
+        let total: f64 = matching.iter().map(|(_, p)| *p as f64).sum();
+
+        let mut result = matching
+            .into_iter()
+            .map(|(hidden, p)| (hidden, (p as f64 / total) as Probability))
+            .collect::<Vec<_>>();
+
+        result.sort_by(|a, b| b.1.total_cmp(&a.1));
+        result
+    }
+
+    /// Computes the probability of `!player` holding each creature,
+    /// as seen by `player`. The result is indexed by creature.
+    pub fn opponent_creature_probabilities(
+        &self,
+        player: Player,
+        own: hidden_index::EncodingInfo,
+    ) -> [Probability; 11] {
+        let mut result = [0.0; 11];
+
+        for (hidden, probability) in self.opponent_distribution(player, own) {
+            for creature in hidden.hand {
+                result[creature as usize] += probability;
+            }
+        }
+
+        result
+    }
+
+    /// Computes the distribution over the hidden information of a given
+    /// player, as seen by someone who knows only the public information.
+    pub fn marginal(&self, player: Player) -> Vec<(HiddenState, Probability)> {
+        let mut by_index: HashMap<HiddenIndex, (HiddenState, Probability)> = HashMap::new();
+
+        for (indices, entry) in &self.entries {
+            let hidden = HiddenState::from_encoding_info(player.select(entry.hidden));
+            by_index
+                .entry(player.select(*indices))
+                .or_insert((hidden, 0.0))
+                .1 += entry.probability;
+        }
+
+        let mut result = by_index.into_values().collect::<Vec<_>>();
+        result.sort_by(|a, b| b.1.total_cmp(&a.1));
+        result
+    }
+
+    /// Returns the probability the given creature is the overseer
+    /// (the creature no player has in hand).
+    pub fn overseer_probability(&self, creature: Creature) -> Probability {
+        if self.state.graveyard.has(creature) {
+            return 0.0;
+        }
+
+        self.entries
+            .values()
+            .filter(|entry| {
+                let [mine, yours] = entry.hidden.map(|h| h.get_main());
+                !(mine | yours).has(creature)
+            })
+            .map(|entry| entry.probability)
+            .sum()
+    }
+    // }}}
+}
+// }}}
+// {{{ Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cfr::hidden_index::PerPhaseInfo;
+    use crate::cfr::phase::MainPhase;
+    use crate::game::battlefield::Battlefield;
+    use crate::game::creature::CreatureSet;
+    use crate::game::edict::Edict;
+
+    /// Plays the gambit whenever holding the monarch, and
+    /// rile the public otherwise. Always plays the strongest creature.
+    struct MonarchTell {
+        state: KnownState,
+    }
+
+    impl StrategyProfile for MonarchTell {
+        fn decision_probabilities(
+            &self,
+            player: Player,
+            hidden: HiddenIndex,
+            count: usize,
+        ) -> Vec<Probability> {
+            let hand = hidden
+                .decode(&self.state, player, PerPhaseInfo::Main(()))
+                .unwrap()
+                .hand;
+
+            let edict = if hand.has(Creature::Monarch) {
+                Edict::Gambit
+            } else {
+                Edict::RileThePublic
+            };
+
+            let creature = hand.into_iter().max_by_key(|c| c.strength()).unwrap();
+            let decision = DecisionIndex::encode_main_phase_index(
+                &self.state,
+                player,
+                hand,
+                CreatureSet::singleton(creature),
+                edict,
+            )
+            .unwrap();
+
+            let mut result = vec![0.0; count];
+            result[decision.0] = 1.0;
+            result
+        }
+    }
+
+    fn starting_state() -> KnownState {
+        KnownState::new_starting([Battlefield::Plains; 4])
+    }
+
+    #[test]
+    fn initial_belief_is_uniform() {
+        let state = starting_state();
+        let belief = PublicBelief::new(state, SomePhase::Main(MainPhase::new()));
+        let hand = CreatureSet::all().subsets_of_size(5).next().unwrap();
+        let distribution = belief.opponent_distribution(Player::Me, PerPhaseInfo::Main(hand));
+
+        // The opponent holds 5 of the remaining 6 creatures
+        assert_eq!(distribution.len(), 6);
+
+        for (hidden, probability) in distribution {
+            assert!(hidden.hand.is_disjoint_from(hand));
+            assert!((probability - 1.0 / 6.0).abs() < 0.0001);
+        }
+    }
+
+    #[test]
+    fn uniform_updates_stay_normalized() {
+        let state = starting_state();
+        let belief = PublicBelief::new(state, SomePhase::Main(MainPhase::new()));
+        let edicts = [Edict::Ambush, Edict::Gambit];
+        let reveal_index =
+            RevealIndex::encode_main_phase_reveal(edicts, state.edict_sets()).unwrap();
+
+        let belief = belief
+            .advance(reveal_index, &UniformStrategy, RuleSet::default())
+            .unwrap()
+            .get_unfinished()
+            .unwrap();
+
+        for player in Player::PLAYERS {
+            let total: f64 = belief.marginal(player).iter().map(|(_, p)| *p as f64).sum();
+            assert!((total - 1.0).abs() < 0.0001);
+        }
+    }
+
+    #[test]
+    fn revealed_tells_are_picked_up() {
+        let state = starting_state();
+        let belief = PublicBelief::new(state, SomePhase::Main(MainPhase::new()));
+        let strategy = MonarchTell { state };
+        let hand = (!CreatureSet::singleton(Creature::Monarch))
+            .subsets_of_size(5)
+            .next()
+            .unwrap();
+
+        for (your_edict, holds_monarch) in [(Edict::Gambit, 1.0), (Edict::RileThePublic, 0.0)] {
+            let edicts = [Edict::RileThePublic, your_edict];
+            let reveal_index =
+                RevealIndex::encode_main_phase_reveal(edicts, state.edict_sets()).unwrap();
+
+            let next = belief
+                .advance(reveal_index, &strategy, RuleSet::default())
+                .unwrap()
+                .get_unfinished()
+                .unwrap();
+
+            // We always play our strongest creature
+            let choice =
+                CreatureSet::singleton(hand.into_iter().max_by_key(|c| c.strength()).unwrap());
+            let probabilities = next
+                .opponent_creature_probabilities(Player::Me, PerPhaseInfo::Sabotage(hand, choice));
+
+            assert!(
+                (probabilities[Creature::Monarch as usize] - holds_monarch).abs() < 0.0001,
+                "Expected the monarch to be held with probability {holds_monarch}"
+            );
+        }
+    }
+}
+// }}}
@@ -168,6 +168,20 @@ impl<'a> DecisionMatrix<'a> {
         }
     }
 
+    /// Iterates over every node of the matrix, together with it's hidden index.
+    /// Trivial matrices contain no nodes.
+    pub fn nodes(&self) -> impl Iterator<Item = (HiddenIndex, &DecisionVector<'a>)> {
+        let vectors: &[DecisionVector<'a>] = match self {
+            Self::Trivial => &[],
+            Self::Expanded(vectors) => vectors,
+        };
+
+        vectors
+            .iter()
+            .enumerate()
+            .map(|(index, vector)| (HiddenIndex(index), vector))
+    }
+
     pub fn estimate_alloc(matrix_size: usize, vector_size: usize) -> usize {
         size_of::<Self>()
             + if vector_size == 1 {
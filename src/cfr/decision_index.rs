@@ -7,9 +7,10 @@ use crate::helpers::bitfield::Bitfield;
 use crate::helpers::choose::choose;
 use crate::helpers::ranged::MixRanged;
 use itertools::Itertools;
+use serde::{Deserialize, Serialize};
 
 /// Used to index decision vectors.
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Copy, Clone, Default)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Copy, Clone, Default, Serialize, Deserialize)]
 pub struct DecisionIndex(pub usize);
 
 impl DecisionIndex {
@@ -0,0 +1,190 @@
+use super::belief::PublicBelief;
+use super::decision::{Probability, Utility};
+use super::hidden_index::HiddenIndex;
+use super::reveal_index::RevealIndex;
+use super::sequence_form::{SequenceForm, SequenceFormSolution};
+use crate::game::rules::RuleSet;
+use crate::game::types::Player;
+
+// {{{ Endgame solving
+/// Something which can solve the final turn of the game,
+/// such as [EndgameSolver] or [LastTurnSolver](super::last_turn::LastTurnSolver).
+pub trait SolveEndgame {
+    /// Solves the remainder of the game, given a belief over the hidden
+    /// information of both players.
+    ///
+    /// Returns `None` unless the belief refers to a state
+    /// on the last battlefield (in any phase).
+    fn solve(&self, belief: &PublicBelief) -> Option<Endgame>;
+}
+// }}}
+// {{{ Endgame solver
+/// Solves the final turn of the game (the main, sabotage and seer phases
+/// fought over the last battlefield) to equilibrium.
+///
+/// The remaining game is small enough to be converted to sequence form
+/// in its entirety, and solved using CFR+ in a matter of milliseconds.
+#[derive(Debug, Clone, Copy)]
+pub struct EndgameSolver {
+    rules: RuleSet,
+    /// The solver stops once the strategies are at most this exploitable.
+    pub tolerance: Utility,
+    pub max_iterations: usize,
+}
+
+impl EndgameSolver {
+    pub fn new(rules: RuleSet) -> Self {
+        Self {
+            rules,
+            tolerance: 1e-3,
+            max_iterations: 10_000,
+        }
+    }
+}
+
+impl SolveEndgame for EndgameSolver {
+    fn solve(&self, belief: &PublicBelief) -> Option<Endgame> {
+        let state = belief.state();
+        if !state.battlefields.is_last() {
+            return None;
+        }
+
+        let game = SequenceForm::new(state, belief.phase(), &belief.deals(), self.rules);
+        let solution = game.solve_cfr_plus(self.tolerance, self.max_iterations);
+
+        Some(Endgame { game, solution })
+    }
+}
+// }}}
+// {{{ Endgame
+/// A solved endgame, which can be queried for the strategy
+/// of either player after any sequence of reveals.
+#[derive(Debug, Clone)]
+pub struct Endgame {
+    game: SequenceForm,
+    solution: SequenceFormSolution,
+}
+
+impl Endgame {
+    pub fn new(game: SequenceForm, solution: SequenceFormSolution) -> Self {
+        Self { game, solution }
+    }
+
+    /// The expected utility of the endgame, from the perspective of [Player::Me].
+    #[inline(always)]
+    pub fn value(&self) -> f64 {
+        self.solution.value
+    }
+
+    /// How far the computed strategies are from an actual equilibrium.
+    pub fn exploitability(&self) -> Utility {
+        self.game.exploitability(&self.solution.plans)
+    }
+
+    /// Returns the probability a player should take each decision with,
+    /// after the given reveals (counted from the state the endgame was solved
+    /// from), knowing their hidden index at that point.
+    ///
+    /// Returns `None` if the player has a single decision to choose
+    /// from, or if the given point cannot be reached under the belief
+    /// the endgame was solved for.
+    pub fn strategy(
+        &self,
+        player: Player,
+        path: &[RevealIndex],
+        hidden: HiddenIndex,
+    ) -> Option<Vec<Probability>> {
+        let infoset = self.game.find_infoset(player, path, hidden)?;
+
+        Some(self.solution.strategy(player, infoset))
+    }
+}
+// }}}
+// {{{ Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cfr::belief::UniformStrategy;
+    use crate::cfr::decision_index::DecisionIndex;
+    use crate::cfr::hidden_index::HiddenState;
+    use crate::cfr::phase::{MainPhase, PerPhase, SomePhase};
+    use crate::game::known_state::KnownState;
+    use crate::game::known_state_summary::KnownStateEssentials;
+    use crate::game::notation::Position;
+
+    fn root(position: &str) -> (KnownState, SomePhase) {
+        let state = Position::parse(position).unwrap().state;
+        (state, PerPhase::Main(MainPhase::new()))
+    }
+
+    #[test]
+    fn agrees_with_the_linear_program() {
+        let (state, phase) = root("PPPL 3 +0 WSRBDG RS/RS S/-");
+        let endgame = EndgameSolver::new(RuleSet::default())
+            .solve(&PublicBelief::new(state, phase))
+            .unwrap();
+        let exact = SequenceForm::uniform(state, phase, RuleSet::default())
+            .solve()
+            .unwrap();
+
+        assert!(endgame.exploitability() <= 1e-3);
+        assert!((endgame.value() - exact.value).abs() < 1e-2);
+    }
+
+    #[test]
+    fn earlier_battlefields_are_not_solved() {
+        let (state, phase) = root("PPPL 2 +0 WSRB RSG/RSG S/-");
+        let belief = PublicBelief::new(state, phase);
+
+        assert!(EndgameSolver::new(RuleSet::default())
+            .solve(&belief)
+            .is_none());
+    }
+
+    #[test]
+    fn solves_entire_final_turns() {
+        let (state, phase) = root("PPPL 3 +0 WSRBDG RS/RS S/-");
+        let belief = PublicBelief::new(state, phase);
+        let endgame = EndgameSolver::new(RuleSet::default())
+            .solve(&belief)
+            .unwrap();
+
+        assert!(endgame.exploitability() <= 1e-3);
+
+        // Every hand we might hold leads to a decision
+        let summary = state.to_summary();
+        for (hidden, _) in belief.marginal(Player::Me) {
+            let index = HiddenIndex::encode(&summary, Player::Me, hidden.to_encoding_info(None));
+            let strategy = endgame.strategy(Player::Me, &[], index).unwrap();
+            let total: Probability = strategy.iter().sum();
+
+            assert_eq!(strategy.len(), phase.decision_counts(&state)[0]);
+            assert!((total - 1.0).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn later_phases_can_be_solved() {
+        let (state, phase) = root("PPPL 3 +0 WSRBDG RS/RS S/-");
+        let belief = PublicBelief::new(state, phase);
+
+        // Reveal the plays of some deal, which leaves
+        // us with a non-uniform belief in the next phase.
+        let deal = phase.valid_hidden_states(state.to_summary())[0];
+        let hidden = deal.map(HiddenState::from_encoding_info);
+        let decisions = [DecisionIndex(0), DecisionIndex(0)];
+        let (reveal_index, _) = phase
+            .advance(state, hidden, decisions, RuleSet::default())
+            .unwrap();
+        let next = belief
+            .advance(reveal_index, &UniformStrategy, RuleSet::default())
+            .unwrap()
+            .get_unfinished()
+            .unwrap();
+
+        let endgame = EndgameSolver::new(RuleSet::default()).solve(&next).unwrap();
+        assert!(endgame.exploitability() <= 1e-3);
+        assert!(endgame.value().abs() <= 1.0);
+    }
+}
+// }}}
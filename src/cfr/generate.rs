@@ -1,8 +1,9 @@
 use super::decision::{DecisionMatrices, ExploredScope, Scope, UnexploredScope};
-use super::phase::{MainPhase, Phase, PhaseStats, PhaseTag};
+use super::phase::{MainPhase, PerPhase, Phase, PhaseStats, PhaseTag, SomePhase};
 use super::reveal_index::RevealIndex;
 use crate::game::known_state::KnownState;
 use crate::game::known_state_summary::KnownStateEssentials;
+use crate::game::rules::RuleSet;
 use crate::game::simulate::BattleContext;
 use crate::game::types::TurnResult;
 use bumpalo::Bump;
@@ -80,15 +81,17 @@ impl Debug for GenerationStats {
 pub struct GenerationContext<'a> {
     turns: usize,
     state: KnownState,
+    rules: RuleSet,
     allocator: &'a Bump,
 }
 
 impl<'a> GenerationContext<'a> {
     // {{{ Helpers
-    pub fn new(turns: usize, state: KnownState, allocator: &'a Bump) -> Self {
+    pub fn new(turns: usize, state: KnownState, rules: RuleSet, allocator: &'a Bump) -> Self {
         Self {
             turns,
             state,
+            rules,
             allocator,
         }
     }
@@ -100,6 +103,27 @@ impl<'a> GenerationContext<'a> {
             None,
         )
     }
+
+    /// Similar to `generate`, but starts from an arbitrary phase.
+    pub fn generate_from(&self, phase: SomePhase) -> Scope<'a> {
+        match phase {
+            PerPhase::Main(phase) => self.generate_generic(
+                phase,
+                #[cfg(debug_assertions)]
+                None,
+            ),
+            PerPhase::Sabotage(phase) => self.generate_generic(
+                phase,
+                #[cfg(debug_assertions)]
+                None,
+            ),
+            PerPhase::Seer(phase) => self.generate_generic(
+                phase,
+                #[cfg(debug_assertions)]
+                None,
+            ),
+        }
+    }
     // }}}
     // {{{ Generic generation
     fn generate_generic<P: Phase>(
@@ -125,7 +149,7 @@ impl<'a> GenerationContext<'a> {
             .allocator
             .alloc_slice_fill_with(phase.reveal_count(&self.state), |index| {
                 let reveal_index = RevealIndex(index);
-                let advanced = phase.advance_state(&self.state, reveal_index, true);
+                let advanced = phase.advance_state(&self.state, reveal_index, self.rules);
 
                 match advanced {
                     TurnResult::Finished(score) => Scope::Completed(score),
@@ -133,6 +157,7 @@ impl<'a> GenerationContext<'a> {
                         let new_self = Self::new(
                             self.turns - P::ADVANCES_TURN as usize,
                             new_state,
+                            self.rules,
                             self.allocator,
                         );
 
@@ -141,7 +166,7 @@ impl<'a> GenerationContext<'a> {
                         new_self.generate_generic::<P::Next>(
                             next,
                             #[cfg(debug_assertions)]
-                            phase.battle_context(&self.state, reveal_index, false),
+                            phase.battle_context(&self.state, reveal_index, self.rules),
                         )
                     }
                 }
@@ -164,12 +189,17 @@ impl<'a> GenerationContext<'a> {
 pub struct EstimationContext {
     turns: usize,
     state: KnownState,
+    rules: RuleSet,
 }
 
 impl EstimationContext {
     // {{{ Helpers
-    pub fn new(turns: usize, state: KnownState) -> Self {
-        Self { turns, state }
+    pub fn new(turns: usize, state: KnownState, rules: RuleSet) -> Self {
+        Self {
+            turns,
+            state,
+            rules,
+        }
     }
 
     pub fn estimate(&self) -> GenerationStats {
@@ -202,7 +232,7 @@ impl EstimationContext {
         let (slice_memory_estimate, mut stats) =
             Self::estimate_slice_alloc(reveal_count, |index| {
                 let reveal_index = RevealIndex(index);
-                let advanced = phase.advance_state(&self.state, reveal_index, true);
+                let advanced = phase.advance_state(&self.state, reveal_index, self.rules);
 
                 match advanced {
                     TurnResult::Finished(_) => {
@@ -211,7 +241,11 @@ impl EstimationContext {
                         stats
                     }
                     TurnResult::Unfinished(new_state) => {
-                        let new_self = Self::new(self.turns - P::ADVANCES_TURN as usize, new_state);
+                        let new_self = Self::new(
+                            self.turns - P::ADVANCES_TURN as usize,
+                            new_state,
+                            self.rules,
+                        );
                         let next = phase.advance_phase(&self.state, reveal_index).unwrap();
 
                         new_self.estimate_generic::<P::Next>(next)
@@ -6,6 +6,7 @@ use crate::helpers::bitfield::const_size_codec::ConstSizeCodec;
 use crate::helpers::bitfield::Bitfield;
 use crate::helpers::choose::choose;
 use crate::helpers::ranged::MixRanged;
+use serde::{Deserialize, Serialize};
 use std::assert_eq;
 
 // {{{ PerPhaseInfo
@@ -13,7 +14,7 @@ use std::assert_eq;
 /// - a `A` if `phase >= main`
 /// - a `B` if `phase >= sabotage`
 /// - a `C` if `phase >= seer`
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum PerPhaseInfo<A, B, C> {
     Main(A),
     Sabotage(A, B),
@@ -117,7 +118,7 @@ pub type DecodingInfo = PerPhaseInfo<(), (), Creature>;
 /// Hidden info known by a player:
 /// - The creatures in hand
 /// - The creatures chosen this turn
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub struct HiddenState {
     pub hand: CreatureSet,
     pub choice: Option<CreatureSet>,
@@ -149,7 +150,17 @@ impl HiddenState {
             (None, None) => EncodingInfo::Main(self.hand),
             (Some(choice), None) => EncodingInfo::Sabotage(self.hand, choice),
             (Some(choice), Some(revealed)) => EncodingInfo::Seer(self.hand, choice, revealed),
-            (_, _) => panic!("Impossible state")
+            (_, _) => panic!("Impossible state"),
+        }
+    }
+
+    /// Similar to `to_encoding_info`, but returns `None` instead of panicking
+    /// when a creature has been revealed, yet the choice is unknown.
+    #[inline(always)]
+    pub fn try_to_encoding_info(self, revealed: Option<Creature>) -> Option<EncodingInfo> {
+        match (self.choice, revealed) {
+            (None, Some(_)) => None,
+            _ => Some(self.to_encoding_info(revealed)),
         }
     }
 }
@@ -157,7 +168,7 @@ impl HiddenState {
 // }}}
 // {{{ HiddenIndex
 /// Encodes all hidden information known by a player.
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Copy, Clone)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Copy, Clone)]
 pub struct HiddenIndex(pub(super) usize);
 
 impl HiddenIndex {
@@ -205,6 +216,38 @@ impl HiddenIndex {
         }
     }
 
+    /// Similar to `encode`, but returns `None` (instead of panicking or returning
+    /// garbage) when the info cannot be known by the player in the given state.
+    pub fn try_encode<S: KnownStateEssentials>(
+        state: &S,
+        player: Player,
+        info: EncodingInfo,
+    ) -> Option<Self> {
+        let hand = info.get_main();
+
+        if hand.len() != state.hand_size() || !hand.is_disjoint_from(state.graveyard()) {
+            return None;
+        }
+
+        if let Some((choice, revealed)) = info.get_post_main() {
+            let choice_size = state.creature_choice_size(player);
+            let valid = choice.is_subset_of(hand)
+                && match revealed {
+                    Some(revealed) if player != state.last_creature_revealer() => {
+                        choice == CreatureSet::singleton(revealed)
+                    }
+                    Some(revealed) => !hand.has(revealed) && choice.len() == choice_size,
+                    None => choice.len() == choice_size,
+                };
+
+            if !valid {
+                return None;
+            }
+        }
+
+        Some(Self::encode(state, player, info))
+    }
+
     pub fn decode<S: KnownStateEssentials>(
         self,
         state: &S,
@@ -415,5 +458,89 @@ mod tests {
         }
     }
     // }}}
+    // {{{ Validation
+    #[test]
+    fn try_encode_rejects_impossible_info() {
+        let graveyard =
+            CreatureSet::singleton(Creature::Wall) | CreatureSet::singleton(Creature::Seer);
+        let state = KnownStateSummary::new_all_edicts(graveyard, Some(Player::You));
+        let hand = [
+            Creature::Rogue,
+            Creature::Bard,
+            Creature::Diplomat,
+            Creature::Ranger,
+        ]
+        .into_iter()
+        .map(CreatureSet::singleton)
+        .fold(CreatureSet::default(), |set, creature| set | creature);
+        let choice = CreatureSet::singleton(Creature::Rogue);
+
+        let try_encode = |player, info| HiddenIndex::try_encode(&state, player, info);
+
+        assert_eq!(
+            try_encode(Player::Me, PerPhaseInfo::Main(hand)),
+            Some(HiddenIndex::encode(
+                &state,
+                Player::Me,
+                PerPhaseInfo::Main(hand)
+            ))
+        );
+
+        // Wrong hand sizes, or creatures which are already in the graveyard
+        assert_eq!(
+            try_encode(Player::Me, PerPhaseInfo::Main(hand - Creature::Bard)),
+            None
+        );
+        assert_eq!(
+            try_encode(
+                Player::Me,
+                PerPhaseInfo::Main((hand - Creature::Bard) | graveyard)
+            ),
+            None
+        );
+
+        // Choices must be part of the hand, and have the right size
+        assert!(try_encode(Player::Me, PerPhaseInfo::Sabotage(hand, choice)).is_some());
+        assert_eq!(
+            try_encode(Player::You, PerPhaseInfo::Sabotage(hand, choice)),
+            None
+        );
+        assert_eq!(
+            try_encode(
+                Player::Me,
+                PerPhaseInfo::Sabotage(hand, CreatureSet::singleton(Creature::Steward))
+            ),
+            None
+        );
+
+        // Only the revealer's opponent knows the revealed creature is in their hand
+        let revealed = Creature::Steward;
+        assert!(try_encode(
+            Player::Me,
+            PerPhaseInfo::Seer(hand, choice, Creature::Rogue)
+        )
+        .is_some());
+        assert_eq!(
+            try_encode(Player::Me, PerPhaseInfo::Seer(hand, choice, revealed)),
+            None
+        );
+        assert!(try_encode(
+            Player::You,
+            PerPhaseInfo::Seer(hand, hand - Creature::Bard - Creature::Diplomat, revealed)
+        )
+        .is_some());
+        assert_eq!(
+            try_encode(
+                Player::You,
+                PerPhaseInfo::Seer(
+                    hand,
+                    hand - Creature::Bard - Creature::Diplomat,
+                    Creature::Rogue
+                )
+            ),
+            None
+        );
+    }
+    // }}}
 }
 // }}}
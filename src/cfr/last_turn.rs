@@ -0,0 +1,200 @@
+use super::belief::PublicBelief;
+use super::decision_index::DecisionIndex;
+use super::endgame::{Endgame, EndgameSolver, SolveEndgame};
+use super::hidden_index::{EncodingInfo, HiddenState};
+use super::phase::SomePhase;
+use super::sequence_form::SequenceForm;
+use crate::game::known_state::KnownState;
+use crate::game::rules::RuleSet;
+use crate::game::types::TurnResult;
+use crate::helpers::matrix_game::{MatrixGame, MatrixGameSolution};
+use crate::helpers::pair::Pair;
+
+// {{{ Last turn solver
+/// Solves positions on the last battlefield exactly.
+///
+/// Once the hands of both players are known, each phase is a simultaneous-move
+/// matrix game. The payoffs of the seer phase come straight from resolving
+/// the battle, while the payoffs of the earlier phases are the values of the
+/// games played during the next phase. These are solved one node at a time.
+///
+/// When the hands are hidden, the matrix games of different nodes are tied
+/// together by what each player knows, so the entire turn gets converted to
+/// sequence form and solved by linear programming instead. Unlike
+/// [EndgameSolver], this yields an actual equilibrium rather than an
+/// approximation of one.
+#[derive(Debug, Clone, Copy)]
+pub struct LastTurnSolver {
+    rules: RuleSet,
+}
+
+impl LastTurnSolver {
+    pub fn new(rules: RuleSet) -> Self {
+        Self { rules }
+    }
+
+    /// Computes the payoff matrix of the current phase. Rows correspond
+    /// to the decisions of [Player::Me], and columns to the decisions
+    /// of [Player::You]. Payoffs are given from the perspective of the former.
+    pub fn payoff_matrix(
+        &self,
+        state: &KnownState,
+        phase: SomePhase,
+        hidden: Pair<EncodingInfo>,
+    ) -> MatrixGame {
+        assert!(
+            state.battlefields.is_last(),
+            "Only positions on the last battlefield can be solved"
+        );
+
+        let [rows, columns] = phase.decision_counts(state);
+        let hidden_states = hidden.map(HiddenState::from_encoding_info);
+
+        MatrixGame::from_fn(rows, columns, |row, column| {
+            let decisions = [DecisionIndex(row), DecisionIndex(column)];
+            let (_, result) = phase
+                .advance(*state, hidden_states, decisions, self.rules)
+                .expect("Decisions should always be valid");
+
+            match result {
+                TurnResult::Finished(score) => score.to_utility() as f64,
+                TurnResult::Unfinished((next_state, next_hidden, next_phase)) => {
+                    self.solve_node(&next_state, next_phase, next_hidden).value
+                }
+            }
+        })
+    }
+
+    /// Solves the matrix game of the current phase.
+    pub fn solve_node(
+        &self,
+        state: &KnownState,
+        phase: SomePhase,
+        hidden: Pair<EncodingInfo>,
+    ) -> MatrixGameSolution {
+        self.payoff_matrix(state, phase, hidden).solve()
+    }
+}
+
+impl SolveEndgame for LastTurnSolver {
+    /// Falls back to CFR+ in the unlikely case the
+    /// linear program runs into numerical issues.
+    fn solve(&self, belief: &PublicBelief) -> Option<Endgame> {
+        let state = belief.state();
+        if !state.battlefields.is_last() {
+            return None;
+        }
+
+        let game = SequenceForm::new(state, belief.phase(), &belief.deals(), self.rules);
+        let solution = match game.solve() {
+            Ok(solution) => solution,
+            Err(_) => {
+                let fallback = EndgameSolver::new(self.rules);
+                game.solve_cfr_plus(fallback.tolerance, fallback.max_iterations)
+            }
+        };
+
+        Some(Endgame::new(game, solution))
+    }
+}
+// }}}
+// {{{ Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cfr::decision::Probability;
+    use crate::cfr::hidden_index::HiddenIndex;
+    use crate::cfr::phase::{MainPhase, PerPhase};
+    use crate::game::known_state_summary::KnownStateEssentials;
+    use crate::game::notation::Position;
+    use crate::game::types::Player;
+
+    fn root() -> (KnownState, SomePhase) {
+        let state = Position::parse("PPPL 3 +0 WSRBDG RS/RS S/-").unwrap().state;
+        (state, PerPhase::Main(MainPhase::new()))
+    }
+
+    #[test]
+    fn agrees_with_the_sequence_form_once_choices_are_made() {
+        let (state, phase) = root();
+        let solver = LastTurnSolver::new(RuleSet::default());
+        let deal = phase.valid_hidden_states(state.to_summary())[0];
+        let hidden = deal.map(HiddenState::from_encoding_info);
+        let [rows, columns] = phase.decision_counts(&state);
+
+        for row in 0..rows {
+            for column in 0..columns {
+                let decisions = [DecisionIndex(row), DecisionIndex(column)];
+                let (_, result) = phase
+                    .advance(state, hidden, decisions, RuleSet::default())
+                    .unwrap();
+                let (state, hidden, phase) = result.get_unfinished().unwrap();
+
+                // With a single deal, neither player has anything to hide
+                let node = solver.solve_node(&state, phase, hidden);
+                let game = SequenceForm::new(state, phase, &[(hidden, 1.0)], RuleSet::default());
+                let exact = game.solve().unwrap();
+
+                assert!((node.value - exact.value).abs() < 1e-6);
+            }
+        }
+    }
+
+    #[test]
+    fn solves_the_last_turn_exactly() {
+        let (state, phase) = root();
+        let belief = PublicBelief::new(state, phase);
+        let endgame = LastTurnSolver::new(RuleSet::default())
+            .solve(&belief)
+            .unwrap();
+
+        assert!(endgame.exploitability() < 1e-6);
+
+        // Every hand we might hold leads to a decision
+        let summary = state.to_summary();
+        for (hidden, _) in belief.marginal(Player::Me) {
+            let index = HiddenIndex::encode(&summary, Player::Me, hidden.to_encoding_info(None));
+            let strategy = endgame.strategy(Player::Me, &[], index).unwrap();
+            let total: Probability = strategy.iter().sum();
+
+            assert_eq!(strategy.len(), phase.decision_counts(&state)[0]);
+            assert!((total - 1.0).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn earlier_battlefields_are_not_solved() {
+        let state = Position::parse("PPPL 2 +0 WSRB RSG/RSG S/-").unwrap().state;
+        let belief = PublicBelief::new(state, PerPhase::Main(MainPhase::new()));
+
+        assert!(LastTurnSolver::new(RuleSet::default())
+            .solve(&belief)
+            .is_none());
+    }
+
+    #[test]
+    fn does_not_overestimate_hidden_hands() {
+        let (state, phase) = root();
+        let solver = LastTurnSolver::new(RuleSet::default());
+        let deals = phase.valid_hidden_states(state.to_summary());
+
+        let relaxed = deals
+            .iter()
+            .map(|deal| solver.solve_node(&state, phase, *deal).value)
+            .sum::<f64>()
+            / deals.len() as f64;
+        let exact = solver
+            .solve(&PublicBelief::new(state, phase))
+            .unwrap()
+            .value();
+
+        // Solving each node on its own lets both players see the hand of
+        // their opponent, which makes the seer worth a lot more
+        assert!((exact - 1.0 / 3.0).abs() < 1e-4, "Exact value is {exact}");
+        assert!(
+            (relaxed - 8.0 / 15.0).abs() < 1e-4,
+            "Relaxed value is {relaxed}"
+        );
+    }
+}
+// }}}
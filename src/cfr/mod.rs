@@ -1,3 +1,4 @@
+pub mod belief;
 pub mod hidden_index;
 pub mod decision_index;
 pub mod reveal_index;
@@ -5,3 +6,7 @@ pub mod decision;
 pub mod phase;
 pub mod generate;
 pub mod train;
+pub mod scope_cursor;
+pub mod sequence_form;
+pub mod last_turn;
+pub mod endgame;
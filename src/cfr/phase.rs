@@ -6,6 +6,7 @@ use crate::game::creature::{Creature, CreatureSet};
 use crate::game::edict::Edict;
 use crate::game::known_state::KnownState;
 use crate::game::known_state_summary::{KnownStateEssentials, KnownStateSummary};
+use crate::game::rules::RuleSet;
 use crate::game::simulate::BattleContext;
 use crate::game::types::{Player, TurnResult};
 use crate::helpers::bitfield::Bitfield;
@@ -15,12 +16,13 @@ use crate::helpers::try_from_iter::TryCollect;
 use derive_more::{Add, AddAssign, Sum};
 use indicatif::HumanBytes;
 use itertools::Itertools;
+use serde::{Deserialize, Serialize};
 use std::fmt::Debug;
 use std::format;
 use std::mem::size_of;
 
 // {{{ Phase tags
-#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Serialize, Deserialize)]
 pub enum PhaseTag {
     Main,
     Sabotage,
@@ -99,12 +101,12 @@ pub trait Phase: Sync + Sized {
     ///
     /// * `state` - Self explainatory.
     /// * `reveal_index` - Precomputed value for the data revealed in this state.
-    /// * `hoeless-surrenders` - See the docs for `BattleContext` to see what this does.
+    /// * `rules` - The rules the game is played under.
     fn advance_state(
         &self,
         state: &KnownState,
         reveal_index: RevealIndex,
-        hopeless_surrenders: bool,
+        rules: RuleSet,
     ) -> TurnResult<KnownState>;
 
     /// Preapres a context for a battle to take place in this state.
@@ -113,12 +115,12 @@ pub trait Phase: Sync + Sized {
     ///
     /// * `state` - Self explainatory.
     /// * `reveal_index` - Precomputed value for the data revealed in this state.
-    /// * `hoeless-surrenders` - See the docs for `BattleContext` to see what this does.
+    /// * `rules` - The rules the game is played under.
     fn battle_context(
         &self,
         _state: &KnownState,
         _reveal_index: RevealIndex,
-        _hopeless_surrenders: bool,
+        _rules: RuleSet,
     ) -> Option<BattleContext> {
         None
     }
@@ -156,7 +158,7 @@ pub trait Phase: Sync + Sized {
 // }}}
 // {{{ Phase instances
 // {{{ Main phase
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct MainPhase;
 
 impl MainPhase {
@@ -205,7 +207,7 @@ impl Phase for MainPhase {
         &self,
         state: &KnownState,
         _reveal_index: RevealIndex,
-        _hopeless_surrenders: bool,
+        _rules: RuleSet,
     ) -> TurnResult<KnownState> {
         // Sanity check
         for player in Player::PLAYERS {
@@ -265,7 +267,7 @@ impl Phase for MainPhase {
 }
 // }}}
 // {{{ Sabotage phase
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct SabotagePhase {
     pub edict_choices: Pair<Edict>,
 }
@@ -333,7 +335,7 @@ impl Phase for SabotagePhase {
         &self,
         state: &KnownState,
         _reveal_index: RevealIndex,
-        _hopeless_surrenders: bool,
+        _rules: RuleSet,
     ) -> TurnResult<KnownState> {
         TurnResult::Unfinished(*state)
     }
@@ -404,7 +406,7 @@ impl Phase for SabotagePhase {
 }
 // }}}
 // {{{ Seer phase
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct SeerPhase {
     pub edict_choices: Pair<Edict>,
     pub sabotage_choices: Pair<SabotagePhaseChoice>,
@@ -453,7 +455,7 @@ impl Phase for SeerPhase {
         &self,
         state: &KnownState,
         reveal_index: RevealIndex,
-        hopeless_surrenders: bool,
+        rules: RuleSet,
     ) -> Option<BattleContext> {
         let seer_player_creature = reveal_index
             .decode_seer_phase_reveal(state.graveyard, self.revealed_creature)
@@ -468,12 +470,7 @@ impl Phase for SeerPhase {
             .attempt_collect()
             .unwrap();
 
-        let context = BattleContext::new(
-            main_choices,
-            self.sabotage_choices,
-            *state,
-            hopeless_surrenders,
-        );
+        let context = BattleContext::new(main_choices, self.sabotage_choices, *state, rules);
 
         Some(context)
     }
@@ -483,10 +480,10 @@ impl Phase for SeerPhase {
         &self,
         state: &KnownState,
         reveal_index: RevealIndex,
-        hopeless_surrenders: bool,
+        rules: RuleSet,
     ) -> TurnResult<KnownState> {
         match self
-            .battle_context(state, reveal_index, hopeless_surrenders)
+            .battle_context(state, reveal_index, rules)
             .unwrap()
             .advance_known_state()
             .1
@@ -716,7 +713,7 @@ mod tests {
 }
 // }}}
 // {{{ Some phase
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum PerPhase<Main, Sabotage, Seer> {
     Main(Main),
     Sabotage(Sabotage),
@@ -776,7 +773,7 @@ impl SomePhase {
         state: KnownState,
         hidden: Pair<hidden_index::HiddenState>,
         decisions: Pair<DecisionIndex>,
-        hopeless_surrenders: bool,
+        rules: RuleSet,
     ) -> Option<(
         RevealIndex,
         TurnResult<(KnownState, Pair<hidden_index::EncodingInfo>, Self)>,
@@ -788,7 +785,7 @@ impl SomePhase {
         let advanced_state = per_phase!(self, |inner| inner.advance_state(
             &state,
             reveal_index,
-            hopeless_surrenders
+            rules
         ));
 
         let next_phase: Self = self.advance_phase(&state, reveal_index)?;
@@ -807,6 +804,57 @@ impl SomePhase {
         per_phase!(self, |inner| inner.decision_counts(state))
     }
 
+    /// Similar to calling the method with the same name on the inner phase object.
+    #[inline(always)]
+    pub fn hidden_counts<S: KnownStateEssentials>(&self, state: &S) -> Pair<usize> {
+        per_phase!(self, |inner| inner.hidden_counts(state))
+    }
+
+    /// Similar to calling the method with the same name on the inner phase object.
+    #[inline(always)]
+    pub fn reveal_count(&self, state: &KnownState) -> usize {
+        per_phase!(self, |inner| inner.reveal_count(state))
+    }
+
+    /// Similar to calling the method with the same name on the inner phase object.
+    #[inline(always)]
+    pub fn advance_state(
+        &self,
+        state: &KnownState,
+        reveal_index: RevealIndex,
+        rules: RuleSet,
+    ) -> TurnResult<KnownState> {
+        per_phase!(self, |inner| inner.advance_state(
+            state,
+            reveal_index,
+            rules
+        ))
+    }
+
+    /// Similar to calling the method with the same name on the inner phase object.
+    #[inline(always)]
+    pub fn advance_hidden_indices(
+        &self,
+        state: KnownStateSummary,
+        hidden: Pair<hidden_index::HiddenState>,
+        decisions: Pair<DecisionIndex>,
+    ) -> Option<(
+        KnownStateSummary,
+        Pair<hidden_index::EncodingInfo>,
+        RevealIndex,
+    )> {
+        per_phase!(self, |inner| inner
+            .advance_hidden_indices(state, hidden, decisions))
+    }
+
+    /// Collects the result of calling `valid_hidden_states` on the inner phase object.
+    pub fn valid_hidden_states(
+        &self,
+        state: KnownStateSummary,
+    ) -> Vec<Pair<hidden_index::EncodingInfo>> {
+        per_phase!(self, |inner| inner.valid_hidden_states(state).collect())
+    }
+
     /// Returns `true` if the given player has played the sabotage edict
     /// this turn.
     ///
@@ -7,9 +7,10 @@ use crate::game::types::Player;
 use crate::helpers::bitfield::Bitfield;
 use crate::helpers::pair::Pair;
 use crate::helpers::ranged::MixRanged;
+use serde::{Deserialize, Serialize};
 
 /// Encodes all the information revealed at the end of a phase.
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Copy, Clone)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Copy, Clone, Serialize, Deserialize)]
 pub struct RevealIndex(pub usize);
 
 impl RevealIndex {
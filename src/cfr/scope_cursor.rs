@@ -0,0 +1,197 @@
+use super::decision::{DecisionMatrices, DecisionMatrix, ExploredScope, Scope};
+use super::phase::SomePhase;
+use super::reveal_index::RevealIndex;
+use crate::game::known_state::KnownState;
+use crate::game::known_state_summary::{KnownStateEssentials, KnownStateSummary};
+use crate::game::rules::RuleSet;
+use crate::game::types::{Player, TurnResult};
+
+// {{{ Scope cursor
+/// Points to some node of a scope tree, keeping track of the phase and
+/// public state that node corresponds to.
+///
+/// The cursor keeps following the game once it leaves the explored part of
+/// the tree. In that case, there is no current scope anymore.
+#[derive(Clone, Copy)]
+pub struct ScopeCursor<'s, 'a> {
+    scope: Option<&'s Scope<'a>>,
+    state: KnownState,
+    phase: SomePhase,
+}
+
+impl<'s, 'a> ScopeCursor<'s, 'a> {
+    /// Creates a cursor pointing to a scope generated from the given state and phase.
+    pub fn new(scope: &'s Scope<'a>, state: KnownState, phase: SomePhase) -> Self {
+        Self {
+            scope: Some(scope),
+            state,
+            phase,
+        }
+    }
+
+    // {{{ Getters
+    /// The current scope, or `None` if the path taken by the game
+    /// is not part of the tree at all.
+    #[inline(always)]
+    pub fn scope(&self) -> Option<&'s Scope<'a>> {
+        self.scope
+    }
+
+    /// The current scope, if it has been explored.
+    #[inline(always)]
+    pub fn explored(&self) -> Option<&'s ExploredScope<'a>> {
+        self.scope?.get_explored()
+    }
+
+    /// Returns true if the cursor has not left the explored part of the tree.
+    #[inline(always)]
+    pub fn is_explored(&self) -> bool {
+        self.explored().is_some()
+    }
+
+    #[inline(always)]
+    pub fn state(&self) -> KnownState {
+        self.state
+    }
+
+    #[inline(always)]
+    pub fn phase(&self) -> SomePhase {
+        self.phase
+    }
+
+    #[inline(always)]
+    pub fn summary(&self) -> KnownStateSummary {
+        self.state.to_summary()
+    }
+
+    /// The decision matrices of both players at the current scope.
+    #[inline(always)]
+    pub fn matrices(&self) -> Option<&'s DecisionMatrices<'a>> {
+        Some(&self.explored()?.matrices)
+    }
+
+    /// The decision matrix of a single player at the current scope.
+    #[inline(always)]
+    pub fn matrix(&self, player: Player) -> Option<&'s DecisionMatrix<'a>> {
+        Some(self.matrices()?.get_matrix(player))
+    }
+    // }}}
+    // {{{ Movement
+    /// Moves the cursor along the info revealed at the end of the current phase.
+    ///
+    /// Returns `None` if the reveal index is invalid for the current phase.
+    /// Moving past the end of the tree (or out of an unexplored
+    /// scope) results in a cursor without a scope.
+    pub fn advance(&self, reveal_index: RevealIndex, rules: RuleSet) -> Option<TurnResult<Self>> {
+        let state = match self.phase.advance_state(&self.state, reveal_index, rules) {
+            TurnResult::Finished(score) => return Some(TurnResult::Finished(score)),
+            TurnResult::Unfinished(state) => state,
+        };
+
+        let phase = self.phase.advance_phase(&self.state, reveal_index)?;
+        let scope = self
+            .explored()
+            .and_then(|explored| explored.next.get(reveal_index.0));
+
+        Some(TurnResult::Unfinished(Self {
+            scope,
+            state,
+            phase,
+        }))
+    }
+
+    /// Iterates over the children of the current scope the game can continue in,
+    /// following the given rules (usually the ones the tree has been generated with).
+    pub fn children(&self, rules: RuleSet) -> impl Iterator<Item = (RevealIndex, Self)> + '_ {
+        let count = self.explored().map_or(0, |explored| explored.next.len());
+
+        (0..count).filter_map(move |index| {
+            let reveal_index = RevealIndex(index);
+            let child = self.advance(reveal_index, rules)?.get_unfinished()?;
+
+            Some((reveal_index, child))
+        })
+    }
+    // }}}
+}
+// }}}
+// {{{ Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cfr::phase::{MainPhase, PerPhase, PhaseTag};
+    use crate::cfr::train::fixtures::{generated_scope, rules};
+    use crate::game::notation::Position;
+    use bumpalo::Bump;
+
+    fn root_state() -> KnownState {
+        Position::parse("PPPL 2 +0 WSRB RSG/RSG -/-").unwrap().state
+    }
+
+    #[test]
+    fn follows_the_tree_until_the_horizon() {
+        let allocator = Bump::new();
+        let state = root_state();
+        let phase = PerPhase::Main(MainPhase::new());
+        let scope = generated_scope(&allocator, state);
+        let mut cursor = ScopeCursor::new(&scope, state, phase);
+
+        // Main, sabotage and seer phases are all part of the tree
+        for tag in [PhaseTag::Main, PhaseTag::Sabotage, PhaseTag::Seer] {
+            assert!(cursor.is_explored());
+            assert_eq!(cursor.phase().tag(), tag);
+            assert!(Player::PLAYERS
+                .into_iter()
+                .all(|player| cursor.matrix(player).is_some()));
+
+            let (_, child) = cursor.children(rules()).next().unwrap();
+            cursor = child;
+        }
+
+        // The next turn is past the horizon
+        assert_eq!(cursor.phase().tag(), PhaseTag::Main);
+        assert_eq!(cursor.state().battlefields.current, 3);
+        assert!(!cursor.is_explored());
+        assert!(matches!(cursor.scope(), Some(Scope::Unexplored(_))));
+        assert!(cursor.matrices().is_none());
+        assert_eq!(cursor.children(rules()).count(), 0);
+    }
+
+    #[test]
+    fn keeps_following_the_game_outside_the_tree() {
+        let allocator = Bump::new();
+        let state = root_state();
+        let phase = PerPhase::Main(MainPhase::new());
+        let scope = generated_scope(&allocator, state);
+        let cursor = ScopeCursor::new(&scope, state, phase);
+
+        let count = cursor.explored().unwrap().next.len();
+        let children: Vec<_> = cursor.children(rules()).collect();
+        assert_eq!(children.len(), count);
+
+        for (reveal_index, child) in children {
+            let expected = &cursor.explored().unwrap().next[reveal_index.0];
+            assert!(std::ptr::eq(child.scope().unwrap(), expected));
+            assert_eq!(child.summary(), child.state().to_summary());
+        }
+
+        // Walking an unexplored scope leaves the tree entirely,
+        // while still keeping track of the game
+        let mut outside = cursor;
+        for _ in 0..3 {
+            let (_, child) = outside.children(rules()).next().unwrap();
+            outside = child;
+        }
+
+        let outside = outside
+            .advance(RevealIndex(0), RuleSet::default())
+            .unwrap()
+            .get_unfinished()
+            .unwrap();
+
+        assert!(outside.scope().is_none());
+        assert_eq!(outside.phase().tag(), PhaseTag::Sabotage);
+        assert_eq!(outside.state().battlefields.current, 3);
+    }
+}
+// }}}
@@ -0,0 +1,627 @@
+use super::decision::{Probability, Scope, Utility};
+use super::decision_index::DecisionIndex;
+use super::hidden_index::{EncodingInfo, HiddenIndex, HiddenState};
+use super::phase::SomePhase;
+use super::reveal_index::RevealIndex;
+use super::scope_cursor::ScopeCursor;
+use crate::game::known_state::KnownState;
+use crate::game::known_state_summary::KnownStateEssentials;
+use crate::game::rules::RuleSet;
+use crate::game::types::{Player, TurnResult};
+use crate::helpers::pair::Pair;
+use crate::helpers::simplex::{LinearProgram, LpError, Relation};
+use std::collections::HashMap;
+
+// {{{ Infosets
+/// A point where a player has to take a decision, identified by the public
+/// reveals leading to it, and the hidden information known by the player.
+///
+/// Points where a player can only take a single decision
+/// carry no information, and are thus not infosets.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Infoset {
+    pub path: Vec<RevealIndex>,
+    pub hidden: HiddenIndex,
+    /// The sequence of decisions the player took before reaching this infoset.
+    pub parent: usize,
+    /// The sequence extending `parent` with the first decision of this infoset.
+    /// The sequences for the other decisions follow right after.
+    pub first: usize,
+    pub count: usize,
+}
+
+impl Infoset {
+    /// The sequence extending `parent` with the given decision.
+    #[inline(always)]
+    pub fn sequence(&self, decision: DecisionIndex) -> usize {
+        debug_assert!(decision.0 < self.count);
+        self.first + decision.0
+    }
+
+    /// The sequences available at this infoset, in decision order.
+    #[inline(always)]
+    pub fn sequences(&self) -> std::ops::Range<usize> {
+        self.first..self.first + self.count
+    }
+}
+
+/// The infosets of a single player. Sequence `0` is the empty sequence.
+#[derive(Debug, Clone)]
+struct PlayerInfosets {
+    infosets: Vec<Infoset>,
+    lookup: HashMap<(Vec<RevealIndex>, HiddenIndex), usize>,
+    sequence_count: usize,
+}
+
+impl PlayerInfosets {
+    fn new() -> Self {
+        Self {
+            infosets: Vec::new(),
+            lookup: HashMap::new(),
+            sequence_count: 1,
+        }
+    }
+
+    /// Returns the first sequence of the given infoset, creating it if needed.
+    fn get_or_insert(
+        &mut self,
+        path: &[RevealIndex],
+        hidden: HiddenIndex,
+        parent: usize,
+        count: usize,
+    ) -> usize {
+        let key = (path.to_vec(), hidden);
+
+        if let Some(&index) = self.lookup.get(&key) {
+            let infoset = &self.infosets[index];
+            assert_eq!(
+                infoset.parent, parent,
+                "Infoset {key:?} can be reached through different sequences"
+            );
+
+            return infoset.first;
+        }
+
+        let first = self.sequence_count;
+        self.sequence_count += count;
+        self.lookup.insert(key.clone(), self.infosets.len());
+        self.infosets.push(Infoset {
+            path: key.0,
+            hidden: key.1,
+            parent,
+            first,
+            count,
+        });
+
+        first
+    }
+}
+// }}}
+// {{{ Sequence form
+/// The sequence form of a (small) subgame. The size of this representation
+/// is linear in the size of the game tree, which makes it possible to
+/// solve the subgame exactly using linear programming (or to quickly
+/// approximate a solution using CFR+).
+///
+/// Utilities are always given from the perspective of [Player::Me].
+#[derive(Debug, Clone)]
+pub struct SequenceForm {
+    state: KnownState,
+    phase: SomePhase,
+    rules: RuleSet,
+    players: Pair<PlayerInfosets>,
+    /// Sparse payoff matrix, indexed by pairs of sequences,
+    /// with chance probabilities already factored in.
+    payoffs: Vec<(Pair<usize>, f64)>,
+}
+
+impl SequenceForm {
+    // {{{ Constructors
+    /// Builds the sequence form of the subgame starting at the given state
+    /// and phase, and lasting until the end of the game.
+    ///
+    /// # Arguments
+    ///
+    /// * `deals` - The possible hidden information of both players at the
+    ///   start of the subgame, together with their probabilities.
+    pub fn new(
+        state: KnownState,
+        phase: SomePhase,
+        deals: &[(Pair<EncodingInfo>, Probability)],
+        rules: RuleSet,
+    ) -> Self {
+        let mut builder = Builder {
+            rules,
+            players: [PlayerInfosets::new(), PlayerInfosets::new()],
+            payoffs: HashMap::new(),
+            path: Vec::new(),
+        };
+
+        for &(hidden, probability) in deals {
+            if probability > 0.0 {
+                builder.walk(state, phase, hidden, [0, 0], probability as f64);
+            }
+        }
+
+        let mut payoffs: Vec<_> = builder.payoffs.into_iter().collect();
+        payoffs.sort_unstable_by_key(|(sequences, _)| *sequences);
+
+        Self {
+            state,
+            phase,
+            rules,
+            players: builder.players,
+            payoffs,
+        }
+    }
+
+    /// Similar to `new`, but every valid combination of hidden
+    /// information is assumed to be equally likely (just like during training).
+    pub fn uniform(state: KnownState, phase: SomePhase, rules: RuleSet) -> Self {
+        let deals = phase.valid_hidden_states(state.to_summary());
+        let probability = 1.0 / deals.len() as Probability;
+        let deals: Vec<_> = deals
+            .into_iter()
+            .map(|hidden| (hidden, probability))
+            .collect();
+
+        Self::new(state, phase, &deals, rules)
+    }
+    // }}}
+    // {{{ Getters
+    /// The infosets of a player. Parents always come before their children.
+    #[inline(always)]
+    pub fn infosets(&self, player: Player) -> &[Infoset] {
+        &player.select_ref(&self.players).infosets
+    }
+
+    /// Number of sequences of a player, including the empty one.
+    #[inline(always)]
+    pub fn sequence_count(&self, player: Player) -> usize {
+        player.select_ref(&self.players).sequence_count
+    }
+
+    /// Looks up the infoset a player is in after the given reveals.
+    pub fn find_infoset(
+        &self,
+        player: Player,
+        path: &[RevealIndex],
+        hidden: HiddenIndex,
+    ) -> Option<&Infoset> {
+        let infosets = player.select_ref(&self.players);
+        let index = *infosets.lookup.get(&(path.to_vec(), hidden))?;
+
+        Some(&infosets.infosets[index])
+    }
+
+    /// Iterates over the payoff matrix as seen by the given player,
+    /// yielding `(own sequence, opponent sequence, payoff)` triples.
+    fn payoffs_for(&self, player: Player) -> impl Iterator<Item = (usize, usize, f64)> + '_ {
+        self.payoffs
+            .iter()
+            .map(move |&(sequences, payoff)| match player {
+                Player::Me => (sequences[0], sequences[1], payoff),
+                Player::You => (sequences[1], sequences[0], -payoff),
+            })
+    }
+    // }}}
+    // {{{ Solving
+    /// Computes an optimal realization plan for the given player by
+    /// solving a linear program. Returns the plan, together with the
+    /// value the player is guaranteed (from their own perspective).
+    ///
+    /// Writing `E x = e` and `F y = f` for the constraints on the realization
+    /// plans of the player and their opponent, and `A` for the payoff matrix,
+    /// the program is the following:
+    ///
+    /// maximize `q₀` subject to `Fᵀ q - Aᵀ x ≤ 0`, `E x = e`, `x ≥ 0`
+    fn solve_for(&self, player: Player) -> Result<(Vec<f64>, f64), LpError> {
+        let own = player.select_ref(&self.players);
+        let other = (!player).select_ref(&self.players);
+
+        // Variables: own sequences, followed by one dual variable for the
+        // root constraint of the opponent, and one for each of their infosets.
+        let duals = own.sequence_count;
+        let mut program = LinearProgram::new(own.sequence_count + other.infosets.len() + 1);
+
+        for dual in duals..program.variable_count() {
+            program.set_free(dual);
+        }
+
+        program.set_objective(duals, 1.0);
+
+        // {{{ Best response constraints
+        let mut rows: Vec<Vec<(usize, f64)>> = vec![Vec::new(); other.sequence_count];
+        rows[0].push((duals, 1.0));
+
+        for (index, infoset) in other.infosets.iter().enumerate() {
+            rows[infoset.parent].push((duals + index + 1, -1.0));
+
+            for sequence in infoset.sequences() {
+                rows[sequence].push((duals + index + 1, 1.0));
+            }
+        }
+
+        for (own_sequence, other_sequence, payoff) in self.payoffs_for(player) {
+            rows[other_sequence].push((own_sequence, -payoff));
+        }
+
+        for row in rows {
+            program.add_constraint(row, Relation::LessEqual, 0.0);
+        }
+        // }}}
+        // {{{ Realization plan constraints
+        program.add_constraint(vec![(0, 1.0)], Relation::Equal, 1.0);
+
+        for infoset in &own.infosets {
+            let mut row: Vec<_> = infoset.sequences().map(|s| (s, 1.0)).collect();
+            row.push((infoset.parent, -1.0));
+            program.add_constraint(row, Relation::Equal, 0.0);
+        }
+        // }}}
+
+        let solution = program.solve()?;
+        let mut plan = solution.variables;
+        plan.truncate(own.sequence_count);
+
+        Ok((plan, solution.value))
+    }
+
+    /// Solves the subgame exactly, computing optimal strategies for both players.
+    ///
+    /// The simplex implementation works on dense tableaus, so this is only
+    /// practical for small subgames (think final turns with two cards in hand).
+    pub fn solve(&self) -> Result<SequenceFormSolution, LpError> {
+        let (mine, value) = self.solve_for(Player::Me)?;
+        let (yours, _) = self.solve_for(Player::You)?;
+
+        Ok(SequenceFormSolution {
+            value,
+            plans: [mine, yours],
+        })
+    }
+
+    /// Approximates optimal strategies using CFR+ (alternating updates,
+    /// regrets floored at zero, and linearly weighted averages), stopping
+    /// once the exploitability of the average strategies drops below the
+    /// given tolerance.
+    ///
+    /// Unlike [Self::solve], this scales to subgames with
+    /// thousands of sequences (such as entire final turns).
+    pub fn solve_cfr_plus(
+        &self,
+        tolerance: Utility,
+        max_iterations: usize,
+    ) -> SequenceFormSolution {
+        let mut regrets = Player::PLAYERS.map(|player| vec![0.0; self.sequence_count(player)]);
+        let mut sums = regrets.clone();
+        let mut strategy = Vec::new();
+
+        for iteration in 1..=max_iterations {
+            for player in Player::PLAYERS {
+                let opponent_plan =
+                    self.regret_matching_plan(!player, (!player).select_ref(&regrets));
+                let own_regrets = player.select_mut(&mut regrets);
+                let mut values = vec![0.0; own_regrets.len()];
+
+                for (own_sequence, other_sequence, payoff) in self.payoffs_for(player) {
+                    values[own_sequence] += payoff * opponent_plan[other_sequence];
+                }
+
+                // Children are always visited before their parents
+                for infoset in self.infosets(player).iter().rev() {
+                    Self::regret_matching(&own_regrets[infoset.sequences()], &mut strategy);
+                    let value: f64 = infoset
+                        .sequences()
+                        .zip(&strategy)
+                        .map(|(sequence, probability)| values[sequence] * probability)
+                        .sum();
+
+                    for sequence in infoset.sequences() {
+                        own_regrets[sequence] =
+                            (own_regrets[sequence] + values[sequence] - value).max(0.0);
+                    }
+
+                    values[infoset.parent] += value;
+                }
+
+                // Later iterations get more weight in the average
+                let plan = self.regret_matching_plan(player, own_regrets);
+                for (sum, reach) in player.select_mut(&mut sums).iter_mut().zip(plan) {
+                    *sum += iteration as f64 * reach;
+                }
+            }
+
+            if iteration % 10 == 0 || iteration == max_iterations {
+                // The empty sequence is always reached, so it
+                // accumulates the total weight of the average.
+                let plans = sums.clone().map(|mut plan| {
+                    let total = plan[0];
+                    plan.iter_mut().for_each(|reach| *reach /= total);
+                    plan
+                });
+
+                if iteration == max_iterations || self.exploitability(&plans) <= tolerance {
+                    return SequenceFormSolution {
+                        value: self.expected_utility(&plans) as f64,
+                        plans,
+                    };
+                }
+            }
+        }
+
+        unreachable!("The last iteration always returns")
+    }
+
+    /// The realization plan induced by playing proportionally to the positive
+    /// regrets at every infoset (uniformly if all of them are zero).
+    fn regret_matching_plan(&self, player: Player, regrets: &[f64]) -> Vec<f64> {
+        let mut plan = vec![0.0; regrets.len()];
+        plan[0] = 1.0;
+        let mut strategy = Vec::new();
+
+        for infoset in self.infosets(player) {
+            Self::regret_matching(&regrets[infoset.sequences()], &mut strategy);
+
+            for (sequence, &probability) in infoset.sequences().zip(&strategy) {
+                plan[sequence] = plan[infoset.parent] * probability;
+            }
+        }
+
+        plan
+    }
+
+    /// Computes the strategy proportional to the positive regrets.
+    /// The result is written to a buffer, as this runs for every
+    /// infoset multiple times per iteration.
+    fn regret_matching(regrets: &[f64], strategy: &mut Vec<f64>) {
+        let total: f64 = regrets.iter().map(|regret| regret.max(0.0)).sum();
+        let size = regrets.len() as f64;
+
+        strategy.clear();
+        strategy.extend(regrets.iter().map(|regret| {
+            if total > 0.0 {
+                regret.max(0.0) / total
+            } else {
+                1.0 / size
+            }
+        }));
+    }
+    // }}}
+    // {{{ Evaluation
+    /// Builds the realization plan of a player from a behavioural strategy.
+    ///
+    /// The strategy is given the infoset the player is in, and should
+    /// return the probability of taking each decision.
+    pub fn realization_plan(
+        &self,
+        player: Player,
+        mut strategy: impl FnMut(&Infoset) -> Vec<Probability>,
+    ) -> Vec<f64> {
+        let mut plan = vec![0.0; self.sequence_count(player)];
+        plan[0] = 1.0;
+
+        for infoset in self.infosets(player) {
+            let probabilities = strategy(infoset);
+            assert_eq!(probabilities.len(), infoset.count);
+
+            for (sequence, probability) in infoset.sequences().zip(probabilities) {
+                plan[sequence] = plan[infoset.parent] * probability as f64;
+            }
+        }
+
+        plan
+    }
+
+    /// Builds the realization plan of the average strategy a player
+    /// follows in a (trained) scope generated from the root of this subgame.
+    ///
+    /// Infosets missing from the scope are played uniformly at random.
+    pub fn scope_realization_plan(&self, player: Player, scope: &Scope) -> Vec<f64> {
+        self.realization_plan(player, |infoset| {
+            let mut cursor = ScopeCursor::new(scope, self.state, self.phase);
+
+            for &reveal_index in &infoset.path {
+                cursor = match cursor.advance(reveal_index, self.rules) {
+                    Some(TurnResult::Unfinished(next)) => next,
+                    _ => unreachable!("Infosets cannot be reached after the game ends"),
+                };
+            }
+
+            match cursor
+                .matrix(player)
+                .and_then(|matrix| matrix.get_node(infoset.hidden))
+            {
+                Some(node) => node.get_average_strategy(),
+                None => vec![1.0 / infoset.count as Probability; infoset.count],
+            }
+        })
+    }
+
+    /// Computes the expected utility of two realization plans.
+    pub fn expected_utility(&self, plans: &Pair<Vec<f64>>) -> Utility {
+        let utility: f64 = self
+            .payoffs
+            .iter()
+            .map(|(sequences, payoff)| plans[0][sequences[0]] * plans[1][sequences[1]] * payoff)
+            .sum();
+
+        utility as Utility
+    }
+
+    /// Computes the best utility a player can achieve (from their own
+    /// perspective) against a fixed realization plan of their opponent.
+    pub fn best_response_value(&self, player: Player, opponent_plan: &[f64]) -> Utility {
+        let mut values = vec![0.0; self.sequence_count(player)];
+
+        for (own_sequence, other_sequence, payoff) in self.payoffs_for(player) {
+            values[own_sequence] += payoff * opponent_plan[other_sequence];
+        }
+
+        // Children are always visited before their parents
+        for infoset in self.infosets(player).iter().rev() {
+            let best = infoset
+                .sequences()
+                .map(|sequence| values[sequence])
+                .fold(f64::NEG_INFINITY, f64::max);
+
+            values[infoset.parent] += best;
+        }
+
+        values[0] as Utility
+    }
+
+    /// Computes how much utility a pair of realization plans
+    /// leaves on the table. This is zero exactly for equilibria.
+    pub fn exploitability(&self, plans: &Pair<Vec<f64>>) -> Utility {
+        self.best_response_value(Player::Me, &plans[1])
+            + self.best_response_value(Player::You, &plans[0])
+    }
+    // }}}
+}
+// }}}
+// {{{ Builder
+/// Walks the game tree, collecting infosets and payoffs.
+struct Builder {
+    rules: RuleSet,
+    players: Pair<PlayerInfosets>,
+    payoffs: HashMap<Pair<usize>, f64>,
+    path: Vec<RevealIndex>,
+}
+
+impl Builder {
+    fn walk(
+        &mut self,
+        state: KnownState,
+        phase: SomePhase,
+        hidden: Pair<EncodingInfo>,
+        parents: Pair<usize>,
+        probability: f64,
+    ) {
+        let summary = state.to_summary();
+        let counts = phase.decision_counts(&state);
+        let hidden_states = hidden.map(HiddenState::from_encoding_info);
+
+        let firsts = Player::PLAYERS.map(|player| {
+            let count = player.select(counts);
+            let parent = player.select(parents);
+
+            if count > 1 {
+                let index = HiddenIndex::encode(&summary, player, player.select(hidden));
+                let infosets = player.select_mut(&mut self.players);
+                Some(infosets.get_or_insert(&self.path, index, parent, count))
+            } else {
+                None
+            }
+        });
+
+        for my_index in 0..counts[0] {
+            for your_index in 0..counts[1] {
+                let decisions = [DecisionIndex(my_index), DecisionIndex(your_index)];
+                let sequences = Player::PLAYERS.map(|player| {
+                    let decision = player.select(decisions).0;
+
+                    match player.select(firsts) {
+                        Some(first) => first + decision,
+                        None => player.select(parents),
+                    }
+                });
+
+                let (reveal_index, result) = phase
+                    .advance(state, hidden_states, decisions, self.rules)
+                    .expect("Decisions should always be valid");
+
+                match result {
+                    TurnResult::Finished(score) => {
+                        *self.payoffs.entry(sequences).or_insert(0.0) +=
+                            probability * score.to_utility() as f64;
+                    }
+                    TurnResult::Unfinished((next_state, next_hidden, next_phase)) => {
+                        self.path.push(reveal_index);
+                        self.walk(next_state, next_phase, next_hidden, sequences, probability);
+                        self.path.pop();
+                    }
+                }
+            }
+        }
+    }
+}
+// }}}
+// {{{ Solutions
+/// Optimal strategies for both players in some subgame.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SequenceFormSolution {
+    /// The value of the subgame, from the perspective of [Player::Me].
+    pub value: f64,
+    /// The realization plans of both players.
+    pub plans: Pair<Vec<f64>>,
+}
+
+impl SequenceFormSolution {
+    /// Converts the realization plan of a player back into
+    /// the probability of taking each decision at some infoset.
+    ///
+    /// Infosets which are never reached are played uniformly at random.
+    pub fn strategy(&self, player: Player, infoset: &Infoset) -> Vec<Probability> {
+        let plan = player.select_ref(&self.plans);
+        let reach = plan[infoset.parent];
+
+        if reach <= 1e-12 {
+            return vec![1.0 / infoset.count as Probability; infoset.count];
+        }
+
+        let mut strategy: Vec<Probability> = infoset
+            .sequences()
+            .map(|sequence| (plan[sequence].max(0.0) / reach) as Probability)
+            .collect();
+
+        crate::helpers::normalize_vec(&mut strategy);
+        strategy
+    }
+}
+// }}}
+// {{{ Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cfr::phase::{MainPhase, PerPhase};
+    use crate::game::notation::Position;
+
+    fn root() -> (KnownState, SomePhase) {
+        let state = Position::parse("PPPL 3 +0 WSRBDG RS/RS -/-").unwrap().state;
+        (state, PerPhase::Main(MainPhase::new()))
+    }
+
+    #[test]
+    fn solution_is_an_equilibrium() {
+        let (state, phase) = root();
+        let game = SequenceForm::uniform(state, phase, RuleSet::default());
+        let solution = game.solve().unwrap();
+
+        // The game is symmetrical
+        assert!(solution.value.abs() < 1e-6);
+        assert!(game.exploitability(&solution.plans).abs() < 1e-4);
+
+        let value = game.expected_utility(&solution.plans) as f64;
+        assert!((value - solution.value).abs() < 1e-4);
+
+        // Behavioural strategies lead back to the same plans
+        for player in Player::PLAYERS {
+            let plan = game.realization_plan(player, |infoset| solution.strategy(player, infoset));
+            let best_response = game.best_response_value(!player, &plan);
+            assert!(best_response < 1e-4);
+        }
+    }
+
+    #[test]
+    fn seer_status_is_an_advantage() {
+        let state = Position::parse("PPPL 3 +0 WSRBDG RS/RS S/-").unwrap().state;
+        let game =
+            SequenceForm::uniform(state, PerPhase::Main(MainPhase::new()), RuleSet::default());
+        let solution = game.solve().unwrap();
+
+        assert!(solution.value > 0.1);
+        assert!(game.exploitability(&solution.plans).abs() < 1e-4);
+    }
+}
+// }}}
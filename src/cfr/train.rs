@@ -4,7 +4,7 @@ use rand::Rng;
 
 use super::decision::{DecisionVector, Probability, Scope, Utility};
 use super::hidden_index::{self, HiddenIndex, HiddenState};
-use super::phase::{MainPhase, Phase};
+use super::phase::{MainPhase, PerPhase, Phase, SomePhase};
 use crate::cfr::decision_index::DecisionIndex;
 use crate::game::known_state_summary::KnownStateSummary;
 use crate::game::types::Player;
@@ -22,13 +22,44 @@ impl TrainingContext {
     }
 
     pub fn cfr(&self, scope: &mut Scope, state: KnownStateSummary, iterations: usize) {
+        self.cfr_generic(scope, MainPhase::new(), state, iterations, true);
+    }
+
+    /// Similar to `cfr`, but starts from an arbitrary phase,
+    /// and does not report progress. Every valid combination of
+    /// hidden information is assumed to be equally likely.
+    pub fn cfr_from(
+        &self,
+        scope: &mut Scope,
+        phase: SomePhase,
+        state: KnownStateSummary,
+        iterations: usize,
+    ) {
+        match phase {
+            PerPhase::Main(phase) => self.cfr_generic(scope, phase, state, iterations, false),
+            PerPhase::Sabotage(phase) => self.cfr_generic(scope, phase, state, iterations, false),
+            PerPhase::Seer(phase) => self.cfr_generic(scope, phase, state, iterations, false),
+        }
+    }
+
+    fn cfr_generic<P: Phase + Copy>(
+        &self,
+        scope: &mut Scope,
+        phase: P,
+        state: KnownStateSummary,
+        iterations: usize,
+        report_progress: bool,
+    ) {
         let probabilities: Pair<Probability> = [1.0; 2];
-        let phase = MainPhase::new();
+        let hidden_vec: Vec<_> = phase.valid_hidden_states(state).collect();
+
         for i in 0..iterations {
-            println!("Iteration {i}");
+            if report_progress {
+                println!("Iteration {i}");
+            }
 
-            for hidden in phase.valid_hidden_states(state) {
-                self.train_phase(scope, phase, state, hidden, probabilities);
+            for hidden in &hidden_vec {
+                self.train_phase(scope, phase, state, *hidden, probabilities);
             }
         }
     }
@@ -101,6 +132,12 @@ impl TrainingContext {
                     return Some(0.0);
                 };
 
+                // The regrets of the second player get updated while iterating over
+                // the decisions of the first, so we freeze their strategy beforehand.
+                let your_strategy: Vec<_> = (0..counts[1])
+                    .map(|index| DecisionVector::try_strategy(nodes[1].as_deref(), index))
+                    .collect();
+
                 // {{{ First player
                 for index in 0..(counts[0]) {
                     let my_decision = DecisionIndex(index);
@@ -113,10 +150,8 @@ impl TrainingContext {
                         } else {
                             let mut total_utility: Utility = 0.0;
 
-                            for index in 0..(counts[1]) {
+                            for (index, &your_probability) in your_strategy.iter().enumerate() {
                                 let your_decision = DecisionIndex(index);
-                                let your_probability =
-                                    DecisionVector::try_strategy(nodes[1].as_deref(), index);
 
                                 // {{{ Recursive call
                                 let new_probabilities = [
@@ -175,9 +210,10 @@ impl TrainingContext {
                     }
                 }
 
+                // The total utility is given from the perspective of the first player
                 if let Some(node) = &mut nodes[1] {
                     for index in 0..counts[1] {
-                        node.accumulate_regret(index, -probabilities[0] * total_utility);
+                        node.accumulate_regret(index, probabilities[0] * total_utility);
                     }
                 }
                 // }}}
@@ -194,3 +230,82 @@ impl TrainingContext {
         num.abs() < 0.00000001
     }
 }
+
+// {{{ Fixtures
+/// Trees shared by the tests of modules working with generated scopes.
+#[cfg(test)]
+pub(crate) mod fixtures {
+    use super::TrainingContext;
+    use crate::cfr::decision::Scope;
+    use crate::cfr::generate::GenerationContext;
+    use crate::cfr::phase::{MainPhase, PerPhase};
+    use crate::game::known_state::KnownState;
+    use crate::game::known_state_summary::KnownStateEssentials;
+    use crate::game::rules::RuleSet;
+    use bumpalo::Bump;
+
+    /// The rules trees get generated under.
+    pub fn rules() -> RuleSet {
+        RuleSet::default().with_hopeless_surrenders(true)
+    }
+
+    /// Generates a tree covering the turn starting at the main phase of the given state.
+    pub fn generated_scope(allocator: &Bump, state: KnownState) -> Scope<'_> {
+        GenerationContext::new(1, state, rules(), allocator).generate()
+    }
+
+    /// Same as [generated_scope], but also trains the tree for a few iterations.
+    pub fn trained_scope(allocator: &Bump, state: KnownState, iterations: usize) -> Scope<'_> {
+        let mut scope = generated_scope(allocator, state);
+        let phase = PerPhase::Main(MainPhase::new());
+        TrainingContext::new(false).cfr_from(&mut scope, phase, state.to_summary(), iterations);
+
+        scope
+    }
+}
+// }}}
+// {{{ Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cfr::generate::GenerationContext;
+    use crate::cfr::sequence_form::SequenceForm;
+    use crate::game::known_state_summary::KnownStateEssentials;
+    use crate::game::notation::Position;
+    use crate::game::rules::RuleSet;
+    use bumpalo::Bump;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn neither_player_stays_exploitable() {
+        // The position is symmetrical, so the game is worth 0 to both players
+        let state = Position::parse("PPPL 3 +0 WSRBDG RS/RS -/-").unwrap().state;
+        let phase = PerPhase::Main(MainPhase::new());
+        let game = SequenceForm::uniform(state, phase, RuleSet::default());
+        let allocator = Bump::new();
+        let generate =
+            || GenerationContext::new(1, state, RuleSet::default(), &allocator).generate();
+
+        let ctx = TrainingContext::new(false);
+        let mut cfr_scope = generate();
+        ctx.cfr(&mut cfr_scope, state.to_summary(), 300);
+
+        let mut cs_cfr_scope = generate();
+        let mut rng = StdRng::seed_from_u64(0);
+        ctx.cs_cfr(&mut rng, &mut cs_cfr_scope, state.to_summary(), 10000);
+
+        for (trainer, scope) in [("cfr", &cfr_scope), ("cs_cfr", &cs_cfr_scope)] {
+            for player in Player::PLAYERS {
+                let plan = game.scope_realization_plan(player, scope);
+                let exploitability = game.best_response_value(!player, &plan);
+
+                assert!(
+                    exploitability < 0.05,
+                    "{player:?} can still be exploited for {exploitability} after {trainer}"
+                );
+            }
+        }
+    }
+}
+// }}}
@@ -1,9 +1,10 @@
 use super::creature::Creature;
+use serde::{Deserialize, Serialize};
 use std::fmt::{self, Display};
 use Battlefield::*;
 
 // {{{ Battlefield
-#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug, Serialize, Deserialize)]
 pub enum Battlefield {
     Mountain,
     Glade,
@@ -47,7 +48,7 @@ impl Display for Battlefield {
 // {{{ Battlefields
 /// List of battlefields used in a battle.
 // TODO: consider sharing battlefields.all
-#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug, Serialize, Deserialize)]
 pub struct Battlefields {
     pub all: [Battlefield; 4],
     pub current: usize,
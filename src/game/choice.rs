@@ -1,8 +1,9 @@
 use super::{creature::Creature, edict::Edict};
+use serde::{Deserialize, Serialize};
 
 // {{{ Main phase choice
 // Choice made by one of the players in the main phase
-#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug, Serialize, Deserialize)]
 pub struct MainPhaseChoice {
     pub edict: Edict,
 
@@ -23,7 +24,7 @@ impl MainPhaseChoice {
 // }}}
 // {{{ Final main phase choice
 // Similar to MainPhaseChoice but used after the seer phase gets resolved
-#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug, Serialize, Deserialize)]
 pub struct FinalMainPhaseChoice {
     pub creature: Creature,
     pub edict: Edict,
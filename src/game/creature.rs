@@ -1,11 +1,12 @@
 use crate::helpers::bitfield::{Bitfield, Bitfield16};
 use crate::make_bitfield;
+use serde::{Deserialize, Serialize};
 use std::convert::TryFrom;
 use std::debug_assert;
 use std::fmt::{self, Display};
 
 // {{{ Creature
-#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug, PartialOrd, Ord)]
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum Creature {
     Wall,
     Seer,
@@ -1,9 +1,10 @@
 use super::creature::{Creature, CreatureSet};
 use crate::helpers::bitfield::{const_size_codec::ConstSizeCodec, Bitfield, Bitfield16};
+use serde::{Deserialize, Serialize};
 
 // {{{ UserCreatureChoice
 /// User facing version of `CreatureChoice`.
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct UserCreatureChoice(pub Creature, pub Option<Creature>);
 
 impl UserCreatureChoice {
@@ -52,7 +53,7 @@ impl UserCreatureChoice {
 /// Encoded version of `UserCreatureChoice`.
 /// The result fits inside an `u8`, but we are
 /// using an `usize` for convenience.
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct CreatureChoice(pub usize);
 
 impl CreatureChoice {
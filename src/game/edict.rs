@@ -2,13 +2,14 @@ use crate::{
     helpers::bitfield::{Bitfield, Bitfield16},
     make_bitfield,
 };
+use serde::{Deserialize, Serialize};
 use std::{
     debug_assert,
     fmt::{self, Display},
 };
 
 // {{{ Edict
-#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug, Serialize, Deserialize)]
 pub enum Edict {
     // Victory point edicts
     RileThePublic,
@@ -2,20 +2,22 @@ use super::battlefield::{Battlefield, Battlefields};
 use super::creature::{Creature, CreatureSet};
 use super::edict::{Edict, EdictSet};
 use super::known_state_summary::KnownStateEssentials;
+use super::rules::RuleSet;
 use super::status_effect::{StatusEffect, StatusEffectSet};
 use super::types::{Player, Score};
 use crate::helpers::bitfield::Bitfield;
 use crate::helpers::pair::{are_equal, Pair};
+use serde::{Deserialize, Serialize};
 
 /// State of a player known by both players.
-#[derive(PartialEq, Eq, Clone, Copy, Debug, Default)]
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default, Serialize, Deserialize)]
 pub struct KnownPlayerState {
     pub edicts: EdictSet,
     pub effects: StatusEffectSet,
 }
 
 /// State known by both players at some point in time.
-#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Serialize, Deserialize)]
 pub struct KnownState {
     pub player_states: Pair<KnownPlayerState>,
     pub battlefields: Battlefields,
@@ -82,10 +84,10 @@ impl KnownState {
         }
     }
 
-    /// Computes whether a given player is guaranteed to win,
-    /// no matter what the opponent can pull off.
+    /// Computes whether a given player is guaranteed to win
+    /// under the given rules, no matter what the opponent can pull off.
     // TODO: add stalling with wall?
-    pub fn guaranteed_win(&self, player: Player) -> bool {
+    pub fn guaranteed_win(&self, player: Player, rules: &RuleSet) -> bool {
         // {{{ Rile the public spam
         let has_rtp = self.player_edicts(!player).has(Edict::RileThePublic);
         let has_steward = !self.graveyard.has(Creature::Steward);
@@ -95,18 +97,18 @@ impl KnownState {
         let mut rtp_usages = 0;
 
         if has_rtp {
-            rtp_usages += 1; // base usage
+            rtp_usages += rules.base_edict_multiplier.max(0); // base usage
         };
 
         if has_urban {
-            rtp_usages += 1; // edict multiplier
+            rtp_usages += rules.urban_edict_bonus.max(0); // edict multiplier
         };
 
         if has_steward {
-            rtp_usages += 1; // edict multiplier
+            rtp_usages += rules.steward_edict_bonus.max(0); // edict multiplier
 
-            if turns_left > 1 {
-                rtp_usages += 1; // steward return edicts to hand effect
+            if turns_left > 1 && rules.steward_returns_edicts {
+                rtp_usages += rules.base_edict_multiplier.max(0); // steward returns edicts to hand
             }
         };
         // }}}
@@ -115,7 +117,7 @@ impl KnownState {
             .battlefields
             .active()
             .into_iter()
-            .map(|battlefield| battlefield.reward())
+            .map(|battlefield| rules.reward(*battlefield))
             .sum::<u8>() as i8
             + rtp_usages;
 
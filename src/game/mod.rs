@@ -8,4 +8,6 @@ pub mod creature_choice;
 pub mod known_state;
 pub mod known_state_summary;
 pub mod simulate;
+pub mod rules;
 
+pub mod notation;
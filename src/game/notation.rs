@@ -0,0 +1,588 @@
+use super::battlefield::{Battlefield, Battlefields};
+use super::creature::{Creature, CreatureSet};
+use super::edict::{Edict, EdictSet};
+use super::known_state::{KnownPlayerState, KnownState};
+use super::known_state_summary::KnownStateEssentials;
+use super::status_effect::{StatusEffect, StatusEffectSet};
+use super::types::{Player, Score};
+use crate::cfr::hidden_index::HiddenState;
+use crate::helpers::bitfield::Bitfield;
+use crate::helpers::pair::Pair;
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+// {{{ Codes
+const BATTLEFIELD_CODES: [(Battlefield, char); 6] = [
+    (Battlefield::Mountain, 'M'),
+    (Battlefield::Glade, 'G'),
+    (Battlefield::Urban, 'U'),
+    (Battlefield::Night, 'N'),
+    (Battlefield::LastStrand, 'L'),
+    (Battlefield::Plains, 'P'),
+];
+
+const CREATURE_CODES: [(Creature, char); 11] = [
+    (Creature::Wall, 'W'),
+    (Creature::Seer, 'S'),
+    (Creature::Rogue, 'R'),
+    (Creature::Bard, 'B'),
+    (Creature::Diplomat, 'D'),
+    (Creature::Ranger, 'G'),
+    (Creature::Steward, 'T'),
+    (Creature::Barbarian, 'A'),
+    (Creature::Witch, 'H'),
+    (Creature::Mercenary, 'M'),
+    (Creature::Monarch, 'K'),
+];
+
+const EDICT_CODES: [(Edict, char); 5] = [
+    (Edict::RileThePublic, 'R'),
+    (Edict::DivertAttention, 'D'),
+    (Edict::Sabotage, 'S'),
+    (Edict::Gambit, 'G'),
+    (Edict::Ambush, 'A'),
+];
+
+// Effects caused by battlefields are lowercase,
+// while effects caused by creatures reuse the creature codes.
+const STATUS_EFFECT_CODES: [(StatusEffect, char); 7] = [
+    (StatusEffect::Mountain, 'm'),
+    (StatusEffect::Glade, 'g'),
+    (StatusEffect::Night, 'n'),
+    (StatusEffect::Seer, 'S'),
+    (StatusEffect::Bard, 'B'),
+    (StatusEffect::Mercenary, 'M'),
+    (StatusEffect::Barbarian, 'A'),
+];
+
+const PLAYER_CODES: [(Player, char); 2] = [(Player::Me, 'm'), (Player::You, 'y')];
+
+fn code_of<T: PartialEq + Copy>(value: T, codes: &[(T, char)]) -> char {
+    codes
+        .iter()
+        .find(|(other, _)| *other == value)
+        .map(|(_, code)| *code)
+        .expect("Every value should have a code")
+}
+
+fn value_of<T: Copy>(code: char, codes: &[(T, char)]) -> Option<T> {
+    codes
+        .iter()
+        .find(|(_, other)| *other == code)
+        .map(|(value, _)| *value)
+}
+// }}}
+// {{{ Errors
+/// An error encountered while parsing a position.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NotationError {
+    /// The name of the field containing the error.
+    pub field: &'static str,
+    pub message: String,
+}
+
+impl NotationError {
+    fn new(field: &'static str, message: String) -> Self {
+        Self { field, message }
+    }
+}
+
+impl Display for NotationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid {}: {}", self.field, self.message)
+    }
+}
+
+impl std::error::Error for NotationError {}
+// }}}
+// {{{ Sets
+/// Displays a bitfield as a string of codes (or `-` if empty).
+struct SetNotation<'a, B: Bitfield>(B, &'a [(B::Element, char)]);
+
+impl<'a, B: Bitfield> Display for SetNotation<'a, B>
+where
+    B::Element: PartialEq + Copy,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0.len() == 0 {
+            return write!(f, "-");
+        }
+
+        for element in self.0 {
+            write!(f, "{}", code_of(element, self.1))?;
+        }
+
+        Ok(())
+    }
+}
+
+fn parse_set<B: Bitfield>(
+    field: &'static str,
+    input: &str,
+    codes: &[(B::Element, char)],
+) -> Result<B, NotationError>
+where
+    B::Element: Copy,
+{
+    let mut result = B::empty();
+
+    if input == "-" {
+        return Ok(result);
+    }
+
+    for code in input.chars() {
+        let element = value_of(code, codes).ok_or_else(|| {
+            NotationError::new(field, format!("unknown code {code:?} in {input:?}"))
+        })?;
+
+        if result.has(element) {
+            return Err(NotationError::new(
+                field,
+                format!("code {code:?} appears twice in {input:?}"),
+            ));
+        }
+
+        result.insert(element);
+    }
+
+    Ok(result)
+}
+
+fn parse_set_pair<B: Bitfield>(
+    field: &'static str,
+    input: &str,
+    codes: &[(B::Element, char)],
+) -> Result<Pair<B>, NotationError>
+where
+    B::Element: Copy,
+{
+    let (me, you) = input.split_once('/').ok_or_else(|| {
+        NotationError::new(field, format!("expected <first>/<second>, got {input:?}"))
+    })?;
+
+    Ok([parse_set(field, me, codes)?, parse_set(field, you, codes)?])
+}
+// }}}
+// {{{ Positions
+/// A `KnownState`, together with (optionally) the hidden information of one of the players,
+/// written in a compact notation similar to chess' FEN. The notation is made out of the
+/// following space separated fields:
+///
+/// 1. The four battlefields (`M`ountain, `G`lade, `U`rban, `N`ight, `L`ast strand, `P`lains)
+/// 2. The index of the current battlefield (0-3)
+/// 3. The score, from the perspective of the first player (always signed)
+/// 4. The graveyard (`W`all, `S`eer, `R`ogue, `B`ard, `D`iplomat, ran`G`er, s`T`eward,
+///    b`A`rbarian, wit`H`, `M`ercenary, mon`K`arch)
+/// 5. The edicts in the hand of each player (`R`ile the public, `D`ivert attention,
+///    `S`abotage, `G`ambit, `A`mbush), separated by `/`
+/// 6. The status effects of each player, separated by `/`. Effects caused by battlefields are
+///    lowercase (`m`, `g`, `n`), while effects caused by creatures use the creature codes
+///    (`S`, `B`, `M`, `A`)
+/// 7. (optional) The player whose hidden information follows (`m`e or `y`ou)
+/// 8. (required after 7) The creatures in the hand of said player
+/// 9. (optional) The creatures said player has chosen this turn
+///
+/// Empty sets are written as `-`. For example, the following describes the second turn,
+/// where the first player lost the first battle, and is under the effect of the seer:
+///
+/// ```text
+/// MGUL 1 -3 WS DSGA/RSGA S/- m RBDT
+/// ```
+///
+/// Every turn sends one creature of each player to the graveyard and uses up one edict of
+/// each player, so positions whose graveyard, edicts or hand do not match the turn are
+/// rejected. Since the steward might hand every edict back, players may however hold
+/// more edicts than the turn alone suggests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Position {
+    pub state: KnownState,
+    pub hidden: Option<(Player, HiddenState)>,
+}
+
+impl Position {
+    #[inline(always)]
+    pub fn new(state: KnownState) -> Self {
+        Self {
+            state,
+            hidden: None,
+        }
+    }
+
+    #[inline(always)]
+    pub fn with_hidden(mut self, player: Player, hidden: HiddenState) -> Self {
+        self.hidden = Some((player, hidden));
+        self
+    }
+
+    pub fn parse(input: &str) -> Result<Self, NotationError> {
+        let mut fields = input.split_whitespace();
+        let mut next = |field| {
+            fields
+                .next()
+                .ok_or_else(|| NotationError::new(field, String::from("missing")))
+        };
+
+        // {{{ Battlefields
+        let input = next("battlefields")?;
+        let battlefields = input
+            .chars()
+            .map(|code| {
+                value_of(code, &BATTLEFIELD_CODES).ok_or_else(|| {
+                    NotationError::new(
+                        "battlefields",
+                        format!("unknown code {code:?} in {input:?}"),
+                    )
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let battlefields: [Battlefield; 4] = battlefields.try_into().map_err(|_| {
+            NotationError::new(
+                "battlefields",
+                format!("expected exactly 4 battlefields, got {input:?}"),
+            )
+        })?;
+
+        let input = next("turn")?;
+        let current = input.parse().ok().filter(|turn| *turn < 4).ok_or_else(|| {
+            NotationError::new("turn", format!("expected a number in 0-3, got {input:?}"))
+        })?;
+        // }}}
+        // {{{ Public information
+        let input = next("score")?;
+        let score = input.parse().map(Score).map_err(|_| {
+            NotationError::new("score", format!("expected a number, got {input:?}"))
+        })?;
+
+        let graveyard = parse_set("graveyard", next("graveyard")?, &CREATURE_CODES)?;
+        let edicts = parse_set_pair::<EdictSet>("edicts", next("edicts")?, &EDICT_CODES)?;
+        let effects = parse_set_pair::<StatusEffectSet>(
+            "status effects",
+            next("status effects")?,
+            &STATUS_EFFECT_CODES,
+        )?;
+
+        let state = KnownState {
+            player_states: [0, 1].map(|index| KnownPlayerState {
+                edicts: edicts[index],
+                effects: effects[index],
+            }),
+            battlefields: Battlefields {
+                all: battlefields,
+                current,
+            },
+            graveyard,
+            score,
+        };
+
+        if graveyard.len() != 2 * current {
+            return Err(NotationError::new(
+                "graveyard",
+                format!(
+                    "expected {} creatures on turn {current}, got {}",
+                    2 * current,
+                    graveyard.len()
+                ),
+            ));
+        }
+
+        for player in Player::PLAYERS {
+            let count = state.player_edicts(player).len();
+            if !(5 - current..=5).contains(&count) {
+                return Err(NotationError::new(
+                    "edicts",
+                    format!(
+                        "expected at least {} edicts for {player:?} on turn {current}, got {count}",
+                        5 - current
+                    ),
+                ));
+            }
+        }
+        // }}}
+        // {{{ Hidden information
+        let hidden = match fields.next() {
+            None => None,
+            Some(input) => {
+                let player = input
+                    .chars()
+                    .next()
+                    .filter(|_| input.len() == 1)
+                    .and_then(|code| value_of(code, &PLAYER_CODES))
+                    .ok_or_else(|| {
+                        NotationError::new("player", format!("expected m or y, got {input:?}"))
+                    })?;
+
+                let hand: CreatureSet = match fields.next() {
+                    Some(input) => parse_set("hand", input, &CREATURE_CODES)?,
+                    None => return Err(NotationError::new("hand", String::from("missing"))),
+                };
+
+                if !hand.is_disjoint_from(graveyard) {
+                    return Err(NotationError::new(
+                        "hand",
+                        String::from("creatures cannot be both in hand and in the graveyard"),
+                    ));
+                }
+
+                if hand.len() != state.hand_size() {
+                    return Err(NotationError::new(
+                        "hand",
+                        format!(
+                            "expected {} creatures on turn {current}, got {}",
+                            state.hand_size(),
+                            hand.len()
+                        ),
+                    ));
+                }
+
+                let choice = fields
+                    .next()
+                    .map(|input| parse_set::<CreatureSet>("choice", input, &CREATURE_CODES))
+                    .transpose()?;
+
+                if let Some(choice) = choice {
+                    let size = state.creature_choice_size(player);
+
+                    if !choice.is_subset_of(hand) {
+                        return Err(NotationError::new(
+                            "choice",
+                            String::from("creatures must be chosen from the hand"),
+                        ));
+                    } else if choice.len() != size {
+                        return Err(NotationError::new(
+                            "choice",
+                            format!("expected {size} creatures, got {}", choice.len()),
+                        ));
+                    }
+                }
+
+                Some((player, HiddenState::new(hand, choice)))
+            }
+        };
+
+        if let Some(input) = fields.next() {
+            return Err(NotationError::new(
+                "position",
+                format!("unexpected trailing input starting at {input:?}"),
+            ));
+        }
+        // }}}
+
+        Ok(Self { state, hidden })
+    }
+}
+
+impl Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = &self.state;
+
+        for battlefield in state.battlefields.all {
+            write!(f, "{}", code_of(battlefield, &BATTLEFIELD_CODES))?;
+        }
+
+        let [me, you] = state.player_states;
+        write!(
+            f,
+            " {} {:+} {} {}/{} {}/{}",
+            state.battlefields.current,
+            state.score.0,
+            SetNotation(state.graveyard, &CREATURE_CODES),
+            SetNotation(me.edicts, &EDICT_CODES),
+            SetNotation(you.edicts, &EDICT_CODES),
+            SetNotation(me.effects, &STATUS_EFFECT_CODES),
+            SetNotation(you.effects, &STATUS_EFFECT_CODES),
+        )?;
+
+        if let Some((player, hidden)) = self.hidden {
+            write!(
+                f,
+                " {} {}",
+                code_of(player, &PLAYER_CODES),
+                SetNotation(hidden.hand, &CREATURE_CODES)
+            )?;
+
+            if let Some(choice) = hidden.choice {
+                write!(f, " {}", SetNotation(choice, &CREATURE_CODES))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for Position {
+    type Err = NotationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+// }}}
+// {{{ Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::seq::SliceRandom;
+    use rand::{Rng, SeedableRng};
+
+    fn random_subset<B: Bitfield, R: Rng>(rng: &mut R, all: B, size: usize) -> B
+    where
+        B::Element: Copy,
+    {
+        let all = all.into_iter().collect::<Vec<_>>();
+        let mut result = B::empty();
+        for element in all.choose_multiple(rng, size) {
+            result.insert(*element);
+        }
+
+        result
+    }
+
+    #[test]
+    fn canonical_notation_roundtrips() {
+        for input in [
+            "MGUL 1 -3 WS DSGA/RSGA S/- m RBDT",
+            "PPPL 0 +0 - RDSGA/RDSGA -/-",
+            "NUGP 3 +12 WSRBDG RS/DA mgn/SBMA y HM HM",
+            "LMMM 2 -1 WSRT RDG/DGA -/B y BDG D",
+        ] {
+            let position = Position::parse(input).unwrap();
+            assert_eq!(position.to_string(), input);
+        }
+    }
+
+    #[test]
+    fn random_positions_roundtrip() {
+        let mut rng = StdRng::seed_from_u64(0);
+
+        for _ in 0..1000 {
+            let mut state = KnownState::new_starting(
+                [(); 4].map(|_| Battlefield::BATTLEFIELDS[rng.gen_range(0..6)]),
+            );
+            let current = rng.gen_range(0..4);
+            state.battlefields.current = current;
+            state.score = Score(rng.gen_range(-20..=20));
+            state.graveyard = random_subset(&mut rng, CreatureSet::all(), 2 * current);
+
+            for player_state in state.player_states.iter_mut() {
+                let effect_count = rng.gen_range(0..=StatusEffect::STATUS_EFFECTS.len());
+                player_state.edicts = random_subset(&mut rng, EdictSet::all(), 5 - current);
+                player_state.effects =
+                    random_subset(&mut rng, StatusEffectSet::all(), effect_count);
+            }
+
+            let mut position = Position::new(state);
+            if rng.gen_bool(0.5) {
+                let player = Player::PLAYERS[rng.gen_range(0..2)];
+                let hand = random_subset(&mut rng, !state.graveyard, state.hand_size());
+                let choice = rng
+                    .gen_bool(0.5)
+                    .then(|| random_subset(&mut rng, hand, state.creature_choice_size(player)));
+
+                position = position.with_hidden(player, HiddenState::new(hand, choice));
+            }
+
+            let printed = position.to_string();
+            assert_eq!(printed.parse(), Ok(position), "Failed to parse {printed:?}");
+        }
+    }
+
+    #[test]
+    fn stewards_hand_back_every_edict() {
+        // We played the steward last turn, which returned all our edicts
+        let position = Position::parse("PPPL 2 +0 WSRT RDSGA/DGA -/- m BDG").unwrap();
+        assert_eq!(position.state.player_edicts(Player::Me).len(), 5);
+        assert_eq!(position.to_string().parse(), Ok(position));
+    }
+
+    #[test]
+    fn json_uses_readable_names() {
+        let position = Position::parse("MGUL 1 -3 WS DSGA/RSGA S/- m RBDT").unwrap();
+        let json = serde_json::to_value(position).unwrap();
+
+        assert_eq!(
+            json["state"]["graveyard"],
+            serde_json::json!(["Wall", "Seer"])
+        );
+        assert_eq!(json["state"]["score"], serde_json::json!(-3));
+        assert_eq!(
+            json["hidden"][1]["hand"],
+            serde_json::json!(["Rogue", "Bard", "Diplomat", "Steward"])
+        );
+        assert_eq!(serde_json::from_value::<Position>(json).unwrap(), position);
+
+        let duplicate = serde_json::from_str::<CreatureSet>(r#"["Wall", "Wall"]"#);
+        assert!(duplicate.is_err());
+    }
+
+    #[test]
+    fn errors_point_at_the_offending_field() {
+        let error = |input: &str| Position::parse(input).unwrap_err().to_string();
+
+        assert_eq!(
+            error("PPPX 0 +0 - -/- -/-"),
+            "Invalid battlefields: unknown code 'X' in \"PPPX\""
+        );
+        assert_eq!(
+            error("PPP 0 +0 - -/- -/-"),
+            "Invalid battlefields: expected exactly 4 battlefields, got \"PPP\""
+        );
+        assert_eq!(
+            error("PPPL 4 +0 - -/- -/-"),
+            "Invalid turn: expected a number in 0-3, got \"4\""
+        );
+        assert_eq!(
+            error("PPPL 0 +0 WW -/- -/-"),
+            "Invalid graveyard: code 'W' appears twice in \"WW\""
+        );
+        assert_eq!(
+            error("PPPL 0 +0 - RD -/-"),
+            "Invalid edicts: expected <first>/<second>, got \"RD\""
+        );
+        assert_eq!(error("PPPL 0 +0 - -/-"), "Invalid status effects: missing");
+        assert_eq!(
+            error("PPPL 0 +0 - RDSGA/RDSGA -/- m"),
+            "Invalid hand: missing"
+        );
+        assert_eq!(
+            error("PPPL 1 +0 WS RDSG/RDSG -/- m WRBD"),
+            "Invalid hand: creatures cannot be both in hand and in the graveyard"
+        );
+        assert_eq!(
+            error("PPPL 0 +0 - RDSGA/RDSGA -/- m WSRBD W W"),
+            "Invalid position: unexpected trailing input starting at \"W\""
+        );
+    }
+
+    #[test]
+    fn positions_must_match_the_turn() {
+        let error = |input: &str| Position::parse(input).unwrap_err().to_string();
+
+        assert_eq!(
+            error("NUGP 3 +12 WSRBDGTA -/- mgn/SBMA y HM H"),
+            "Invalid graveyard: expected 6 creatures on turn 3, got 8"
+        );
+        assert_eq!(
+            error("PPPL 1 +0 WS RDS/RDSG -/-"),
+            "Invalid edicts: expected at least 4 edicts for Me on turn 1, got 3"
+        );
+        assert_eq!(
+            error("PPPL 2 +0 WSRB RDSGA/RD -/-"),
+            "Invalid edicts: expected at least 3 edicts for You on turn 2, got 2"
+        );
+        assert_eq!(
+            error("MGUL 1 -3 WS DSGA/RSGA S/- m RBDTA"),
+            "Invalid hand: expected 4 creatures on turn 1, got 5"
+        );
+        assert_eq!(
+            error("PPPL 1 +0 WS RDSG/RDSG -/- m RBDT G"),
+            "Invalid choice: creatures must be chosen from the hand"
+        );
+        assert_eq!(
+            error("PPPL 1 +0 WS RDSG/RDSG S/- m RBDT R"),
+            "Invalid choice: expected 2 creatures, got 1"
+        );
+    }
+}
+// }}}
@@ -0,0 +1,123 @@
+use super::battlefield::Battlefield;
+use super::creature::{Creature, CreatureSet};
+use crate::helpers::bitfield::Bitfield;
+use serde::{Deserialize, Serialize};
+
+// {{{ Rule set
+/// Every rule of the game which can be tweaked without changing the structure
+/// of a turn. Makes it possible to study house rules and errata.
+///
+/// The default rule set matches the cards (and plays hopeless games until the end).
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct RuleSet {
+    /// Amount of points rewarded for winning a battle
+    /// in each location, indexed by battlefield.
+    pub rewards: [u8; 6],
+    /// The creatures receiving a bonus in each location, indexed by battlefield.
+    pub bonuses: [CreatureSet; 6],
+    /// The strength of each creature, indexed by creature.
+    pub strengths: [u8; 11],
+
+    /// The edict multiplier every player starts out with.
+    pub base_edict_multiplier: i8,
+    /// How much the urban battlefield increases the edict multiplier by.
+    pub urban_edict_bonus: i8,
+    /// How much an active steward increases the edict multiplier by.
+    pub steward_edict_bonus: i8,
+    /// Whether an active steward returns all edicts to the hand of it's owner.
+    pub steward_returns_edicts: bool,
+
+    /// When true, games end preemptively once one player
+    /// wouldn't be able to win, no matter what they did.
+    pub hopeless_surrenders: bool,
+}
+
+impl RuleSet {
+    /// The rules printed on the cards.
+    pub fn standard() -> Self {
+        // The list of battlefields is not sorted by discriminant
+        let mut rewards = [0; 6];
+        let mut bonuses = [CreatureSet::empty(); 6];
+
+        for battlefield in Battlefield::BATTLEFIELDS {
+            rewards[battlefield as usize] = battlefield.reward();
+
+            for creature in Creature::CREATURES {
+                if battlefield.bonus(creature) {
+                    bonuses[battlefield as usize].insert(creature);
+                }
+            }
+        }
+
+        Self {
+            rewards,
+            bonuses,
+            strengths: Creature::CREATURES.map(|creature| creature.strength()),
+            base_edict_multiplier: 1,
+            urban_edict_bonus: 1,
+            steward_edict_bonus: 1,
+            steward_returns_edicts: true,
+            hopeless_surrenders: false,
+        }
+    }
+
+    /// Returns a copy of the rules with hopeless surrenders toggled.
+    #[inline(always)]
+    pub fn with_hopeless_surrenders(self, hopeless_surrenders: bool) -> Self {
+        Self {
+            hopeless_surrenders,
+            ..self
+        }
+    }
+
+    /// Amount of points rewarded for winning a battle in a given location.
+    #[inline(always)]
+    pub fn reward(&self, battlefield: Battlefield) -> u8 {
+        self.rewards[battlefield as usize]
+    }
+
+    /// Whether a creature receives a bonus in a given location.
+    #[inline(always)]
+    pub fn bonus(&self, battlefield: Battlefield, creature: Creature) -> bool {
+        self.bonuses[battlefield as usize].has(creature)
+    }
+
+    /// The strength of a given creature.
+    #[inline(always)]
+    pub fn strength(&self, creature: Creature) -> u8 {
+        self.strengths[creature as usize]
+    }
+}
+
+impl Default for RuleSet {
+    fn default() -> Self {
+        Self::standard()
+    }
+}
+// }}}
+// {{{ Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn standard_rules_match_the_cards() {
+        let rules = RuleSet::standard();
+
+        for battlefield in Battlefield::BATTLEFIELDS {
+            assert_eq!(rules.reward(battlefield), battlefield.reward());
+
+            for creature in Creature::CREATURES {
+                assert_eq!(
+                    rules.bonus(battlefield, creature),
+                    battlefield.bonus(creature)
+                );
+            }
+        }
+
+        for creature in Creature::CREATURES {
+            assert_eq!(rules.strength(creature), creature.strength());
+        }
+    }
+}
+// }}}
@@ -3,6 +3,7 @@ use super::choice::{FinalMainPhaseChoice, SabotagePhaseChoice};
 use super::creature::Creature;
 use super::edict::Edict;
 use super::known_state::KnownState;
+use super::rules::RuleSet;
 use super::status_effect::{StatusEffect, StatusEffectSet};
 use super::types::{BattleResult, Player, TurnResult};
 use crate::game::edict::EdictSet;
@@ -17,9 +18,8 @@ pub struct BattleContext {
     pub sabotage_choices: Pair<SabotagePhaseChoice>,
     pub state: KnownState,
 
-    /// When true, the state will preemtively end games if one player
-    /// wouldn't be able to win, no matter what they did.
-    enable_hopeless_surrenders: bool,
+    /// The rules the battle is fought under.
+    rules: RuleSet,
 }
 
 impl BattleContext {
@@ -28,13 +28,13 @@ impl BattleContext {
         main_choices: Pair<FinalMainPhaseChoice>,
         sabotage_choices: Pair<SabotagePhaseChoice>,
         state: KnownState,
-        enable_hopeless_surrenders: bool,
+        rules: RuleSet,
     ) -> Self {
         Self {
             main_choices,
             sabotage_choices,
             state,
-            enable_hopeless_surrenders,
+            rules,
         }
     }
 
@@ -101,16 +101,16 @@ impl BattleContext {
     /// - the urban battlefield
     /// - the steward creature
     fn edict_multiplier(&self, player: Player) -> i8 {
-        let mut result = 1;
+        let mut result = self.rules.base_edict_multiplier;
 
         // [[[URBAN EFFECT 1]]]
         if self.battlefield() == Battlefield::Urban {
-            result += 1;
+            result += self.rules.urban_edict_bonus;
         }
 
         // [[[STEWARD EFFECT 1]]]
         if self.is_active_creature(player, Creature::Steward) {
-            result += 1;
+            result += self.rules.steward_edict_bonus;
         }
 
         result
@@ -120,7 +120,7 @@ impl BattleContext {
     /// is affected by the battlefield bonus.
     #[inline(always)]
     fn battlefield_bonus(&self, player: Player) -> bool {
-        self.battlefield().bonus(self.creature(player))
+        self.rules.bonus(self.battlefield(), self.creature(player))
     }
 
     /// Calculates the strength modifier for the creature the current player has played
@@ -269,8 +269,8 @@ impl BattleContext {
         }
 
         let base_strengths = (
-            self.creature(player).strength() as i8,
-            self.creature(!player).strength() as i8,
+            self.rules.strength(self.creature(player)) as i8,
+            self.rules.strength(self.creature(!player)) as i8,
         );
 
         let strength_modifiers = self.strength_modifiers(player);
@@ -308,7 +308,7 @@ impl BattleContext {
     /// as a given player.
     fn battle_reward(&self, player: Player) -> u8 {
         let effects = self.player_effects(player);
-        let mut total = self.battlefield().reward();
+        let mut total = self.rules.reward(self.battlefield());
 
         // Lingering effects:
         // [[[NIGHT EFFECT 1]]]
@@ -404,9 +404,11 @@ impl BattleContext {
 
                 // Resolve the Steward effect
                 // [[[STEWARD EFFECT 2]]]
-                if self.is_active_creature(player, Creature::Steward) {
+                let returns_edicts = self.rules.steward_returns_edicts;
+
+                if returns_edicts && self.is_active_creature(player, Creature::Steward) {
                     p1.edicts = EdictSet::all();
-                } else if self.is_active_creature(!player, Creature::Steward) {
+                } else if returns_edicts && self.is_active_creature(!player, Creature::Steward) {
                     p2.edicts = EdictSet::all();
                 }
 
@@ -463,10 +465,12 @@ impl BattleContext {
                     }
                 }
 
-                if self.enable_hopeless_surrenders && new_state.guaranteed_win(player) {
-                    debug_assert!(!new_state.guaranteed_win(!player));
+                let surrenders = self.rules.hopeless_surrenders;
+
+                if surrenders && new_state.guaranteed_win(player, &self.rules) {
+                    debug_assert!(!new_state.guaranteed_win(!player, &self.rules));
                     TurnResult::Finished(new_state.score(player))
-                } else if self.enable_hopeless_surrenders && new_state.guaranteed_win(!player) {
+                } else if surrenders && new_state.guaranteed_win(!player, &self.rules) {
                     TurnResult::Finished(new_state.score(player))
                 } else {
                     TurnResult::Unfinished(new_state)
@@ -539,7 +543,12 @@ mod tests {
         let p1_choice = FinalMainPhaseChoice::new(Creature::Mercenary, Edict::Gambit);
         let p2_choice = FinalMainPhaseChoice::new(Creature::Seer, Edict::Gambit);
 
-        BattleContext::new([p1_choice, p2_choice], [None, None], *BASIC_STATE, false)
+        BattleContext::new(
+            [p1_choice, p2_choice],
+            [None, None],
+            *BASIC_STATE,
+            RuleSet::default(),
+        )
     });
     // }}}
     // {{{ Battlefields
@@ -900,5 +909,74 @@ mod tests {
         );
     }
     // }}}
+    // {{{ Rule variants
+    #[test]
+    fn steward_return_can_be_disabled() {
+        let mut ctx = *BASIC_BATTLE_CONTEXT;
+        ctx.rules.steward_returns_edicts = false;
+        ctx.set_creature(Player::Me, Creature::Steward);
+
+        let player_states = ctx
+            .advance_known_state()
+            .1
+            .get_unfinished()
+            .unwrap()
+            .player_states;
+
+        for player_state in player_states {
+            assert_eq!(
+                player_state.edicts.len(),
+                4,
+                "The edicts should stay played"
+            );
+        }
+    }
+
+    #[test]
+    fn edict_multipliers_follow_the_rules() {
+        let mut ctx = *BASIC_BATTLE_CONTEXT;
+        ctx.rules.base_edict_multiplier = 2;
+        ctx.rules.steward_edict_bonus = 0;
+        ctx.rules.urban_edict_bonus = 3;
+        ctx.set_creature(Player::Me, Creature::Steward);
+
+        assert_eq!(ctx.edict_multiplier(Player::Me), 2);
+        assert_eq!(ctx.edict_multiplier(Player::You), 2);
+
+        ctx.set_battlefield(Battlefield::Urban);
+        assert_eq!(ctx.edict_multiplier(Player::Me), 5);
+    }
+
+    #[test]
+    fn hopeless_surrenders_follow_the_edict_multiplier() {
+        let mut ctx = *BASIC_BATTLE_CONTEXT;
+        ctx.rules = ctx.rules.with_hopeless_surrenders(true);
+        ctx.rules.base_edict_multiplier = 2;
+        ctx.state.battlefields.current = 1;
+
+        // Winning brings us to 11 points. The opponent can still get 6 points from the
+        // remaining battles, and 5 more by riling the public both before and after
+        // the steward hands their edicts back.
+        ctx.state.score = Score(8);
+        let (result, turn) = ctx.advance_known_state();
+        assert_eq!(result, BattleResult::Won);
+        assert_eq!(turn.get_unfinished().unwrap().score, Score(11));
+
+        ctx.state.score = Score(9);
+        assert_eq!(ctx.advance_known_state().1, TurnResult::Finished(Score(12)));
+    }
+
+    #[test]
+    fn strengths_and_rewards_follow_the_rules() {
+        let mut ctx = *BASIC_BATTLE_CONTEXT;
+        assert_eq!(ctx.advance_known_state().0, BattleResult::Won);
+
+        ctx.rules.strengths[Creature::Seer as usize] = 10;
+        ctx.rules.rewards[Battlefield::Plains as usize] = 5;
+
+        assert_eq!(ctx.advance_known_state().0, BattleResult::Lost);
+        assert_eq!(ctx.battle_reward(Player::You), 5);
+    }
+    // }}}
 }
 // }}
@@ -1,8 +1,9 @@
 use crate::{helpers::bitfield::{Bitfield16, Bitfield}, make_bitfield};
+use serde::{Deserialize, Serialize};
 use std::fmt::{self, Display};
 
 /// Different kind of lingering effects affecting a given player
-#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug, Serialize, Deserialize)]
 pub enum StatusEffect {
     // === Effects caused by battlefields:
     // The player gains 1 strength
@@ -26,9 +27,10 @@ pub enum StatusEffect {
 }
 
 impl StatusEffect {
-    pub const STATUS_EFFECTS: [StatusEffect; 6] = [
+    pub const STATUS_EFFECTS: [StatusEffect; 7] = [
         StatusEffect::Mountain,
         StatusEffect::Glade,
+        StatusEffect::Night,
         StatusEffect::Seer,
         StatusEffect::Bard,
         StatusEffect::Mercenary,
@@ -50,3 +52,24 @@ impl From<usize> for StatusEffect {
 
 make_bitfield!(StatusEffectSet, StatusEffect, u8, 7, Bitfield16, true);
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn status_effects_are_listed_in_order() {
+        for (index, effect) in StatusEffect::STATUS_EFFECTS.into_iter().enumerate() {
+            assert_eq!(effect as usize, index);
+            assert_eq!(StatusEffect::from(index), effect);
+        }
+    }
+
+    #[test]
+    fn sets_iterate_over_their_effects() {
+        for effect in StatusEffect::STATUS_EFFECTS {
+            let effects: Vec<_> = StatusEffectSet::singleton(effect).into_iter().collect();
+            assert_eq!(effects, vec![effect]);
+        }
+    }
+}
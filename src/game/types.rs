@@ -1,12 +1,13 @@
 use crate::cfr::decision::Utility;
 use crate::helpers::pair::{conditional_swap, Pair};
+use serde::{Deserialize, Serialize};
 use std::ops::Add;
 use std::ops::Neg;
 use std::ops::Not;
 use std::ops::Sub;
 
 // {{{ Players
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Player {
     Me,  // Current player
     You, // Opponent
@@ -108,7 +109,7 @@ mod player_tests {
 // - Negative => player 2 won
 // - Positive => player 1 won
 // - 0 => draw
-#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug, Default)]
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug, Default, Serialize, Deserialize)]
 pub struct Score(pub i8);
 
 impl Score {
@@ -200,7 +201,7 @@ impl<T> TurnResult<T> {
 }
 // }}}
 // {{{ BattleResult
-#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Serialize, Deserialize)]
 pub enum BattleResult {
     Lost,
     Tied,
@@ -467,6 +467,38 @@ macro_rules! make_bitfield {
             }
         }
         // }}}
+        // {{{ Serde implementations
+        // Sets are serialized as lists of elements, which is
+        // much friendlier than bitmasks for consumers outside rust.
+        impl ::serde::Serialize for $name {
+            fn serialize<S: ::serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.collect_seq(self.into_iter())
+            }
+        }
+
+        impl<'de> ::serde::Deserialize<'de> for $name {
+            fn deserialize<D: ::serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                use crate::helpers::bitfield::Bitfield;
+                use ::serde::de::Error;
+
+                let elements = Vec::<$element>::deserialize(deserializer)?;
+                let mut result = Self::empty();
+
+                for element in elements {
+                    if element as usize >= Self::BITS || result.has(element) {
+                        return Err(D::Error::custom(format!(
+                            "invalid or duplicate element {:?}",
+                            element
+                        )));
+                    }
+
+                    result.insert(element);
+                }
+
+                Ok(result)
+            }
+        }
+        // }}}
         // {{{ Default implementation
         impl Default for $name {
             fn default() -> Self {
@@ -0,0 +1,316 @@
+use super::pair::Pair;
+use super::simplex::{LinearProgram, LpError, Relation};
+
+// {{{ Matrix games
+/// A two player zero-sum game in normal form. The first (row) player
+/// attempts to maximize the payoffs, while the second (column) player
+/// attempts to minimize them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatrixGame {
+    rows: usize,
+    columns: usize,
+    payoffs: Vec<f64>,
+}
+
+/// Mixed strategies for both players of a matrix game,
+/// together with the value they lead to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatrixGameSolution {
+    pub value: f64,
+    pub strategies: Pair<Vec<f64>>,
+}
+
+impl MatrixGame {
+    // {{{ Constructors
+    /// Creates a game where every payoff is zero.
+    pub fn new(rows: usize, columns: usize) -> Self {
+        assert!(rows > 0 && columns > 0, "Both players need a choice");
+
+        Self {
+            rows,
+            columns,
+            payoffs: vec![0.0; rows * columns],
+        }
+    }
+
+    /// Creates a game by computing the payoff of every pair of choices.
+    pub fn from_fn(rows: usize, columns: usize, mut f: impl FnMut(usize, usize) -> f64) -> Self {
+        let mut game = Self::new(rows, columns);
+
+        for row in 0..rows {
+            for column in 0..columns {
+                game.set(row, column, f(row, column));
+            }
+        }
+
+        game
+    }
+    // }}}
+    // {{{ Getters & setters
+    #[inline(always)]
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    #[inline(always)]
+    pub fn columns(&self) -> usize {
+        self.columns
+    }
+
+    #[inline(always)]
+    pub fn get(&self, row: usize, column: usize) -> f64 {
+        self.payoffs[row * self.columns + column]
+    }
+
+    #[inline(always)]
+    pub fn set(&mut self, row: usize, column: usize, payoff: f64) {
+        self.payoffs[row * self.columns + column] = payoff;
+    }
+
+    #[inline(always)]
+    pub fn add(&mut self, row: usize, column: usize, payoff: f64) {
+        self.payoffs[row * self.columns + column] += payoff;
+    }
+
+    /// Returns the game seen from the perspective of the column player.
+    pub fn transpose(&self) -> Self {
+        Self::from_fn(self.columns, self.rows, |row, column| {
+            -self.get(column, row)
+        })
+    }
+    // }}}
+    // {{{ Evaluation
+    /// The expected payoff of each row against some column strategy.
+    pub fn row_values(&self, columns: &[f64]) -> Vec<f64> {
+        (0..self.rows)
+            .map(|row| {
+                (0..self.columns)
+                    .map(|c| self.get(row, c) * columns[c])
+                    .sum()
+            })
+            .collect()
+    }
+
+    /// The expected payoff of each column against some row strategy.
+    pub fn column_values(&self, rows: &[f64]) -> Vec<f64> {
+        (0..self.columns)
+            .map(|column| (0..self.rows).map(|r| self.get(r, column) * rows[r]).sum())
+            .collect()
+    }
+
+    /// The expected payoff of a pair of strategies.
+    pub fn expected_value(&self, strategies: &Pair<Vec<f64>>) -> f64 {
+        self.row_values(&strategies[1])
+            .iter()
+            .zip(&strategies[0])
+            .map(|(value, probability)| value * probability)
+            .sum()
+    }
+
+    /// How much the players could gain by switching to a best response.
+    /// This is zero exactly for equilibria.
+    pub fn exploitability(&self, strategies: &Pair<Vec<f64>>) -> f64 {
+        let best_row = self
+            .row_values(&strategies[1])
+            .into_iter()
+            .fold(f64::NEG_INFINITY, f64::max);
+
+        let best_column = self
+            .column_values(&strategies[0])
+            .into_iter()
+            .fold(f64::INFINITY, f64::min);
+
+        best_row - best_column
+    }
+    // }}}
+    // {{{ Solvers
+    /// Solves the game exactly, falling back to regret matching
+    /// if the linear program runs into numerical trouble.
+    pub fn solve(&self) -> MatrixGameSolution {
+        self.solve_lp()
+            .unwrap_or_else(|_| self.solve_regret_matching(1e-6, 100_000))
+    }
+
+    /// Computes the optimal strategy of the row player by solving:
+    ///
+    /// maximize `v` subject to `v ≤ Σᵢ xᵢ Aᵢⱼ` for every column `j`, `Σᵢ xᵢ = 1`, `x ≥ 0`
+    fn solve_row_player(&self) -> Result<(Vec<f64>, f64), LpError> {
+        let value = self.rows;
+        let mut program = LinearProgram::new(self.rows + 1);
+        program.set_free(value);
+        program.set_objective(value, 1.0);
+
+        for column in 0..self.columns {
+            let mut coefficients: Vec<_> = (0..self.rows)
+                .map(|row| (row, -self.get(row, column)))
+                .collect();
+
+            coefficients.push((value, 1.0));
+            program.add_constraint(coefficients, Relation::LessEqual, 0.0);
+        }
+
+        let all = (0..self.rows).map(|row| (row, 1.0)).collect();
+        program.add_constraint(all, Relation::Equal, 1.0);
+
+        let solution = program.solve()?;
+        let mut strategy = solution.variables;
+        strategy.truncate(self.rows);
+
+        for probability in &mut strategy {
+            *probability = probability.max(0.0);
+        }
+
+        Ok((strategy, solution.value))
+    }
+
+    /// Solves the game exactly using linear programming.
+    pub fn solve_lp(&self) -> Result<MatrixGameSolution, LpError> {
+        let (rows, value) = self.solve_row_player()?;
+        let (columns, _) = self.transpose().solve_row_player()?;
+
+        Ok(MatrixGameSolution {
+            value,
+            strategies: [rows, columns],
+        })
+    }
+
+    /// Approximates a solution using regret matching+, stopping once the
+    /// exploitability of the average strategies drops below the given tolerance.
+    pub fn solve_regret_matching(
+        &self,
+        tolerance: f64,
+        max_iterations: usize,
+    ) -> MatrixGameSolution {
+        let mut regrets = [vec![0.0; self.rows], vec![0.0; self.columns]];
+        let mut sums = regrets.clone();
+        let mut strategies = regrets.clone();
+
+        for iteration in 1..=max_iterations {
+            // Updates alternate between the players, which speeds up convergence
+            for player in 0..2 {
+                strategies[player] = Self::regret_matching(&regrets[player]);
+
+                let values = if player == 0 {
+                    self.row_values(&Self::regret_matching(&regrets[1]))
+                } else {
+                    // The column player minimizes
+                    let values = self.column_values(&strategies[0]);
+                    values.into_iter().map(|value| -value).collect()
+                };
+
+                let current: f64 = values
+                    .iter()
+                    .zip(&strategies[player])
+                    .map(|(value, probability)| value * probability)
+                    .sum();
+
+                for (regret, value) in regrets[player].iter_mut().zip(values) {
+                    *regret = (*regret + value - current).max(0.0);
+                }
+
+                // Later iterations get more weight in the average
+                let strategy = Self::regret_matching(&regrets[player]);
+                for (sum, probability) in sums[player].iter_mut().zip(strategy) {
+                    *sum += iteration as f64 * probability;
+                }
+            }
+
+            if iteration % 100 == 0 || iteration == max_iterations {
+                let average = sums.clone().map(Self::normalized);
+
+                if iteration == max_iterations || self.exploitability(&average) <= tolerance {
+                    return MatrixGameSolution {
+                        value: self.expected_value(&average),
+                        strategies: average,
+                    };
+                }
+            }
+        }
+
+        unreachable!("The last iteration always returns")
+    }
+
+    /// The strategy proportional to the positive regrets.
+    fn regret_matching(regrets: &[f64]) -> Vec<f64> {
+        Self::normalized(regrets.iter().map(|regret| regret.max(0.0)).collect())
+    }
+
+    /// Normalizes some non-negative weights. If all of them
+    /// are zero, every entry is set to 1/size instead.
+    fn normalized(mut weights: Vec<f64>) -> Vec<f64> {
+        let total: f64 = weights.iter().sum();
+        let size = weights.len() as f64;
+
+        for weight in &mut weights {
+            *weight = if total > 0.0 {
+                *weight / total
+            } else {
+                1.0 / size
+            };
+        }
+
+        weights
+    }
+    // }}}
+}
+// }}}
+// {{{ Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rock_paper_scissors() -> MatrixGame {
+        MatrixGame::from_fn(3, 3, |row, column| match (3 + row - column) % 3 {
+            0 => 0.0,
+            1 => 1.0,
+            _ => -1.0,
+        })
+    }
+
+    #[test]
+    fn rock_paper_scissors_is_uniform() {
+        let game = rock_paper_scissors();
+
+        for solution in [
+            game.solve_lp().unwrap(),
+            game.solve_regret_matching(1e-4, 10_000),
+        ] {
+            assert!(solution.value.abs() < 1e-4);
+            assert!(game.exploitability(&solution.strategies) < 1e-4);
+
+            for strategy in &solution.strategies {
+                assert!(strategy.iter().all(|p| (p - 1.0 / 3.0).abs() < 1e-2));
+            }
+        }
+    }
+
+    #[test]
+    fn solvers_agree_on_asymmetric_games() {
+        let game = MatrixGame::from_fn(2, 3, |row, column| {
+            [[3.0, -1.0, 0.5], [-2.0, 4.0, 1.0]][row][column]
+        });
+
+        let exact = game.solve_lp().unwrap();
+        let approximate = game.solve_regret_matching(1e-5, 100_000);
+
+        assert!(game.exploitability(&exact.strategies) < 1e-6);
+        assert!((exact.value - approximate.value).abs() < 1e-3);
+        assert!((exact.value - game.expected_value(&exact.strategies)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn saddle_points_are_pure() {
+        // The first row dominates, and the second column is the best answer to it
+        let game = MatrixGame::from_fn(2, 2, |row, column| [[2.0, 1.0], [0.0, -1.0]][row][column]);
+        let solution = game.solve();
+
+        assert!((solution.value - 1.0).abs() < 1e-6);
+        assert!((solution.strategies[0][0] - 1.0).abs() < 1e-6);
+        assert!((solution.strategies[1][1] - 1.0).abs() < 1e-6);
+
+        // Trivial games work as well
+        let single = MatrixGame::from_fn(1, 3, |_, column| column as f64);
+        assert!(single.solve().value.abs() < 1e-6);
+    }
+}
+// }}}
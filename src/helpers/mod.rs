@@ -7,6 +7,9 @@ pub mod pair;
 pub mod bitfield;
 pub mod ranged;
 pub mod itertools;
+pub mod statistics;
+pub mod simplex;
+pub mod matrix_game;
 
 /// Normalize a vector. If all the values are zero,
 /// all the entries will be set to 1/size.
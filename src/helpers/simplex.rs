@@ -0,0 +1,531 @@
+use std::fmt::{self, Display};
+
+/// Values smaller than this are treated as zero.
+const EPSILON: f64 = 1e-9;
+
+/// Pivots smaller than this are avoided, as they amplify rounding errors.
+const PIVOT_TOLERANCE: f64 = 1e-7;
+
+/// Phase one objectives smaller than this are considered feasible.
+const FEASIBILITY_TOLERANCE: f64 = 1e-7;
+
+// {{{ Linear programs
+/// The relation between the two sides of a constraint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Relation {
+    LessEqual,
+    Equal,
+    GreaterEqual,
+}
+
+impl Relation {
+    fn flip(self) -> Self {
+        match self {
+            Self::LessEqual => Self::GreaterEqual,
+            Self::Equal => Self::Equal,
+            Self::GreaterEqual => Self::LessEqual,
+        }
+    }
+}
+
+/// A linear constraint, with it's (sparse) left hand side given as
+/// a list of `(variable, coefficient)` pairs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Constraint {
+    pub coefficients: Vec<(usize, f64)>,
+    pub relation: Relation,
+    pub rhs: f64,
+}
+
+/// A linear program maximizing a linear objective. Variables are
+/// non-negative, unless explicitly marked as free.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LinearProgram {
+    objective: Vec<f64>,
+    free: Vec<bool>,
+    constraints: Vec<Constraint>,
+}
+
+/// Reasons a linear program might not have an optimal solution.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LpError {
+    Infeasible,
+    Unbounded,
+    /// The solver gave up (most likely because of numerical issues).
+    IterationLimit,
+}
+
+impl Display for LpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Infeasible => write!(f, "The linear program is infeasible"),
+            Self::Unbounded => write!(f, "The linear program is unbounded"),
+            Self::IterationLimit => write!(f, "The simplex method did not converge"),
+        }
+    }
+}
+
+impl std::error::Error for LpError {}
+
+/// An optimal solution of a linear program.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LpSolution {
+    pub value: f64,
+    pub variables: Vec<f64>,
+}
+
+impl LinearProgram {
+    /// Creates a program with the given number of variables,
+    /// and an objective which is constantly zero.
+    pub fn new(variables: usize) -> Self {
+        Self {
+            objective: vec![0.0; variables],
+            free: vec![false; variables],
+            constraints: Vec::new(),
+        }
+    }
+
+    #[inline(always)]
+    pub fn variable_count(&self) -> usize {
+        self.objective.len()
+    }
+
+    /// Sets the coefficient of a variable in the objective to maximize.
+    pub fn set_objective(&mut self, variable: usize, coefficient: f64) {
+        self.objective[variable] = coefficient;
+    }
+
+    /// Allows a variable to take negative values.
+    pub fn set_free(&mut self, variable: usize) {
+        self.free[variable] = true;
+    }
+
+    pub fn add_constraint(
+        &mut self,
+        coefficients: Vec<(usize, f64)>,
+        relation: Relation,
+        rhs: f64,
+    ) {
+        debug_assert!(coefficients
+            .iter()
+            .all(|(variable, _)| *variable < self.variable_count()));
+
+        self.constraints.push(Constraint {
+            coefficients,
+            relation,
+            rhs,
+        });
+    }
+
+    /// Solves the program using the two phase simplex method.
+    pub fn solve(&self) -> Result<LpSolution, LpError> {
+        // {{{ Column layout
+        // Free variables are split into a positive and a negative part
+        let mut columns = Vec::with_capacity(self.variable_count());
+        let mut width = 0;
+
+        for &free in &self.free {
+            let negative = free.then_some(width + 1);
+            columns.push((width, negative));
+            width += if free { 2 } else { 1 };
+        }
+
+        let structural = width;
+
+        // Constraints are normalized to have non-negative right hand sides
+        let relations: Vec<_> = self
+            .constraints
+            .iter()
+            .map(|constraint| {
+                if constraint.rhs < 0.0 {
+                    constraint.relation.flip()
+                } else {
+                    constraint.relation
+                }
+            })
+            .collect();
+
+        let slacks = relations
+            .iter()
+            .filter(|&&relation| relation != Relation::Equal)
+            .count();
+
+        let artificial_start = structural + slacks;
+        let artificials = relations
+            .iter()
+            .filter(|&&relation| relation != Relation::LessEqual)
+            .count();
+
+        width = artificial_start + artificials;
+        // }}}
+        // {{{ Initial tableau
+        let mut tableau = Tableau::new(self.constraints.len(), width);
+        let (mut slack, mut artificial) = (structural, artificial_start);
+
+        for (row, (constraint, relation)) in self.constraints.iter().zip(&relations).enumerate() {
+            let sign = if constraint.rhs < 0.0 { -1.0 } else { 1.0 };
+
+            for &(variable, coefficient) in &constraint.coefficients {
+                let (positive, negative) = columns[variable];
+                *tableau.at(row, positive) += sign * coefficient;
+
+                if let Some(negative) = negative {
+                    *tableau.at(row, negative) -= sign * coefficient;
+                }
+            }
+
+            *tableau.rhs(row) = sign * constraint.rhs;
+
+            match relation {
+                Relation::LessEqual => {
+                    *tableau.at(row, slack) = 1.0;
+                    tableau.basis[row] = slack;
+                    slack += 1;
+                }
+                Relation::GreaterEqual => {
+                    *tableau.at(row, slack) = -1.0;
+                    *tableau.at(row, artificial) = 1.0;
+                    tableau.basis[row] = artificial;
+                    slack += 1;
+                    artificial += 1;
+                }
+                Relation::Equal => {
+                    *tableau.at(row, artificial) = 1.0;
+                    tableau.basis[row] = artificial;
+                    artificial += 1;
+                }
+            }
+        }
+        // }}}
+        // {{{ Phase one: find a feasible solution
+        let mut costs = vec![0.0; width];
+        for cost in &mut costs[artificial_start..] {
+            *cost = -1.0;
+        }
+
+        tableau.set_objective(&costs);
+        tableau.optimize(|_| true)?;
+
+        if tableau.objective_value() < -FEASIBILITY_TOLERANCE {
+            return Err(LpError::Infeasible);
+        }
+
+        // Pivot the remaining (zero valued) artificial variables out of the basis.
+        // Rows where this is impossible are redundant, and can be safely ignored.
+        for row in 0..tableau.rows {
+            if tableau.basis[row] >= artificial_start {
+                if let Some(column) =
+                    (0..artificial_start).find(|&column| tableau.at(row, column).abs() > EPSILON)
+                {
+                    tableau.pivot(row, column);
+                }
+            }
+        }
+        // }}}
+        // {{{ Phase two: optimize the actual objective
+        let mut costs = vec![0.0; width];
+        for (&(positive, negative), &coefficient) in columns.iter().zip(&self.objective) {
+            costs[positive] = coefficient;
+
+            if let Some(negative) = negative {
+                costs[negative] = -coefficient;
+            }
+        }
+
+        tableau.set_objective(&costs);
+        tableau.optimize(|column| column < artificial_start)?;
+        // }}}
+        // {{{ Extract solution
+        let mut values = vec![0.0; width];
+        for row in 0..tableau.rows {
+            values[tableau.basis[row]] = *tableau.rhs(row);
+        }
+
+        let variables: Vec<f64> = columns
+            .iter()
+            .map(|&(positive, negative)| values[positive] - negative.map_or(0.0, |n| values[n]))
+            .collect();
+
+        let value = variables
+            .iter()
+            .zip(&self.objective)
+            .map(|(value, coefficient)| value * coefficient)
+            .sum();
+
+        Ok(LpSolution { value, variables })
+        // }}}
+    }
+}
+// }}}
+// {{{ Tableau
+/// A dense simplex tableau. The last row holds the reduced costs of the
+/// objective, and the last column holds the right hand sides.
+struct Tableau {
+    rows: usize,
+    width: usize,
+    data: Vec<f64>,
+    basis: Vec<usize>,
+}
+
+impl Tableau {
+    fn new(rows: usize, columns: usize) -> Self {
+        let width = columns + 1;
+
+        Self {
+            rows,
+            width,
+            data: vec![0.0; (rows + 1) * width],
+            basis: vec![0; rows],
+        }
+    }
+
+    #[inline(always)]
+    fn at(&mut self, row: usize, column: usize) -> &mut f64 {
+        &mut self.data[row * self.width + column]
+    }
+
+    #[inline(always)]
+    fn rhs(&mut self, row: usize) -> &mut f64 {
+        self.at(row, self.width - 1)
+    }
+
+    #[inline(always)]
+    fn objective_value(&mut self) -> f64 {
+        *self.rhs(self.rows)
+    }
+
+    /// Replaces the objective row with the given costs (to be maximized),
+    /// expressed in terms of the non-basic variables.
+    fn set_objective(&mut self, costs: &[f64]) {
+        let objective = self.rows * self.width;
+
+        for (column, cost) in costs.iter().enumerate() {
+            self.data[objective + column] = -cost;
+        }
+
+        self.data[objective + self.width - 1] = 0.0;
+
+        for row in 0..self.rows {
+            let factor = self.data[objective + self.basis[row]];
+
+            if factor != 0.0 {
+                let columns = self.nonzero_columns(row);
+                self.subtract_row(row, &columns, self.rows, factor);
+            }
+        }
+    }
+
+    /// The columns where the given row has non-zero entries.
+    fn nonzero_columns(&self, row: usize) -> Vec<usize> {
+        let start = row * self.width;
+
+        (0..self.width)
+            .filter(|&column| self.data[start + column] != 0.0)
+            .collect()
+    }
+
+    /// Subtracts a multiple of the source row from the target row. Only the
+    /// given columns (where the source row is non-zero) are touched.
+    fn subtract_row(&mut self, source: usize, columns: &[usize], target: usize, factor: f64) {
+        let (source, target) = (source * self.width, target * self.width);
+
+        for &column in columns {
+            let result = self.data[target + column] - factor * self.data[source + column];
+
+            // Flushing rounding errors to zero keeps them from piling up
+            self.data[target + column] = if result.abs() < EPSILON { 0.0 } else { result };
+        }
+    }
+
+    fn pivot(&mut self, row: usize, column: usize) {
+        let start = row * self.width;
+        let pivot = self.data[start + column];
+
+        for value in &mut self.data[start..start + self.width] {
+            *value /= pivot;
+        }
+
+        // Tableaus for games tend to be sparse, so this saves a lot of work
+        let columns = self.nonzero_columns(row);
+
+        for other in 0..=self.rows {
+            if other != row {
+                let factor = self.data[other * self.width + column];
+
+                if factor.abs() > EPSILON {
+                    self.subtract_row(row, &columns, other, factor);
+                }
+
+                self.data[other * self.width + column] = 0.0;
+            }
+        }
+
+        self.basis[row] = column;
+    }
+
+    /// Runs the simplex method on the current objective, only
+    /// considering columns accepted by the given predicate.
+    fn optimize(&mut self, allowed: impl Fn(usize) -> bool) -> Result<(), LpError> {
+        let objective = self.rows * self.width;
+        let max_iterations = 100 * (self.rows + self.width);
+
+        // After this many pivots which do not improve the objective, we switch to
+        // Bland's rule, which cannot cycle, but is slower and numerically fragile.
+        let max_degenerate_streak = self.rows + self.width;
+        let mut degenerate_streak = 0;
+
+        for _ in 0..max_iterations {
+            // {{{ Pick entering column
+            let candidates = (0..self.width - 1)
+                .filter(|&column| allowed(column) && self.data[objective + column] < -EPSILON);
+
+            let bland = degenerate_streak >= max_degenerate_streak;
+            let entering = if bland {
+                candidates.min()
+            } else {
+                candidates
+                    .min_by(|&a, &b| self.data[objective + a].total_cmp(&self.data[objective + b]))
+            };
+
+            let Some(entering) = entering else {
+                return Ok(());
+            };
+            // }}}
+            // {{{ Ratio test
+            let coefficient = |row: usize| self.data[row * self.width + entering];
+            let ratio = |row: usize| {
+                // Rounding errors can make the right hand side slightly negative
+                self.data[row * self.width + self.width - 1].max(0.0) / coefficient(row)
+            };
+
+            let rows = (0..self.rows).filter(|&row| coefficient(row) > PIVOT_TOLERANCE);
+
+            let leaving = if bland {
+                // Ties are broken in favour of the smallest basic variable
+                rows.min_by(|&a, &b| {
+                    let (ratio_a, ratio_b) = (ratio(a), ratio(b));
+
+                    if (ratio_a - ratio_b).abs() < EPSILON {
+                        self.basis[a].cmp(&self.basis[b])
+                    } else {
+                        ratio_a.total_cmp(&ratio_b)
+                    }
+                })
+            } else {
+                // Harris' ratio test: out of the rows which (almost) limit the step
+                // the most, we pick the one with the biggest (most stable) pivot.
+                let bound = rows
+                    .clone()
+                    .map(|row| {
+                        (self.data[row * self.width + self.width - 1].max(0.0) + EPSILON)
+                            / coefficient(row)
+                    })
+                    .fold(f64::INFINITY, f64::min);
+
+                rows.filter(|&row| ratio(row) <= bound)
+                    .max_by(|&a, &b| coefficient(a).total_cmp(&coefficient(b)))
+            };
+
+            let Some(leaving) = leaving else {
+                return Err(LpError::Unbounded);
+            };
+
+            let ratio = ratio(leaving);
+            // }}}
+
+            if ratio.abs() < EPSILON {
+                degenerate_streak += 1;
+            } else {
+                degenerate_streak = 0;
+            }
+
+            self.pivot(leaving, entering);
+        }
+
+        Err(LpError::IterationLimit)
+    }
+}
+// }}}
+// {{{ Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-6, "{a} is not close to {b}");
+    }
+
+    #[test]
+    fn textbook_program() {
+        // maximize 3x + 5y subject to x ≤ 4, 2y ≤ 12, 3x + 2y ≤ 18
+        let mut program = LinearProgram::new(2);
+        program.set_objective(0, 3.0);
+        program.set_objective(1, 5.0);
+        program.add_constraint(vec![(0, 1.0)], Relation::LessEqual, 4.0);
+        program.add_constraint(vec![(1, 2.0)], Relation::LessEqual, 12.0);
+        program.add_constraint(vec![(0, 3.0), (1, 2.0)], Relation::LessEqual, 18.0);
+
+        let solution = program.solve().unwrap();
+        assert_close(solution.value, 36.0);
+        assert_close(solution.variables[0], 2.0);
+        assert_close(solution.variables[1], 6.0);
+    }
+
+    #[test]
+    fn equalities_and_free_variables() {
+        // maximize -x - y subject to x - y = -3, x + y ≥ 1, with x free
+        let mut program = LinearProgram::new(2);
+        program.set_free(0);
+        program.set_objective(0, -1.0);
+        program.set_objective(1, -1.0);
+        program.add_constraint(vec![(0, 1.0), (1, -1.0)], Relation::Equal, -3.0);
+        program.add_constraint(vec![(0, 1.0), (1, 1.0)], Relation::GreaterEqual, 1.0);
+
+        let solution = program.solve().unwrap();
+        assert_close(solution.value, -1.0);
+        assert_close(solution.variables[0], -1.0);
+        assert_close(solution.variables[1], 2.0);
+    }
+
+    #[test]
+    fn matching_pennies() {
+        // The value v of the game, and the probability p of playing heads:
+        // maximize v subject to v ≤ p - (1 - p), v ≤ (1 - p) - p, p ≤ 1
+        let mut program = LinearProgram::new(2);
+        program.set_free(0);
+        program.set_objective(0, 1.0);
+        program.add_constraint(vec![(0, 1.0), (1, -2.0)], Relation::LessEqual, -1.0);
+        program.add_constraint(vec![(0, 1.0), (1, 2.0)], Relation::LessEqual, 1.0);
+        program.add_constraint(vec![(1, 1.0)], Relation::LessEqual, 1.0);
+
+        let solution = program.solve().unwrap();
+        assert_close(solution.value, 0.0);
+        assert_close(solution.variables[1], 0.5);
+    }
+
+    #[test]
+    fn infeasible_and_unbounded_programs() {
+        let mut program = LinearProgram::new(1);
+        program.add_constraint(vec![(0, 1.0)], Relation::GreaterEqual, 2.0);
+        program.add_constraint(vec![(0, 1.0)], Relation::LessEqual, 1.0);
+        assert_eq!(program.solve(), Err(LpError::Infeasible));
+
+        let mut program = LinearProgram::new(2);
+        program.set_objective(0, 1.0);
+        program.add_constraint(vec![(0, 1.0), (1, -1.0)], Relation::LessEqual, 1.0);
+        assert_eq!(program.solve(), Err(LpError::Unbounded));
+    }
+
+    #[test]
+    fn redundant_equalities() {
+        // The second constraint is a multiple of the first one
+        let mut program = LinearProgram::new(2);
+        program.set_objective(0, 1.0);
+        program.add_constraint(vec![(0, 1.0), (1, 1.0)], Relation::Equal, 1.0);
+        program.add_constraint(vec![(0, 2.0), (1, 2.0)], Relation::Equal, 2.0);
+
+        let solution = program.solve().unwrap();
+        assert_close(solution.value, 1.0);
+    }
+}
+// }}}
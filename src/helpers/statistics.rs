@@ -0,0 +1,128 @@
+/// The z-score of a two sided 95% confidence interval.
+pub const Z_95: f64 = 1.959964;
+
+// {{{ Running statistics
+/// Keeps track of the mean and variance of a stream of samples
+/// (using Welford's algorithm).
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RunningStats {
+    count: usize,
+    mean: f64,
+    /// Sum of squared differences from the mean.
+    m2: f64,
+}
+
+impl RunningStats {
+    #[inline(always)]
+    pub fn push(&mut self, sample: f64) {
+        self.count += 1;
+
+        let delta = sample - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (sample - self.mean);
+    }
+
+    #[inline(always)]
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    #[inline(always)]
+    pub fn mean(&self) -> f64 {
+        self.mean
+    }
+
+    /// The unbiased sample variance.
+    pub fn variance(&self) -> f64 {
+        if self.count < 2 {
+            0.0
+        } else {
+            self.m2 / (self.count - 1) as f64
+        }
+    }
+
+    /// The standard error of the mean.
+    pub fn standard_error(&self) -> f64 {
+        if self.count == 0 {
+            0.0
+        } else {
+            (self.variance() / self.count as f64).sqrt()
+        }
+    }
+
+    /// Half the width of the 95% confidence interval of the mean
+    /// (using the normal approximation).
+    #[inline(always)]
+    pub fn confidence_radius(&self) -> f64 {
+        Z_95 * self.standard_error()
+    }
+}
+
+impl FromIterator<f64> for RunningStats {
+    fn from_iter<T: IntoIterator<Item = f64>>(iter: T) -> Self {
+        let mut result = Self::default();
+
+        for sample in iter {
+            result.push(sample);
+        }
+
+        result
+    }
+}
+// }}}
+// {{{ Proportions
+/// Computes the 95% Wilson score interval for a proportion.
+/// Behaves much better than the normal approximation for
+/// proportions close to 0 or 1.
+pub fn wilson_interval(successes: f64, trials: f64) -> (f64, f64) {
+    if trials <= 0.0 {
+        return (0.0, 1.0);
+    }
+
+    let z2 = Z_95 * Z_95;
+    let proportion = successes / trials;
+    let denominator = 1.0 + z2 / trials;
+    let center = (proportion + z2 / (2.0 * trials)) / denominator;
+    let radius = Z_95
+        * (proportion * (1.0 - proportion) / trials + z2 / (4.0 * trials * trials)).sqrt()
+        / denominator;
+
+    // The bounds are exactly 0 or 1 at the extremes,
+    // but rounding errors might push them slightly past the proportion.
+    let low = if successes <= 0.0 { 0.0 } else { center - radius };
+    let high = if successes >= trials { 1.0 } else { center + radius };
+
+    (low.max(0.0), high.min(1.0))
+}
+// }}}
+// {{{ Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn running_stats_match_direct_computation() {
+        let samples = [3.0, -1.0, 4.0, 1.0, -5.0, 9.0, 2.0, 6.0];
+        let stats: RunningStats = samples.into_iter().collect();
+
+        let mean = samples.iter().sum::<f64>() / samples.len() as f64;
+        let variance =
+            samples.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (samples.len() - 1) as f64;
+
+        assert_eq!(stats.count(), samples.len());
+        assert!((stats.mean() - mean).abs() < 1e-9);
+        assert!((stats.variance() - variance).abs() < 1e-9);
+    }
+
+    #[test]
+    fn wilson_interval_contains_proportion() {
+        for (successes, trials) in [(0.0, 10.0), (5.0, 10.0), (10.0, 10.0), (37.0, 1000.0)] {
+            let (low, high) = wilson_interval(successes, trials);
+            let proportion = successes / trials;
+
+            assert!(low <= proportion && proportion <= high);
+            assert!(0.0 <= low && high <= 1.0);
+        }
+    }
+}
+// }}}
@@ -2,31 +2,125 @@
 
 use bumpalo::Bump;
 use echo::ai::always_zero_agent::AlwaysZeroAgent;
-use echo::ai::echo_ai::EchoRunner;
+use echo::ai::cfr_agent::{ActionSelection, CfrAgent, Fallback};
+use echo::ai::duplicate::DuplicateRunner;
+use echo::ai::echo_ai::{EchoAgent, EchoRunner};
+use echo::ai::endgame_agent::EndgameAgent;
+use echo::ai::engine::{serve, EngineAgent};
+use echo::ai::exploit_agent::{ExploitAgent, ExploitSettings};
+use echo::ai::game_record::{parse_named, parse_pair, parse_set};
+use echo::ai::greedy_agent::GreedyAgent;
 use echo::ai::human_player::GUIApp;
 use echo::ai::human_player::HumanAgent;
+use echo::ai::ismcts_agent::{Bandit, IsmctsAgent};
+use echo::ai::match_runner::{Deal, MatchReport, MatchRunner};
+use echo::ai::network::{connect, GameServer};
+use echo::ai::policy_export::{export_policy, ExportFormat};
 use echo::ai::random_agent::RandomAgent;
-use echo::cfr::decision_index::DecisionIndex;
+use echo::ai::replay::{parse_records, verify};
+use echo::ai::strategy_diff::diff_strategies;
+use echo::ai::strategy_query::query_strategy;
+use echo::ai::terminal_agent::TerminalAgent;
+use echo::ai::tournament::{AgentFactory, Tournament, TournamentResults};
+use echo::cfr::decision::Scope;
+use echo::cfr::endgame::EndgameSolver;
 use echo::cfr::generate::EstimationContext;
 use echo::cfr::generate::GenerationContext;
-use echo::cfr::hidden_index::HiddenIndex;
-use echo::cfr::hidden_index::PerPhaseInfo;
-use echo::cfr::phase::Phase;
+use echo::cfr::hidden_index::HiddenState;
+use echo::cfr::last_turn::LastTurnSolver;
+use echo::cfr::phase::{MainPhase, PerPhase, Phase};
+use echo::cfr::scope_cursor::ScopeCursor;
 use echo::cfr::train::TrainingContext;
 use echo::game::battlefield::Battlefield;
 use echo::game::creature::Creature;
 use echo::game::edict::Edict;
 use echo::game::known_state::KnownState;
 use echo::game::known_state_summary::KnownStateEssentials;
-use echo::game::types::Player;
+use echo::game::notation::Position;
+use echo::game::rules::RuleSet;
+use echo::game::types::{Player, Score};
 use echo::helpers::bitfield::Bitfield;
-use rand::thread_rng;
-use std::println;
+use itertools::Itertools;
+use rand::rngs::StdRng;
+use rand::{thread_rng, Rng, SeedableRng};
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::process::Command;
+use std::str::FromStr;
 use std::thread;
 use std::time::Instant;
 use tracing::Level;
 use tracing_subscriber::prelude::*;
 
+const USAGE: &str = "\
+Usage: echo <command> [arguments] [--option value]...
+
+Commands:
+  estimate              Estimate the size of the game tree
+  generate              Generate the game tree, reporting memory usage
+  train                 Generate and train a blueprint
+  evaluate              Train a blueprint, then play it against another agent
+  diff                  Train two blueprints, and compare their strategies
+  play <gui|terminal>   Play against an agent
+  match                 Play two agents against each other
+  tournament            Play a round robin between a set of agents
+  engine                Expose an agent over stdin/stdout
+  host                  Host games over the network
+  connect               Connect a seat to a game server
+  verify <path>         Replay a file of game records
+  convert [path]        Convert a file of game records (or a --position) to JSON
+  help                  Print this message
+
+Position options (estimate, generate, train, evaluate):
+  --battlefields A,B,C,D   Battlefields to play on [default: Plains,Plains,Plains,LastStrand]
+  --turn N                 Turn to start from [default: 0]
+  --score N                Score at the start of the turn [default: 0]
+  --graveyard A,B,...      Creatures already played [default: the first 2N creatures]
+  --edicts A,B|C,D         Edicts still in each player's hand [default: all but the first N]
+  --position NOTATION      The entire position in compact notation (see `Position`),
+                           overriding all of the above
+  --rules PATH             JSON file describing a variant of the rules (see `RuleSet`),
+                           also accepted by every command playing games [default: the
+                           printed rules]
+
+Tree options (estimate, generate, train, evaluate):
+  --depth N                Number of turns to generate [default: until the end of the game]
+  --memory MB              Allocator budget [default: 4096]
+
+Training options (train, evaluate):
+  --trainer cfr|cs-cfr     Training algorithm [default: cfr]
+  --iterations N           Training iterations [default: 1000]
+  --pruning                Enable regret based pruning
+  --output PATH            Write the strategy at the root of the tree to a file (train)
+  --export PATH            Write the policy table of the whole tree to a file (train)
+  --format csv|jsonl       Format of the policy table [default: csv]
+
+Diff options (diff):
+  --other-trainer, --other-iterations, --other-seed
+                           Settings of the second blueprint [default: same as the first]
+  --other-pruning          Enable regret based pruning for the second blueprint
+  --top N                  Number of infosets with the largest divergence to show [default: 10]
+
+Agents are given as one of random, zero, greedy, ismcts[:iterations], exploit
+or engine:<command>. Prefixing an agent with endgame: (e.g. endgame:ismcts:500)
+solves the last battlefield to equilibrium using CFR+ and leaves the rest of the
+game to the agent, which defaults to greedy. The lastturn: prefix solves the last
+battlefield exactly using linear programming instead. Other options:
+  --opponent AGENT         Opponent for evaluate, play and host [default: greedy]
+  --first AGENT            First agent of a match [default: greedy]
+  --second AGENT           Second agent of a match [default: random]
+  --agent AGENT            Agent for engine and connect [default: greedy, gui for connect]
+  --agents A,B,...         Tournament entrants [default: random,zero,greedy]
+  --games N                Number of games (or deals) to play [default: 1000, 200 for
+                           tournaments, 1 when hosting]
+  --duplicate              Play every deal from both seats (match)
+  --seed N                 Seed for dealing and agents [default: 0]
+  --output PATH            Tournament results, reused across runs [default: tournament.txt],
+                           or where to write converted JSON [default: stdout]
+  --address HOST:PORT      Address to host on or connect to [default: 127.0.0.1:7878]
+  --remote N               Number of remote seats when hosting [default: 2]
+";
+
 // {{{ Dumb size conversion functions
 fn mb_to_b(mb: usize) -> usize {
     mb * 1024 * 1024
@@ -44,169 +138,810 @@ fn b_to_gb(b: usize) -> usize {
     b_to_mb(b) / 1024
 }
 // }}}
-// {{{ Simple generation/estimating routine
-fn simple_generation(from: usize, turns: usize, generate: bool) {
-    let start = Instant::now();
-    let capacity = mb_to_b(4096);
-    let allocator = Bump::with_capacity(2500);
-    allocator.set_allocation_limit(Some(capacity));
-    let allocation_duration = start.elapsed();
+// {{{ Argument parsing
+/// The arguments given to a subcommand.
+///
+/// Options are consumed as they get read, so any
+/// options left over at the end must be mistakes.
+struct Arguments {
+    positional: Vec<String>,
+    options: HashMap<String, Option<String>>,
+}
 
-    println!("Performance:");
-    println!("Allocation: {:?}", allocation_duration);
+impl Arguments {
+    fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut args = args.into_iter().peekable();
+        let mut positional = Vec::new();
+        let mut options = HashMap::new();
 
-    let start = Instant::now();
-    let mut state = KnownState::new_starting([Battlefield::Plains; 4]);
-    state.battlefields.all[3] = Battlefield::LastStrand;
-    state.battlefields.current = from;
+        while let Some(arg) = args.next() {
+            let Some(name) = arg.strip_prefix("--") else {
+                positional.push(arg);
+                continue;
+            };
 
-    for i in 0..from {
-        state.graveyard.insert(Creature::CREATURES[2 * i]);
-        state.graveyard.insert(Creature::CREATURES[2 * i + 1]);
+            let value = args.next_if(|value| !value.starts_with("--"));
+            if options.insert(name.to_string(), value).is_some() {
+                return Err(format!("Option --{name} given more than once"));
+            }
+        }
+
+        Ok(Self {
+            positional,
+            options,
+        })
     }
 
-    for state in state.player_states.iter_mut() {
-        for edict in Edict::EDICTS.into_iter().take(from) {
-            state.edicts.remove(edict);
+    fn positional(&self, index: usize, name: &str) -> Result<&str, String> {
+        self.positional
+            .get(index)
+            .map(String::as_str)
+            .ok_or_else(|| format!("Missing argument <{name}>"))
+    }
+
+    /// Returns the raw value of an option, if it was given.
+    fn raw(&mut self, name: &str) -> Result<Option<String>, String> {
+        match self.options.remove(name) {
+            None => Ok(None),
+            Some(Some(value)) => Ok(Some(value)),
+            Some(None) => Err(format!("Option --{name} requires a value")),
         }
     }
 
-    let generator = GenerationContext::new(turns, state, &allocator);
-    let estimator = EstimationContext::new(turns, state);
-    let state_init_duration = start.elapsed();
+    fn optional<T: FromStr>(&mut self, name: &str) -> Result<Option<T>, String> {
+        self.raw(name)?
+            .map(|value| {
+                value
+                    .parse()
+                    .map_err(|_| format!("Invalid value {value:?} for --{name}"))
+            })
+            .transpose()
+    }
 
-    println!("State init: {:?}", state_init_duration);
+    fn get<T: FromStr>(&mut self, name: &str, default: T) -> Result<T, String> {
+        Ok(self.optional(name)?.unwrap_or(default))
+    }
 
-    let start = Instant::now();
-    let stats = estimator.estimate();
-    let estimation_duration = start.elapsed();
+    fn flag(&mut self, name: &str) -> Result<bool, String> {
+        match self.options.remove(name) {
+            None => Ok(false),
+            Some(None) => Ok(true),
+            Some(Some(value)) => Err(format!("Flag --{name} does not take a value ({value:?})")),
+        }
+    }
+
+    /// Errors out on options which were never read.
+    fn finish(self) -> Result<(), String> {
+        match self.options.keys().sorted().next() {
+            Some(name) => Err(format!("Unknown option --{name}")),
+            None => Ok(()),
+        }
+    }
+}
+// }}}
+// {{{ Positions
+/// Reads the rules to play under from the JSON file given by the `rules` option.
+fn parse_rules(args: &mut Arguments) -> Result<RuleSet, String> {
+    let Some(path) = args.raw("rules")? else {
+        return Ok(RuleSet::default());
+    };
+
+    let contents = std::fs::read_to_string(&path).map_err(|error| format!("{path}: {error}"))?;
+    serde_json::from_str(&contents).map_err(|error| format!("{path}: {error}"))
+}
+
+/// The position to generate or train from, together with the tree settings.
+struct PositionOptions {
+    state: KnownState,
+    rules: RuleSet,
+    depth: usize,
+    memory: usize,
+}
+
+impl PositionOptions {
+    /// Builds a state out of the individual position options.
+    fn parse_state(args: &mut Arguments) -> Result<KnownState, String> {
+        let battlefields = match args.raw("battlefields")? {
+            None => [
+                Battlefield::Plains,
+                Battlefield::Plains,
+                Battlefield::Plains,
+                Battlefield::LastStrand,
+            ],
+            Some(input) => input
+                .split(',')
+                .map(|name| parse_named(name, &Battlefield::BATTLEFIELDS))
+                .collect::<Result<Vec<_>, _>>()?
+                .try_into()
+                .map_err(|_| format!("Expected 4 battlefields, got {input:?}"))?,
+        };
+
+        let turn = args.get("turn", 0)?;
+        if turn >= 4 {
+            return Err(format!("Invalid turn {turn}, expected a number below 4"));
+        }
+
+        let mut state = KnownState::new_starting(battlefields);
+        state.battlefields.current = turn;
+        state.score = Score(args.get("score", 0)?);
+
+        // By default, creatures and edicts are assumed to be played in order
+        match args.raw("graveyard")? {
+            Some(input) => state.graveyard = parse_set(&input, &Creature::CREATURES)?,
+            None => {
+                for creature in Creature::CREATURES.into_iter().take(2 * turn) {
+                    state.graveyard.insert(creature);
+                }
+            }
+        }
+
+        match args.raw("edicts")? {
+            Some(input) => {
+                let edicts = parse_pair(&input, |input| parse_set(input, &Edict::EDICTS))?;
+                for (player_state, edicts) in state.player_states.iter_mut().zip(edicts) {
+                    player_state.edicts = edicts;
+                }
+            }
+            None => {
+                for player_state in state.player_states.iter_mut() {
+                    for edict in Edict::EDICTS.into_iter().take(turn) {
+                        player_state.edicts.remove(edict);
+                    }
+                }
+            }
+        }
+
+        Ok(state)
+    }
+
+    fn parse(args: &mut Arguments) -> Result<Self, String> {
+        let state = match args.raw("position")? {
+            Some(input) => {
+                Position::parse(&input)
+                    .map_err(|error| error.to_string())?
+                    .state
+            }
+            None => Self::parse_state(args)?,
+        };
+
+        let turn = state.battlefields.current;
+        if state.hand_size() * 2 > (!state.graveyard).len() {
+            return Err(format!(
+                "Not enough creatures left outside the graveyard for turn {turn}"
+            ));
+        }
+
+        Ok(Self {
+            state,
+            rules: parse_rules(args)?,
+            depth: args.get("depth", 4 - turn)?,
+            memory: args.get("memory", 4096)?,
+        })
+    }
+
+    /// The rules trees get generated with. Hopeless games
+    /// are cut short, which keeps the trees a lot smaller.
+    fn tree_rules(&self) -> RuleSet {
+        self.rules.with_hopeless_surrenders(true)
+    }
+
+    fn allocator(&self) -> Bump {
+        let allocator = Bump::new();
+        allocator.set_allocation_limit(Some(mb_to_b(self.memory)));
+        allocator
+    }
+
+    fn generate<'a>(&self, allocator: &'a Bump) -> Scope<'a> {
+        let start = Instant::now();
+        let scope =
+            GenerationContext::new(self.depth, self.state, self.tree_rules(), allocator).generate();
+        println!("Generation: {:?}", start.elapsed());
+        println!("Allocated: {}MB", b_to_mb(allocator.allocated_bytes()));
+        scope
+    }
+}
 
-    println!("Estimation: {:?}", estimation_duration);
+/// The algorithm used for training blueprints.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Trainer {
+    Cfr,
+    ChanceSampling,
+}
+
+impl FromStr for Trainer {
+    type Err = ();
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input {
+            "cfr" => Ok(Self::Cfr),
+            "cs-cfr" => Ok(Self::ChanceSampling),
+            _ => Err(()),
+        }
+    }
+}
+
+struct TrainingOptions {
+    trainer: Trainer,
+    iterations: usize,
+    pruning: bool,
+    seed: u64,
+}
+
+impl TrainingOptions {
+    fn parse(args: &mut Arguments) -> Result<Self, String> {
+        Ok(Self {
+            trainer: args.get("trainer", Trainer::Cfr)?,
+            iterations: args.get("iterations", 1000)?,
+            pruning: args.flag("pruning")?,
+            seed: args.get("seed", 0)?,
+        })
+    }
 
-    if generate {
+    /// Parses settings for a second blueprint (like `--other-iterations`),
+    /// defaulting to the current settings.
+    fn parse_other(&self, args: &mut Arguments) -> Result<Self, String> {
+        Ok(Self {
+            trainer: args.get("other-trainer", self.trainer)?,
+            iterations: args.get("other-iterations", self.iterations)?,
+            pruning: args.flag("other-pruning")?,
+            seed: args.get("other-seed", self.seed)?,
+        })
+    }
+
+    fn train(&self, scope: &mut Scope, state: &KnownState) {
         let start = Instant::now();
-        generator.generate();
-        let generation_duration = start.elapsed();
+        let ctx = TrainingContext::new(self.pruning);
+
+        match self.trainer {
+            Trainer::Cfr => ctx.cfr(scope, state.to_summary(), self.iterations),
+            Trainer::ChanceSampling => {
+                let mut rng = StdRng::seed_from_u64(self.seed);
+                ctx.cs_cfr(&mut rng, scope, state.to_summary(), self.iterations);
+            }
+        }
+
+        println!("Training: {:?}", start.elapsed());
+    }
+}
+// }}}
+// {{{ Agents
+fn boxed<A: EchoAgent + 'static>(factory: impl Fn(u64) -> A + Sync + 'static) -> AgentFactory {
+    Box::new(move |seed| Box::new(factory(seed)))
+}
 
-        println!("Generation: {:?}", generation_duration);
+/// Builds an agent factory from a description like `greedy` or `ismcts:1000`.
+/// Agents get told about the rules the game is played under (engines are not
+/// though, as the protocol has no way of describing variants).
+fn agent_factory(spec: &str, rules: RuleSet) -> Result<AgentFactory, String> {
+    let (name, parameter) = match spec.split_once(':') {
+        Some((name, parameter)) => (name, Some(parameter)),
+        None => (spec, None),
     };
 
-    println!("\nAllocation stats:");
-    println!("Allocated: {:?}MB", b_to_mb(allocator.allocated_bytes()));
+    let factory = match (name, parameter) {
+        ("random", None) => boxed(|seed| RandomAgent::new(StdRng::seed_from_u64(seed))),
+        ("zero", None) => boxed(|_| AlwaysZeroAgent::default()),
+        ("greedy", None) => boxed(move |_| GreedyAgent::default().with_rules(rules)),
+        ("exploit", None) => boxed(move |seed| {
+            let mut settings = ExploitSettings::default();
+            settings.evaluator.rules = rules;
+
+            ExploitAgent::new(
+                GreedyAgent::default().with_rules(rules),
+                settings,
+                StdRng::seed_from_u64(seed),
+            )
+        }),
+        ("ismcts", iterations) => {
+            let iterations = match iterations {
+                Some(iterations) => iterations
+                    .parse()
+                    .map_err(|_| format!("Invalid iteration count in {spec:?}"))?,
+                None => 1000,
+            };
+
+            boxed(move |seed| {
+                IsmctsAgent::new(iterations, Bandit::default(), StdRng::seed_from_u64(seed))
+                    .with_rules(rules)
+            })
+        }
+        ("endgame", inner) => {
+            let inner = agent_factory(inner.unwrap_or("greedy"), rules)?;
+
+            boxed(move |seed| {
+                EndgameAgent::new(
+                    inner(seed),
+                    EndgameSolver::new(rules),
+                    StdRng::seed_from_u64(seed),
+                )
+            })
+        }
+        ("lastturn", inner) => {
+            let inner = agent_factory(inner.unwrap_or("greedy"), rules)?;
+
+            boxed(move |seed| {
+                EndgameAgent::new(
+                    inner(seed),
+                    LastTurnSolver::new(rules),
+                    StdRng::seed_from_u64(seed),
+                )
+            })
+        }
+        ("engine", Some(command)) => {
+            let mut words = command.split_whitespace().map(String::from);
+            let program = words
+                .next()
+                .ok_or_else(|| format!("Missing engine command in {spec:?}"))?;
+            let args: Vec<_> = words.collect();
+
+            // Make sure the engine starts at all before any game gets played
+            EngineAgent::spawn(Command::new(&program).args(&args))
+                .map_err(|error| format!("Could not start engine {program:?}: {error}"))?;
+
+            boxed(move |_| {
+                EngineAgent::spawn(Command::new(&program).args(&args))
+                    .expect("The engine has already been started successfully")
+            })
+        }
+        _ => return Err(format!("Unknown agent {spec:?}")),
+    };
+
+    Ok(factory)
+}
+// }}}
+// {{{ Generation commands
+fn estimate(mut args: Arguments) -> Result<(), String> {
+    let position = PositionOptions::parse(&mut args)?;
+    args.finish()?;
+
+    let start = Instant::now();
+    let stats =
+        EstimationContext::new(position.depth, position.state, position.tree_rules()).estimate();
+    println!("Estimation: {:?}", start.elapsed());
+    println!("{stats:#?}");
+
+    Ok(())
+}
+
+fn generate(mut args: Arguments) -> Result<(), String> {
+    let position = PositionOptions::parse(&mut args)?;
+    args.finish()?;
+
+    let stats =
+        EstimationContext::new(position.depth, position.state, position.tree_rules()).estimate();
+    println!("{stats:#?}");
+
+    let allocator = position.allocator();
+    position.generate(&allocator);
     println!(
-        "Remaining capacity: {:?}MB",
+        "Remaining capacity: {}MB",
         b_to_mb(allocator.chunk_capacity())
     );
-    println!("{stats:#?}");
+
+    Ok(())
 }
 // }}}
-// {{{ Simple training routine
-fn simple_trainig() {
-    // {{{ State creation
-    let mut state = KnownState::new_starting([Battlefield::Plains; 4]);
-    state.battlefields.current = 2;
-    for creature in Creature::CREATURES.into_iter().take(4) {
-        state.graveyard.insert(creature);
-    }
-
-    for state in state.player_states.iter_mut() {
-        for edict in Edict::EDICTS.into_iter().take(2) {
-            state.edicts.remove(edict);
-        }
-    }
-    // }}}
-    // {{{ Generation
-    let allocator = Bump::new();
-    let generator = GenerationContext::new(2, state, &allocator);
-    let mut scope = generator.generate();
-    // }}}
-    // {{{ Training
-    let ctx = TrainingContext::new(false);
-    let mut _rng = rand::thread_rng();
-    ctx.cfr(&mut scope, state.to_summary(), 10000);
-    // ctx.cs_cfr(&mut rng, &mut scope, state.to_summary(), 100000);
-    // }}}
-    // {{{ Displaying
-    let player = Player::Me;
-    let hand = (!state.graveyard)
-        .subsets_of_size(state.hand_size())
-        .next()
-        .unwrap();
-    let hidden_index = HiddenIndex::encode(&state, player, PerPhaseInfo::Main(hand));
-    let vector = scope
-        .get_explored()
-        .unwrap()
-        .matrices
-        .get_matrix(player)
-        .get_node(hidden_index)
-        .unwrap();
+// {{{ Training commands
+/// Lists the average strategy of the first player for every possible hand.
+fn describe_root_strategy(scope: &Scope, state: &KnownState, rules: RuleSet) -> String {
+    let mut output = String::new();
+    let phase = PerPhase::Main(MainPhase::new());
 
-    println!("{:?}", vector.strategy_sum);
-    println!("{:?}", vector.regret_sum);
-    let strategy = vector.get_average_strategy();
-    for index in 0..vector.len() {
-        let decision = DecisionIndex(index);
-        let decoded = decision
-            .decode_main_phase_index(&state, player, hand)
-            .unwrap();
-        let probability = strategy[index];
+    for hand in (!state.graveyard).subsets_of_size(state.hand_size()) {
+        let hidden = HiddenState::new(hand, None);
+        let Ok(actions) = query_strategy(scope, *state, phase, rules, &[], Player::Me, hidden)
+        else {
+            continue;
+        };
 
-        println!("Probability: {probability}. Action: {decoded:?}");
+        writeln!(output, "Hand {}", hand.into_iter().join(",")).unwrap();
+        for action in actions {
+            writeln!(output, "  {:.4} {}", action.probability, action.choice).unwrap();
+        }
     }
-    // }}}
+
+    output
+}
+
+fn train(mut args: Arguments) -> Result<(), String> {
+    let position = PositionOptions::parse(&mut args)?;
+    let training = TrainingOptions::parse(&mut args)?;
+    let output = args.raw("output")?;
+    let export = args.raw("export")?;
+    let format = args.get("format", ExportFormat::Csv)?;
+    args.finish()?;
+
+    let allocator = position.allocator();
+    let mut scope = position.generate(&allocator);
+    training.train(&mut scope, &position.state);
+
+    if let Some(path) = export {
+        let file = std::fs::File::create(&path).map_err(|error| format!("{path}: {error}"))?;
+        let phase = PerPhase::Main(MainPhase::new());
+        let rows = export_policy(
+            &scope,
+            position.state,
+            phase,
+            position.tree_rules(),
+            format,
+            std::io::BufWriter::new(file),
+        )
+        .map_err(|error| format!("{path}: {error}"))?;
+
+        println!("Exported {rows} policy rows to {path}");
+    }
+
+    let description = describe_root_strategy(&scope, &position.state, position.tree_rules());
+    match output {
+        Some(path) => {
+            std::fs::write(&path, description).map_err(|error| format!("{path}: {error}"))?
+        }
+        None => print!("{description}"),
+    }
+
+    Ok(())
+}
+
+/// Trains a blueprint, and plays it against some other agent.
+/// Once the blueprint runs out of tree, the greedy agent takes over.
+fn evaluate(mut args: Arguments) -> Result<(), String> {
+    let position = PositionOptions::parse(&mut args)?;
+    let training = TrainingOptions::parse(&mut args)?;
+    let opponent = agent_factory(
+        &args.get("opponent", String::from("greedy"))?,
+        position.rules,
+    )?;
+    let games = args.get("games", 1000)?;
+    args.finish()?;
+
+    let allocator = position.allocator();
+    let mut scope = position.generate(&allocator);
+    training.train(&mut scope, &position.state);
+
+    let state = position.state;
+    let main_phase = MainPhase::new();
+    let hidden_states: Vec<_> = main_phase.valid_hidden_states(state.to_summary()).collect();
+    let mut rng = StdRng::seed_from_u64(training.seed);
+    let mut report = MatchReport::default();
+
+    for _ in 0..games {
+        let hidden_state = hidden_states[rng.gen_range(0..hidden_states.len())];
+        let blueprint_agent = CfrAgent::new(
+            ScopeCursor::new(&scope, state, PerPhase::Main(main_phase)),
+            ActionSelection::Sample,
+            Fallback::Agent(GreedyAgent::default().with_rules(position.rules)),
+            StdRng::seed_from_u64(rng.gen()),
+        )
+        .with_rules(position.rules);
+        let agents = (blueprint_agent, opponent(rng.gen()));
+        let runner = EchoRunner::new(state, PerPhase::Main(main_phase), agents, hidden_state)
+            .with_rules(position.rules);
+
+        report.record(runner.run_game_with_score());
+    }
+
+    println!("Blueprint vs opponent\n{report}");
+
+    Ok(())
+}
+
+/// Trains two blueprints from the same position, and reports
+/// where (and by how much) their strategies differ.
+fn diff(mut args: Arguments) -> Result<(), String> {
+    let position = PositionOptions::parse(&mut args)?;
+    let first = TrainingOptions::parse(&mut args)?;
+    let second = first.parse_other(&mut args)?;
+    let top = args.get("top", 10)?;
+    args.finish()?;
+
+    let [first_allocator, second_allocator] = [position.allocator(), position.allocator()];
+    let mut first_scope = position.generate(&first_allocator);
+    let mut second_scope = position.generate(&second_allocator);
+    first.train(&mut first_scope, &position.state);
+    second.train(&mut second_scope, &position.state);
+
+    let phase = PerPhase::Main(MainPhase::new());
+    let diff = diff_strategies(
+        &first_scope,
+        &second_scope,
+        position.state,
+        phase,
+        position.tree_rules(),
+        top,
+    );
+    print!("{diff}");
+
+    Ok(())
+}
+// }}}
+// {{{ Match commands
+fn play_match(mut args: Arguments) -> Result<(), String> {
+    let first_name = args.get("first", String::from("greedy"))?;
+    let second_name = args.get("second", String::from("random"))?;
+    let rules = parse_rules(&mut args)?;
+    let first = agent_factory(&first_name, rules)?;
+    let second = agent_factory(&second_name, rules)?;
+    let games = args.get("games", 1000)?;
+    let seed = args.get("seed", 0)?;
+    let duplicate = args.flag("duplicate")?;
+    args.finish()?;
+
+    println!("{first_name} vs {second_name}");
+    if duplicate {
+        let report = DuplicateRunner::new(first, second, games)
+            .with_seed(seed)
+            .with_rules(rules)
+            .run();
+        println!("{report}");
+    } else {
+        let report = MatchRunner::new(first, second, games)
+            .with_seed(seed)
+            .with_rules(rules)
+            .run();
+        println!("{report}");
+    }
+
+    Ok(())
+}
+
+fn tournament(mut args: Arguments) -> Result<(), String> {
+    let names = args.get("agents", String::from("random,zero,greedy"))?;
+    let deals = args.get("games", 200)?;
+    let seed = args.get("seed", 0)?;
+    let path = args.get("output", String::from("tournament.txt"))?;
+    let rules = parse_rules(&mut args)?;
+    args.finish()?;
+
+    let results = match std::fs::read_to_string(&path) {
+        Ok(contents) => contents
+            .parse()
+            .map_err(|error| format!("{path}: {error}"))?,
+        Err(_) => TournamentResults::default(),
+    };
+
+    let mut tournament = Tournament::new(deals)
+        .with_seed(seed)
+        .with_rules(rules)
+        .with_results(results);
+    for name in names.split(',') {
+        tournament.register(name, agent_factory(name, rules)?);
+    }
+
+    let played = tournament.run();
+    println!("Played {played} new pairings");
+
+    std::fs::write(&path, tournament.results().to_string())
+        .map_err(|error| format!("{path}: {error}"))?;
+
+    println!("{}", tournament.results().leaderboard());
+    println!("{}", tournament.results().matrix());
+
+    Ok(())
+}
+// }}}
+// {{{ Play commands
+fn play(mut args: Arguments) -> Result<(), String> {
+    let interface = args.positional(1, "gui|terminal")?.to_string();
+    let rules = parse_rules(&mut args)?;
+    let opponent = agent_factory(&args.get("opponent", String::from("greedy"))?, rules)?;
+    args.finish()?;
+
+    let seed = thread_rng().gen();
+    let deal = Deal::random(&mut thread_rng());
+
+    match interface.as_str() {
+        "terminal" => {
+            deal.play((TerminalAgent::default(), opponent(seed)), rules);
+        }
+        "gui" => play_gui(deal, &opponent, seed, rules),
+        _ => return Err(format!("Unknown interface {interface:?}")),
+    }
+
+    Ok(())
 }
 // }}}
-// {{{ Simple gui routine
-fn show_gui() {
+// {{{ Engine & network commands
+fn engine(mut args: Arguments) -> Result<(), String> {
+    let name = args.get("agent", String::from("greedy"))?;
+    let rules = parse_rules(&mut args)?;
+    let agent = agent_factory(&name, rules)?(args.get("seed", 0)?);
+    args.finish()?;
+
+    let stdin = std::io::stdin();
+    let stdout = std::io::stdout();
+    serve(&name, agent, stdin.lock(), stdout.lock()).map_err(|error| error.to_string())
+}
+
+/// Hosts games on the given address. With a single remote seat,
+/// the other seat is taken by the opponent.
+fn host(mut args: Arguments) -> Result<(), String> {
+    let address = args.get("address", String::from("127.0.0.1:7878"))?;
+    let remote_seats = args.get("remote", 2)?;
+    let rules = parse_rules(&mut args)?;
+    let opponent = agent_factory(&args.get("opponent", String::from("greedy"))?, rules)?;
+    let games = args.get("games", 1)?;
+    let seed = args.get("seed", 0)?;
+    args.finish()?;
+
+    let server = GameServer::bind(&address).map_err(|error| format!("{address}: {error}"))?;
+    println!("Listening on {}", server.local_addr().unwrap());
+
+    let mut rng = StdRng::seed_from_u64(seed);
+    let deals: Vec<_> = (0..games).map(|_| Deal::random(&mut rng)).collect();
+    let first = server.accept().map_err(|error| error.to_string())?;
+
+    let scores = if remote_seats >= 2 {
+        let second = server.accept().map_err(|error| error.to_string())?;
+        GameServer::host_games((first, second), deals, rules)
+    } else {
+        GameServer::host_games((first, opponent(seed)), deals, rules)
+    };
+
+    for score in scores {
+        println!("{:?}", score.map(|score| score.to_battle_result()));
+    }
+
+    Ok(())
+}
+
+fn connect_seat(mut args: Arguments) -> Result<(), String> {
+    let address = args.get("address", String::from("127.0.0.1:7878"))?;
+    let name = args.get("agent", String::from("gui"))?;
+    let seed = args.get("seed", 0)?;
+    let rules = parse_rules(&mut args)?;
+    args.finish()?;
+
+    match name.as_str() {
+        "gui" => connect_gui(address, rules)?,
+        "terminal" => connect(&address, "terminal", TerminalAgent::default())
+            .map_err(|error| format!("{address}: {error}"))?,
+        _ => connect(&address, &name, agent_factory(&name, rules)?(seed))
+            .map_err(|error| format!("{address}: {error}"))?,
+    }
+
+    Ok(())
+}
+// }}}
+// {{{ Record verification command
+fn verify_records(args: Arguments) -> Result<(), String> {
+    let path = args.positional(1, "path")?.to_string();
+    args.finish()?;
+
+    let contents = std::fs::read_to_string(&path).map_err(|error| format!("{path}: {error}"))?;
+    let records = parse_records(&contents).map_err(|error| format!("{path}: {error}"))?;
+
+    let mut valid = 0;
+    for (index, record) in records.iter().enumerate() {
+        match verify(record) {
+            Ok(()) => valid += 1,
+            Err(error) => println!("Record {index}: {error}"),
+        }
+    }
+
+    println!("{valid}/{} records replayed cleanly", records.len());
+
+    Ok(())
+}
+// }}}
+// {{{ JSON conversion command
+fn convert(mut args: Arguments) -> Result<(), String> {
+    let position = args.raw("position")?;
+    let output = args.raw("output")?;
+
+    let json = match position {
+        Some(input) => {
+            let position = Position::parse(&input).map_err(|error| error.to_string())?;
+            serde_json::to_string_pretty(&position)
+        }
+        None => {
+            let path = args.positional(1, "path")?;
+            let contents =
+                std::fs::read_to_string(path).map_err(|error| format!("{path}: {error}"))?;
+            let records = parse_records(&contents).map_err(|error| format!("{path}: {error}"))?;
+            serde_json::to_string_pretty(&records)
+        }
+    }
+    .map_err(|error| error.to_string())?;
+    args.finish()?;
+
+    match output {
+        Some(path) => std::fs::write(&path, json).map_err(|error| format!("{path}: {error}"))?,
+        None => println!("{json}"),
+    }
+
+    Ok(())
+}
+// }}}
+// {{{ Gui routines
+/// Plays a single deal against the given opponent using the gui.
+fn play_gui(deal: Deal, opponent: &AgentFactory, seed: u64, rules: RuleSet) {
+    let (human_agent, bus) = HumanAgent::create();
+
+    // Agents are not necessarily `Send`, so the opponent
+    // gets created on the thread the game is played on.
+    thread::scope(|scope| {
+        scope.spawn(|| {
+            let result = deal.play((human_agent, opponent(seed)), rules);
+            println!("{:?}", result.map(|score| score.to_battle_result()));
+        });
+
+        let options = eframe::NativeOptions::default();
+        eframe::run_native(
+            "million prescient trees",
+            options,
+            Box::new(move |cc| Box::new(GUIApp::new(cc, bus, rules))),
+        )
+        .unwrap();
+    });
+}
+
+/// Connects the gui to a seat on a remote game server.
+/// Connection errors are reported once the window gets closed.
+fn connect_gui(address: String, rules: RuleSet) -> Result<(), String> {
     let (human_agent, bus) = HumanAgent::create();
 
-    let handle = thread::spawn(|| {
-        let random_agent = RandomAgent::new(thread_rng());
-        let always_zero_agent = AlwaysZeroAgent::default();
-        let opponent_agent = random_agent;
-
-        let battlefields = [
-            Battlefield::Night,
-            Battlefield::Glade,
-            Battlefield::Urban,
-            Battlefield::LastStrand,
-        ];
-
-        let state = KnownState::new_starting(battlefields);
-        let main_phase = echo::cfr::phase::MainPhase::new();
-        let phase = echo::cfr::phase::PerPhase::Main(main_phase);
-        let agents = (human_agent, opponent_agent);
-        let hidden_state = main_phase
-            .valid_hidden_states(state.to_summary())
-            .next()
-            .unwrap();
-        let runner = EchoRunner::new(state, phase, agents, hidden_state);
-        let result = runner.run_game();
-        println!("{result:?}");
+    let handle = thread::spawn(move || {
+        connect(&address, "human", human_agent).map_err(|error| format!("{address}: {error}"))
     });
 
     let options = eframe::NativeOptions::default();
     eframe::run_native(
         "million prescient trees",
         options,
-        Box::new(|cc| Box::new(GUIApp::new(cc, bus))),
+        Box::new(move |cc| Box::new(GUIApp::new(cc, bus, rules))),
     )
     .unwrap();
 
-    handle.join().unwrap();
+    handle
+        .join()
+        .map_err(|_| String::from("The connection to the server crashed"))?
 }
 // }}}
 
+fn run(args: Arguments) -> Result<(), String> {
+    let command = args.positional.first().cloned().unwrap_or_default();
+
+    match command.as_str() {
+        "estimate" => estimate(args),
+        "generate" => generate(args),
+        "train" => train(args),
+        "evaluate" => evaluate(args),
+        "diff" => diff(args),
+        "play" => play(args),
+        "match" => play_match(args),
+        "tournament" => tournament(args),
+        "engine" => engine(args),
+        "host" => host(args),
+        "connect" => connect_seat(args),
+        "verify" => verify_records(args),
+        "convert" => convert(args),
+        "help" | "" => {
+            print!("{USAGE}");
+            Ok(())
+        }
+        _ => Err(format!("Unknown command {command:?}")),
+    }
+}
+
 fn main() {
     let filter = tracing_subscriber::filter::Targets::new()
         .with_target("winit", Level::ERROR)
-        .with_target("echo", Level::TRACE);
+        .with_target("echo", Level::INFO);
 
     tracing_subscriber::registry()
-        .with(tracing_subscriber::fmt::layer().compact())
+        .with(
+            tracing_subscriber::fmt::layer()
+                .compact()
+                .with_writer(std::io::stderr),
+        )
         .with(filter)
         .init();
 
-    show_gui();
-    // simple_generation(2, 2, false);
+    let result = Arguments::parse(std::env::args().skip(1)).and_then(run);
+
+    if let Err(error) = result {
+        eprintln!("error: {error}\n");
+        eprint!("{USAGE}");
+        std::process::exit(2);
+    }
 }
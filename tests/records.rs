@@ -0,0 +1,29 @@
+//! Replays every game record in `tests/records`, making sure
+//! the rule engine still agrees with what happened in them.
+
+use echo::ai::replay::{parse_records, verify};
+use std::fs;
+use std::path::Path;
+
+#[test]
+fn record_corpus_replays_cleanly() {
+    let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/records");
+    let mut count = 0;
+
+    for entry in fs::read_dir(directory).unwrap() {
+        let path = entry.unwrap().path();
+        let contents = fs::read_to_string(&path).unwrap();
+        let records =
+            parse_records(&contents).unwrap_or_else(|error| panic!("{}: {error}", path.display()));
+
+        for (index, record) in records.iter().enumerate() {
+            if let Err(error) = verify(record) {
+                panic!("{}, record {index}: {error}", path.display());
+            }
+        }
+
+        count += records.len();
+    }
+
+    assert!(count > 0);
+}